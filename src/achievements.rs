@@ -0,0 +1,329 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::locale::Strings;
+use crate::store;
+use crate::theme::Theme;
+use crate::tween::{Ease, Tween, TweenCompleted, TweenTarget};
+use crate::{Game, GameEvent, GameState, Planet, Ship, Stats, FONT_PATH};
+
+pub const ACHIEVEMENTS_PATH: &str = "achievements.json";
+const ACHIEVEMENTS_VERSION: u32 = 1;
+
+const ACHIEVEMENT_SURVIVOR_TIME_MS: u64 = 5 * 60 * 1000;
+const ACHIEVEMENT_STEADY_HAND_COUNT: u32 = 100;
+const ACHIEVEMENT_LIGHT_TOUCH_SCORE: u32 = 10000;
+const ACHIEVEMENT_LIGHT_TOUCH_MASS: f32 = 2000.0;
+const ACHIEVEMENT_ORBIT_MAX_STEP: f32 = std::f32::consts::FRAC_PI_4; // larger jumps are wraps or teleports, not flight
+
+const ACHIEVEMENT_TOAST_TIME: f32 = 4.0;
+const ACHIEVEMENT_TOAST_FADE: f32 = 1.0;
+const ACHIEVEMENT_TOAST_SIZE: f32 = 22.0;
+const ACHIEVEMENT_TOAST_Y: f32 = -300.0;
+const ACHIEVEMENT_TOAST_SPACING: f32 = 30.0;
+
+const ACHIEVEMENT_VIEW_TITLE_SIZE: f32 = 60.0;
+const ACHIEVEMENT_VIEW_SIZE: f32 = 22.0;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum Achievement {
+    Survivor,
+    SteadyHand,
+    BlindOrbit,
+    LightTouch,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 4] = [
+        Achievement::Survivor,
+        Achievement::SteadyHand,
+        Achievement::BlindOrbit,
+        Achievement::LightTouch,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Achievement::Survivor => "survivor",
+            Achievement::SteadyHand => "steady_hand",
+            Achievement::BlindOrbit => "blind_orbit",
+            Achievement::LightTouch => "light_touch",
+        }
+    }
+
+    pub fn name(&self, strings: &Strings) -> String {
+        strings.get(&format!("achievement.{}.name", self.id())).to_string()
+    }
+
+    pub fn description(&self, strings: &Strings) -> String {
+        strings.get(&format!("achievement.{}.description", self.id())).to_string()
+    }
+
+    fn from_id(id: &str) -> Option<Achievement> {
+        Achievement::ALL.iter().copied().find(|achievement| achievement.id() == id)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Achievements {
+    unlocked: Vec<Achievement>
+}
+
+impl Achievements {
+    pub fn load(path: &str) -> Self {
+        let ids: Vec<String> = store::load(path, ACHIEVEMENTS_VERSION);
+        Self {
+            unlocked: ids.iter().filter_map(|id| Achievement::from_id(id)).collect()
+        }
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let ids: Vec<String> = self.unlocked.iter().map(|achievement| achievement.id().to_string()).collect();
+        store::save(path, ACHIEVEMENTS_VERSION, &ids)
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    // returns true if this is the first time the achievement was unlocked
+    pub fn unlock(&mut self, achievement: Achievement) -> bool {
+        if self.is_unlocked(achievement) {
+            return false;
+        }
+        self.unlocked.push(achievement);
+        true
+    }
+}
+
+// per-run progress towards achievements that depend on more than the current stats
+#[derive(Resource, Default)]
+pub struct AchievementTracker {
+    asteroids_since_teleport: u32,
+    orbits: HashMap<usize, Orbit>     // by player, every ship circles the star on its own
+}
+
+#[derive(Default)]
+struct Orbit {
+    angle: f32,
+    last_direction: Option<Vec2>
+}
+
+impl AchievementTracker {
+    fn orbit_completed(&self) -> bool {
+        self.orbits.values().any(|orbit| orbit.angle.abs() >= 2.0 * std::f32::consts::PI)
+    }
+}
+
+pub struct AchievementUnlocked(pub Achievement);
+
+#[derive(Component)]
+pub struct AchievementToast;
+
+pub fn reset_achievement_tracker(mut tracker: ResMut<AchievementTracker>) {
+    *tracker = AchievementTracker::default();
+}
+
+pub fn track_orbit(ship_query: Query<(&Ship, &Transform)>, planet_query: Query<&Transform, With<Planet>>, mut tracker: ResMut<AchievementTracker>, game: Res<Game>) {
+    let Ok(planet_transform) = planet_query.get_single() else {
+        tracker.orbits.clear();
+        return;
+    };
+    if game.draw_trajectory {
+        tracker.orbits.clear();
+        return;
+    }
+
+    // a ship that is lost starts its orbit over if it comes back
+    tracker.orbits.retain(|player, _| ship_query.iter().any(|(ship, _)| ship.player == *player));
+    for (ship, ship_transform) in &ship_query {
+        let direction = (ship_transform.translation - planet_transform.translation).truncate().normalize_or_zero();
+        if direction == Vec2::ZERO {
+            tracker.orbits.remove(&ship.player);
+            continue;
+        }
+
+        let orbit = tracker.orbits.entry(ship.player).or_default();
+        if let Some(last_direction) = orbit.last_direction {
+            let step = last_direction.angle_between(direction);
+            if step.abs() > ACHIEVEMENT_ORBIT_MAX_STEP {
+                orbit.angle = 0.0;
+            } else {
+                orbit.angle += step;
+            }
+        }
+        orbit.last_direction = Some(direction);
+    }
+}
+
+pub fn check_achievements(mut events: EventReader<GameEvent>, mut unlocked_events: EventWriter<AchievementUnlocked>, mut achievements: ResMut<Achievements>, mut tracker: ResMut<AchievementTracker>, game: Res<Game>, stats: Res<Stats>) {
+    let mut earned = Vec::new();
+
+    for event in events.iter() {
+        match event {
+            GameEvent::AsteroidDestroyed => {
+                tracker.asteroids_since_teleport += 1;
+                if tracker.asteroids_since_teleport >= ACHIEVEMENT_STEADY_HAND_COUNT {
+                    earned.push(Achievement::SteadyHand);
+                }
+                if game.score >= ACHIEVEMENT_LIGHT_TOUCH_SCORE && stats.peak_planet_mass < ACHIEVEMENT_LIGHT_TOUCH_MASS {
+                    earned.push(Achievement::LightTouch);
+                }
+            }
+            GameEvent::Teleported { player } => {
+                tracker.asteroids_since_teleport = 0;
+                tracker.orbits.remove(player);
+            }
+        }
+    }
+
+    if game.time >= ACHIEVEMENT_SURVIVOR_TIME_MS {
+        earned.push(Achievement::Survivor);
+    }
+    if tracker.orbit_completed() {
+        earned.push(Achievement::BlindOrbit);
+    }
+
+    let mut changed = false;
+    for achievement in earned {
+        if achievements.unlock(achievement) {
+            changed = true;
+            unlocked_events.send(AchievementUnlocked(achievement));
+        }
+    }
+
+    if changed {
+        if let Err(err) = achievements.save(ACHIEVEMENTS_PATH) {
+            warn!("could not save achievements: {}", err);
+        }
+    }
+}
+
+pub fn spawn_toasts(mut commands: Commands, mut events: EventReader<AchievementUnlocked>, asset_server: Res<AssetServer>, toast_query: Query<&AchievementToast>, strings: Res<Strings>, theme: Res<Theme>) {
+    let toast_count = toast_query.iter().count();
+    for (i, AchievementUnlocked(achievement)) in events.iter().enumerate() {
+        let text_style = TextStyle {
+            font: asset_server.load(FONT_PATH),
+            font_size: ACHIEVEMENT_TOAST_SIZE,
+            color: theme.toast,
+        };
+        commands.spawn(
+            (Text2dBundle {
+                text: Text::from_section(strings.format("achievements.unlocked", &[&achievement.name(&strings)]), text_style)
+                    .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_xyz(0.0, ACHIEVEMENT_TOAST_Y + ACHIEVEMENT_TOAST_SPACING * (toast_count + i) as f32, 1.0),
+                ..default()
+            },
+            AchievementToast,
+            Tween::new(TweenTarget::TextAlpha { from: 1.0, to: 0.0 }, Ease::Linear, ACHIEVEMENT_TOAST_FADE).with_delay(ACHIEVEMENT_TOAST_TIME - ACHIEVEMENT_TOAST_FADE))
+        );
+    }
+}
+
+pub fn despawn_toasts(mut commands: Commands, mut events: EventReader<TweenCompleted>, query: Query<(), With<AchievementToast>>) {
+    for TweenCompleted(entity) in events.iter() {
+        if query.contains(*entity) {
+            commands.entity(*entity).despawn();
+        }
+    }
+}
+
+pub fn setup_achievements_view(mut commands: Commands, asset_server: Res<AssetServer>, achievements: Res<Achievements>, strings: Res<Strings>, theme: Res<Theme>) {
+    let font = asset_server.load(FONT_PATH);
+    let text_style = TextStyle {
+        font,
+        font_size: ACHIEVEMENT_VIEW_TITLE_SIZE,
+        color: theme.title,
+    };
+    let text_alignment = TextAlignment::CENTER;
+
+    commands.spawn(
+        Text2dBundle {
+            text: Text::from_section(strings.get("achievements.title"), text_style)
+                .with_alignment(text_alignment),
+            transform: Transform::from_xyz(0.0, 200.0, 0.0),
+            ..default()
+        }
+    );
+
+    for (i, achievement) in Achievement::ALL.iter().enumerate() {
+        let unlocked = achievements.is_unlocked(*achievement);
+        let text_style = TextStyle {
+            font: asset_server.load(FONT_PATH),
+            font_size: ACHIEVEMENT_VIEW_SIZE,
+            color: if unlocked { theme.text } else { theme.disabled },
+        };
+        commands.spawn(
+            Text2dBundle {
+                text: Text::from_section(strings.format("achievements.entry", &[&achievement.name(&strings), &achievement.description(&strings)]), text_style)
+                    .with_alignment(text_alignment),
+                transform: Transform::from_xyz(0.0, 100.0 - 50.0 * i as f32, 0.0),
+                ..default()
+            }
+        );
+    }
+}
+
+pub fn update_achievements_view(mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::Space) || keyboard_input.just_pressed(KeyCode::A) {
+        state.set(GameState::Title).unwrap();
+    }
+}
+
+pub fn teardown_achievements_view(mut commands: Commands, entities: Query<Entity, With<Text>>) {
+    for entity in &entities {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORBIT_STEPS: usize = 64;
+
+    // the second of two ships circles the star once while the first sits still
+    #[test]
+    fn any_ship_can_complete_the_orbit() {
+        let mut world = World::new();
+        let mut game = Game::new();
+        game.players = 2;
+        world.insert_resource(game);
+        world.init_resource::<AchievementTracker>();
+        world.spawn((Planet::new(), Transform::default()));
+        world.spawn((Ship::new(0), Transform::from_xyz(0.0, 200.0, 0.0)));
+        let orbiter = world.spawn((Ship::new(1), Transform::from_xyz(200.0, 0.0, 0.0))).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(track_orbit);
+
+        // a step past the full turn so rounding can't leave it a hair short
+        for step in 0..=ORBIT_STEPS + 1 {
+            assert!(!world.resource::<AchievementTracker>().orbit_completed(), "before step {}", step);
+            let angle = 2.0 * std::f32::consts::PI * step as f32 / ORBIT_STEPS as f32;
+            world.get_mut::<Transform>(orbiter).unwrap().translation = Vec3::new(angle.cos(), angle.sin(), 0.0) * 200.0;
+            stage.run(&mut world);
+        }
+        assert!(world.resource::<AchievementTracker>().orbit_completed());
+    }
+
+    #[test]
+    fn teleporting_starts_only_that_ships_orbit_over() {
+        let mut tracker = AchievementTracker::default();
+        tracker.orbits.insert(0, Orbit { angle: 7.0, last_direction: Some(Vec2::X) });
+        tracker.orbits.insert(1, Orbit { angle: 3.0, last_direction: Some(Vec2::X) });
+        let mut world = World::new();
+        world.insert_resource(tracker);
+        world.insert_resource(Game::new());
+        world.insert_resource(Stats::new());
+        world.init_resource::<Events<GameEvent>>();
+        world.init_resource::<Events<AchievementUnlocked>>();
+        world.insert_resource(Achievements { unlocked: vec![Achievement::BlindOrbit] });
+        world.send_event(GameEvent::Teleported { player: 1 });
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(check_achievements);
+        stage.run(&mut world);
+
+        let tracker = world.resource::<AchievementTracker>();
+        assert!(tracker.orbits.contains_key(&0));
+        assert!(!tracker.orbits.contains_key(&1));
+    }
+}
@@ -0,0 +1,342 @@
+use bevy::prelude::*;
+
+use crate::particles::{Particle, ParticleEmitter, SHIP_TRAIL};
+use crate::plasma::Plasma;
+use crate::theme::Theme;
+use crate::{
+    AngularVelocity, Asteroid, Bullet, Enemy, GravityVis, Mass, Planet, Radius, Ship, ShipInput, Star, Trajectory, Tuning, Velocity,
+    PLANET_START_MASS, PLANET_START_RADIUS, PLAYER_START_DISTANCE, SHIP_MASS, SHIP_RADIUS, SHIP_TRAIL_ALPHA, TRAJECTORY_STEP,
+};
+
+const AUTOPILOT_AIM_TOLERANCE: f32 = 0.05;   // radians of heading error before turning
+const AUTOPILOT_TURN_LOOKAHEAD: f32 = 0.15;  // seconds of angular velocity to anticipate so turns don't overshoot
+const AUTOPILOT_THRUST_CONE: f32 = 0.5;      // only thrust when roughly facing the way it wants to go
+const AUTOPILOT_PLAN_TIME: f32 = 3.0;        // seconds of trajectory checked for a planet impact
+const AUTOPILOT_SAFE_ALTITUDE: f32 = 50.0;
+const AUTOPILOT_ORBIT_ALTITUDE: f32 = 180.0;
+const AUTOPILOT_ORBIT_TOLERANCE: f32 = 20.0; // speed error tolerated before correcting the orbit
+const AUTOPILOT_ORBIT_RADIAL_GAIN: f32 = 0.2; // how hard to push back towards the orbit altitude per unit of error
+const AUTOPILOT_DODGE_TIME: f32 = 1.5;       // seconds ahead to look for hazards on a collision course
+const AUTOPILOT_DODGE_MARGIN: f32 = 12.0;
+const AUTOPILOT_TELEPORT_TIME: f32 = 0.4;    // teleport away from hazards that will hit sooner than this
+const AUTOPILOT_FIRE_RANGE: f32 = 350.0;
+const AUTOPILOT_FIRE_CONE: f32 = 0.1;
+
+// a ship flown by the autopilot instead of a keyboard
+#[derive(Component)]
+pub struct Autopilot;
+
+pub struct BotShip {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub facing: Vec2,
+    pub angular_velocity: f32,
+    pub teleport_ready: bool,
+    pub bullet_velocity: f32
+}
+
+pub struct BotPlanet {
+    pub position: Vec2,
+    pub radius: f32,
+    pub mass: f32,
+    pub gravity: f32,
+    pub collapsing: bool
+}
+
+// anything worth shooting or avoiding
+pub struct BotHazard {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+    pub mass: f32
+}
+
+// gravity is applied as an acceleration of gravity * M * m / r^2, so this is the speed that balances it on a circle
+pub fn circular_orbit_speed(gravity: f32, planet_mass: f32, mass: f32, distance: f32) -> f32 {
+    (gravity * planet_mass * mass / distance).sqrt()
+}
+
+pub fn predict_position(position: Vec2, velocity: Vec2, mass: f32, planet: &BotPlanet, seconds: f32) -> Vec2 {
+    let steps = (seconds / TRAJECTORY_STEP).round() as usize;
+    if steps == 0 {
+        return position;
+    }
+    Trajectory::new(planet.gravity, position.extend(0.0), velocity, mass, planet.position.extend(0.0), planet.mass)
+        .nth(steps - 1)
+        .map_or(position, |predicted| predicted.truncate())
+}
+
+pub fn lowest_altitude(position: Vec2, velocity: Vec2, mass: f32, planet: &BotPlanet, seconds: f32) -> f32 {
+    let steps = (seconds / TRAJECTORY_STEP) as usize;
+    Trajectory::new(planet.gravity, position.extend(0.0), velocity, mass, planet.position.extend(0.0), planet.mass)
+        .take(steps)
+        .map(|predicted| Vec2::distance(predicted.truncate(), planet.position) - planet.radius)
+        .fold(f32::INFINITY, f32::min)
+}
+
+// the burn that would put a ship onto a circular orbit at the given altitude, keeping its direction of travel
+pub fn orbit_correction(position: Vec2, velocity: Vec2, mass: f32, planet: &BotPlanet, altitude: f32) -> Vec2 {
+    let offset = position - planet.position;
+    let orbit_distance = planet.radius + altitude;
+    let radial = offset.normalize_or_zero();
+    let tangent = if radial.perp().dot(velocity) >= 0.0 { radial.perp() } else { -radial.perp() };
+    let desired_velocity = tangent * circular_orbit_speed(planet.gravity, planet.mass, mass, orbit_distance)
+        + radial * (orbit_distance - offset.length()) * AUTOPILOT_ORBIT_RADIAL_GAIN;
+    desired_velocity - velocity
+}
+
+// burn outwards and along the current direction of travel to lift the orbit
+pub fn escape_direction(position: Vec2, velocity: Vec2, planet: &BotPlanet) -> Vec2 {
+    let outward = (position - planet.position).normalize_or_zero();
+    (outward + velocity.normalize_or_zero()).normalize_or_zero()
+}
+
+// sets the turn inputs towards the direction and returns the remaining heading error
+pub fn steer_towards(facing: Vec2, angular_velocity: f32, direction: Vec2, input: &mut ShipInput) -> f32 {
+    let error = facing.angle_between(direction);
+    let anticipated = error - angular_velocity * AUTOPILOT_TURN_LOOKAHEAD;
+    input.left = anticipated > AUTOPILOT_AIM_TOLERANCE;
+    input.right = anticipated < -AUTOPILOT_AIM_TOLERANCE;
+    error
+}
+
+fn burn_towards(ship: &BotShip, direction: Vec2, input: &mut ShipInput) {
+    let error = steer_towards(ship.facing, ship.angular_velocity, direction, input);
+    input.thrust = error.abs() < AUTOPILOT_THRUST_CONE;
+}
+
+// the soonest hazard on a collision course, with the time until it is closest and where it will be relative to the ship
+fn incoming_hazard(ship: &BotShip, hazards: &[BotHazard]) -> Option<(f32, Vec2)> {
+    hazards.iter()
+        .filter_map(|hazard| {
+            let relative_position = hazard.position - ship.position;
+            let relative_velocity = hazard.velocity - ship.velocity;
+            let closing = relative_velocity.length_squared();
+            let time = if closing > 0.0 { (-relative_position.dot(relative_velocity) / closing).clamp(0.0, AUTOPILOT_DODGE_TIME) } else { 0.0 };
+            let closest = relative_position + relative_velocity * time;
+            let miss = closest.length() - hazard.radius - SHIP_RADIUS;
+            (miss < AUTOPILOT_DODGE_MARGIN).then_some((time, closest))
+        })
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+}
+
+pub fn autopilot(ship: &BotShip, planet: &BotPlanet, hazards: &[BotHazard]) -> ShipInput {
+    let mut input = ShipInput::default();
+
+    let in_sights = |position: Vec2| {
+        let offset = position - ship.position;
+        offset.length() < AUTOPILOT_FIRE_RANGE && ship.facing.angle_between(offset).abs() < AUTOPILOT_FIRE_CONE
+    };
+    input.fire = hazards.iter().any(|hazard| in_sights(hazard.position));
+
+    if !planet.collapsing && lowest_altitude(ship.position, ship.velocity, SHIP_MASS, planet, AUTOPILOT_PLAN_TIME) < AUTOPILOT_SAFE_ALTITUDE {
+        burn_towards(ship, escape_direction(ship.position, ship.velocity, planet), &mut input);
+        return input;
+    }
+
+    if let Some((time, closest)) = incoming_hazard(ship, hazards) {
+        if time < AUTOPILOT_TELEPORT_TIME && ship.teleport_ready {
+            input.teleport = true;
+        } else if !input.fire {
+            burn_towards(ship, -closest.normalize_or_zero(), &mut input);
+        }
+        return input;
+    }
+
+    let correction = orbit_correction(ship.position, ship.velocity, SHIP_MASS, planet, AUTOPILOT_ORBIT_ALTITUDE);
+    if correction.length() > AUTOPILOT_ORBIT_TOLERANCE {
+        burn_towards(ship, correction.normalize(), &mut input);
+        return input;
+    }
+
+    let target = hazards.iter()
+        .filter(|hazard| Vec2::distance(hazard.position, ship.position) < AUTOPILOT_FIRE_RANGE)
+        .min_by(|a, b| Vec2::distance(a.position, ship.position).total_cmp(&Vec2::distance(b.position, ship.position)));
+    if let Some(target) = target {
+        let lead_time = Vec2::distance(target.position, ship.position) / ship.bullet_velocity;
+        let lead_position = predict_position(target.position, target.velocity, target.mass, planet, lead_time);
+        steer_towards(ship.facing, ship.angular_velocity, lead_position - ship.position, &mut input);
+        input.fire |= in_sights(lead_position);
+    }
+    input
+}
+
+type AutopilotQuery<'w, 's> = Query<'w, 's, (&'static Ship, &'static Transform, &'static Velocity, &'static AngularVelocity, &'static mut ShipInput), With<Autopilot>>;
+type HazardQuery<'w, 's> = Query<'w, 's, (&'static Transform, &'static Velocity, &'static Radius, &'static Mass), Or<(With<Asteroid>, With<Enemy>, With<Plasma>)>>;
+
+pub fn autopilot_input(mut query: AutopilotQuery, hazard_query: HazardQuery, planet_query: Query<(&Transform, &Radius, &Mass, &Planet)>, tuning: Res<Tuning>) {
+    let (planet_transform, Radius(planet_radius), Mass(planet_mass), planet) = planet_query.single();
+    let planet = BotPlanet {
+        position: planet_transform.translation.truncate(),
+        radius: *planet_radius,
+        mass: *planet_mass,
+        gravity: tuning.gravity,
+        collapsing: planet.collapsing
+    };
+    let hazards: Vec<BotHazard> = hazard_query.iter()
+        .map(|(transform, Velocity(velocity), Radius(radius), Mass(mass))| BotHazard {
+            position: transform.translation.truncate(),
+            velocity: *velocity,
+            radius: *radius,
+            mass: *mass
+        })
+        .collect();
+
+    for (ship, transform, Velocity(velocity), AngularVelocity(angular_velocity), mut input) in &mut query {
+        let bot_ship = BotShip {
+            position: transform.translation.truncate(),
+            velocity: *velocity,
+            facing: (transform.rotation * Vec3::Y).truncate(),
+            angular_velocity: *angular_velocity,
+            teleport_ready: ship.teleport_cooldown <= 0.0,
+            bullet_velocity: tuning.bullet_velocity
+        };
+        *input = autopilot(&bot_ship, &planet, &hazards);
+    }
+}
+
+const ATTRACT_RESTART_DELAY_MS: u64 = 3000;
+
+type AttractEntityQuery<'w, 's> = Query<'w, 's, Entity, Or<(With<Ship>, With<Planet>, With<Bullet>, With<Asteroid>, With<Enemy>, With<Plasma>, With<Particle>)>>;
+
+fn spawn_attract(commands: &mut Commands, theme: &Theme) {
+    let start = Vec3::new(0.0, PLAYER_START_DISTANCE, 0.0);
+    let mut trail_color = theme.players[0];
+    trail_color.set_a(SHIP_TRAIL_ALPHA);
+    commands.spawn((Ship::new(0),
+                    ShipInput::default(),
+                    Autopilot,
+                    Radius(SHIP_RADIUS),
+                    Mass(SHIP_MASS),
+                    Transform::from_translation(start),
+                    Velocity(Vec2::new(0.0, 0.0)),
+                    AngularVelocity(0.0),
+                    ParticleEmitter::new(&SHIP_TRAIL, Some(trail_color))));
+    commands.spawn((Planet::new(),
+                    Radius(PLANET_START_RADIUS),
+                    Mass(PLANET_START_MASS),
+                    Transform::from_xyz(0.0, 0.0, 0.0),
+                    GravityVis { radius: 0.0 } ));
+}
+
+// a demo game flown by the autopilot plays behind the title text
+pub fn setup_attract(mut commands: Commands, theme: Res<Theme>) {
+    spawn_attract(&mut commands, &theme);
+}
+
+pub fn restart_attract(mut commands: Commands, entities: AttractEntityQuery, ship_query: Query<&Ship>, planet_query: Query<&Planet>, mut over_time: Local<u64>, time: Res<Time>, theme: Res<Theme>) {
+    let over = ship_query.is_empty() || planet_query.iter().any(|planet| planet.collapsing);
+    if !over {
+        *over_time = 0;
+        return;
+    }
+
+    *over_time += time.delta().as_millis() as u64;
+    if *over_time >= ATTRACT_RESTART_DELAY_MS {
+        *over_time = 0;
+        for entity in &entities {
+            commands.entity(entity).despawn_recursive();
+        }
+        spawn_attract(&mut commands, &theme);
+    }
+}
+
+pub fn teardown_attract(mut commands: Commands, entities: AttractEntityQuery, star_query: Query<Entity, With<Star>>) {
+    for entity in entities.iter().chain(star_query.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::{Duration, Instant};
+
+    use super::*;
+    use crate::GRAVITY;
+
+    fn planet() -> BotPlanet {
+        BotPlanet { position: Vec2::ZERO, radius: PLANET_START_RADIUS, mass: PLANET_START_MASS, gravity: GRAVITY, collapsing: false }
+    }
+
+    fn ship(position: Vec2, velocity: Vec2, facing: Vec2) -> BotShip {
+        BotShip { position, velocity, facing, angular_velocity: 0.0, teleport_ready: true, bullet_velocity: 300.0 }
+    }
+
+    fn hazard(position: Vec2, velocity: Vec2) -> BotHazard {
+        BotHazard { position, velocity, radius: 10.0, mass: 1.0 }
+    }
+
+    #[test]
+    fn a_circular_orbit_keeps_its_altitude() {
+        let planet = planet();
+        let distance = planet.radius + AUTOPILOT_ORBIT_ALTITUDE;
+        let position = Vec2::new(distance, 0.0);
+        let velocity = Vec2::Y * circular_orbit_speed(planet.gravity, planet.mass, SHIP_MASS, distance);
+        for seconds in [0.5, 1.0, 2.0] {
+            let later = predict_position(position, velocity, SHIP_MASS, &planet, seconds);
+            assert!((later.length() - distance).abs() < distance * 0.02, "{} away after {}s", later.length(), seconds);
+        }
+        assert!(orbit_correction(position, velocity, SHIP_MASS, &planet, AUTOPILOT_ORBIT_ALTITUDE).length() < 1e-3);
+        assert!(lowest_altitude(position, velocity, SHIP_MASS, &planet, 2.0) > AUTOPILOT_ORBIT_ALTITUDE * 0.95);
+    }
+
+    #[test]
+    fn steering_turns_the_short_way_and_holds_when_on_target() {
+        let mut input = ShipInput::default();
+        steer_towards(Vec2::Y, 0.0, Vec2::NEG_X, &mut input);
+        assert!(input.left && !input.right);
+        steer_towards(Vec2::Y, 0.0, Vec2::X, &mut input);
+        assert!(input.right && !input.left);
+        let error = steer_towards(Vec2::Y, 0.0, Vec2::Y, &mut input);
+        assert!(!input.left && !input.right && error == 0.0);
+    }
+
+    #[test]
+    fn only_hazards_on_a_collision_course_are_incoming() {
+        let ship = ship(Vec2::new(0.0, 300.0), Vec2::ZERO, Vec2::Y);
+        assert!(incoming_hazard(&ship, &[hazard(Vec2::new(100.0, 300.0), Vec2::new(-100.0, 0.0))]).is_some());
+        assert!(incoming_hazard(&ship, &[hazard(Vec2::new(100.0, 300.0), Vec2::new(100.0, 0.0))]).is_none());
+        assert!(incoming_hazard(&ship, &[hazard(Vec2::new(100.0, 400.0), Vec2::new(-100.0, 0.0))]).is_none());
+    }
+
+    #[test]
+    fn the_autopilot_fires_ahead_and_teleports_out_of_a_hit() {
+        let planet = planet();
+        let distance = planet.radius + AUTOPILOT_ORBIT_ALTITUDE;
+        let position = Vec2::new(0.0, distance);
+        let velocity = Vec2::NEG_X * circular_orbit_speed(planet.gravity, planet.mass, SHIP_MASS, distance);
+        let target = hazard(position + Vec2::NEG_X * 200.0, velocity);
+        assert!(autopilot(&ship(position, velocity, Vec2::NEG_X), &planet, &[target]).fire);
+
+        let close = hazard(position + Vec2::X * 20.0, velocity + Vec2::NEG_X * 100.0);
+        assert!(autopilot(&ship(position, velocity, Vec2::NEG_X), &planet, &[close]).teleport);
+    }
+
+    #[test]
+    fn the_attract_demo_starts_over_once_its_ship_is_gone() {
+        let mut world = World::new();
+        let start = Instant::now();
+        let mut time = Time::new(start);
+        time.update_with_instant(start);
+        world.insert_resource(time);
+        world.insert_resource(crate::theme::CLASSIC);
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(restart_attract);
+        stage.add_system(setup_attract.with_run_criteria(bevy::ecs::schedule::ShouldRun::once));
+        stage.run(&mut world);
+        let ship = world.query_filtered::<Entity, With<Ship>>().single(&world);
+        world.despawn(ship);
+
+        let mut restarted_after = None;
+        for second in 1..=5 {
+            world.resource_mut::<Time>().update_with_instant(start + Duration::from_secs(second));
+            stage.run(&mut world);
+            if world.query::<&Ship>().iter(&world).count() == 1 {
+                restarted_after = Some(second * 1000);
+                break;
+            }
+        }
+        assert_eq!(restarted_after, Some(ATTRACT_RESTART_DELAY_MS));
+        assert_eq!(world.query::<&Planet>().iter(&world).count(), 1);
+    }
+}
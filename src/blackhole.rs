@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::theme::Theme;
+use crate::tween::{lerp_color, Ease, Tween, TweenTarget};
+use crate::vector::{VectorLines, VectorPath, LAYER_EFFECTS};
+use crate::{Game, Mass, Planet, Radius, PLANET_COLLAPSE_SIZE, PLANET_COLLAPSE_TIME_MS};
+
+pub const BLACK_HOLE_START_MASS: f32 = 5000.0;       // collapsing with the black hole phase on stops here instead of the fatal collapse mass
+pub const BLACK_HOLE_CONSUME_SCALE: f32 = 1.0;       // share of an eaten body's mass the hole gains
+const BLACK_HOLE_GROWTH_RATE: f32 = 40.0;            // mass gained per second even when nothing falls in
+const BLACK_HOLE_HORIZON_PER_MASS: f32 = 0.004;      // horizon radius grows linearly with mass, like a schwarzschild radius
+const BLACK_HOLE_FORM_TIME: f32 = 0.5;
+const BLACK_HOLE_MULTIPLIER_START: u32 = 2;
+const BLACK_HOLE_MULTIPLIER_INTERVAL: f32 = 15.0;    // seconds survived per extra score multiplier
+
+const PHOTON_RING_SCALE: f32 = 1.5;
+const ACCRETION_INNER_SCALE: f32 = 2.0;
+const ACCRETION_OUTER_SCALE: f32 = 4.5;
+const ACCRETION_RINGS: u32 = 6;
+const ACCRETION_ARCS: u32 = 5;                       // dashes per ring
+const ACCRETION_ARC_SEGMENTS: u32 = 6;
+const ACCRETION_SPEED: f32 = 4.0;                    // angular speed of the inner ring, outer rings slow down like keplerian orbits
+
+#[derive(Component, Default, Clone, Serialize, Deserialize)]
+pub struct BlackHole {
+    age: f32
+}
+
+pub fn horizon_radius(mass: f32) -> f32 {
+    f32::max(PLANET_COLLAPSE_SIZE, mass * BLACK_HOLE_HORIZON_PER_MASS)
+}
+
+// once the collapse has run its course the remnant becomes a black hole, if the player opted in
+pub fn form_black_hole(mut commands: Commands, game: Res<Game>, query: Query<(Entity, &Planet, &Mass, &Radius), Without<BlackHole>>) {
+    if !game.black_hole_phase {
+        return;
+    }
+    for (entity, planet, Mass(mass), Radius(radius)) in &query {
+        if planet.collapsing && planet.collapse_timer >= PLANET_COLLAPSE_TIME_MS {
+            commands.entity(entity).insert((BlackHole::default(),
+                                            Tween::new(TweenTarget::Radius { from: *radius, to: horizon_radius(*mass) }, Ease::BackOut, BLACK_HOLE_FORM_TIME)));
+        }
+    }
+}
+
+// the hole keeps growing and the score multiplier keeps climbing for as long as the player holds out
+pub fn update_black_hole(mut query: Query<(&mut BlackHole, &mut Mass, &mut Radius, Option<&Tween>)>, mut game: ResMut<Game>, time: Res<Time>) {
+    for (mut black_hole, mut mass, mut radius, tween) in &mut query {
+        black_hole.age += time.delta_seconds();
+        **mass += BLACK_HOLE_GROWTH_RATE * time.delta_seconds();
+        if tween.is_none() {
+            **radius = horizon_radius(**mass);
+        }
+        game.score_multiplier = BLACK_HOLE_MULTIPLIER_START + (black_hole.age / BLACK_HOLE_MULTIPLIER_INTERVAL) as u32;
+    }
+}
+
+pub fn black_hole_render(query: Query<(&Transform, &Radius, &BlackHole)>, mut lines: ResMut<VectorLines>, theme: Res<Theme>) {
+    for (transform, Radius(radius), black_hole) in &query {
+        let center = transform.translation.truncate();
+        lines.path(VectorPath::circle(center, radius * PHOTON_RING_SCALE, 40, theme.photon_ring), LAYER_EFFECTS);
+
+        for ring in 0..ACCRETION_RINGS {
+            let t = ring as f32 / (ACCRETION_RINGS - 1) as f32;
+            let scale = ACCRETION_INNER_SCALE + (ACCRETION_OUTER_SCALE - ACCRETION_INNER_SCALE) * t;
+            let ring_radius = radius * scale;
+            let angular_speed = ACCRETION_SPEED * (ACCRETION_INNER_SCALE / scale).powf(1.5);
+            let rotation = black_hole.age * angular_speed + ring as f32;
+            let arc_length = std::f32::consts::PI / ACCRETION_ARCS as f32;
+
+            // each dash fades from hot at its head to cool at its tail
+            for arc in 0..ACCRETION_ARCS {
+                let start = rotation + 2.0 * std::f32::consts::PI * arc as f32 / ACCRETION_ARCS as f32;
+                let mut path = VectorPath::default();
+                for i in 0..=ACCRETION_ARC_SEGMENTS {
+                    let f = i as f32 / ACCRETION_ARC_SEGMENTS as f32;
+                    let angle = start - arc_length * f;
+                    let mut color = lerp_color(theme.accretion_hot, theme.accretion_cool, (t + f) / 2.0);
+                    color.set_a(color.a() * (1.0 - f));
+                    path.push(center + Vec2::new(angle.cos(), angle.sin()) * ring_radius, color);
+                }
+                lines.path(path, LAYER_EFFECTS);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::{Duration, Instant};
+
+    use super::*;
+    use crate::sfx::SoundEffect;
+    use crate::{planet_colision, GameRng, Tuning};
+
+    fn black_hole_world(seconds: f32) -> World {
+        let mut world = World::new();
+        let start = Instant::now();
+        let mut time = Time::new(start);
+        time.update_with_instant(start);
+        time.update_with_instant(start + Duration::from_secs_f32(seconds));
+        world.insert_resource(time);
+        world.insert_resource(Game::new());
+        world
+    }
+
+    #[test]
+    fn the_horizon_grows_with_mass_from_the_collapsed_size() {
+        assert_eq!(horizon_radius(0.0), PLANET_COLLAPSE_SIZE);
+        assert_eq!(horizon_radius(BLACK_HOLE_START_MASS), BLACK_HOLE_START_MASS * BLACK_HOLE_HORIZON_PER_MASS);
+        assert_eq!(horizon_radius(2.0 * BLACK_HOLE_START_MASS), 2.0 * horizon_radius(BLACK_HOLE_START_MASS));
+    }
+
+    #[test]
+    fn only_a_finished_collapse_forms_a_hole_and_only_when_opted_in() {
+        for (phase, timer, formed) in [(false, PLANET_COLLAPSE_TIME_MS, false), (true, PLANET_COLLAPSE_TIME_MS / 2.0, false), (true, PLANET_COLLAPSE_TIME_MS, true)] {
+            let mut world = black_hole_world(0.0);
+            world.resource_mut::<Game>().black_hole_phase = phase;
+            let mut planet = Planet::new();
+            planet.collapsing = true;
+            planet.collapse_timer = timer;
+            world.spawn((planet, Mass(BLACK_HOLE_START_MASS), Radius(20.0)));
+            let mut stage = SystemStage::single_threaded();
+            stage.add_system(form_black_hole);
+            stage.run(&mut world);
+            assert_eq!(world.query::<&BlackHole>().iter(&world).count() == 1, formed);
+        }
+    }
+
+    #[test]
+    fn the_hole_grows_and_raises_the_multiplier_over_time() {
+        let mut world = black_hole_world(BLACK_HOLE_MULTIPLIER_INTERVAL);
+        let hole = world.spawn((BlackHole::default(), Mass(BLACK_HOLE_START_MASS), Radius(0.0))).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(update_black_hole);
+        stage.run(&mut world);
+        let mass = BLACK_HOLE_START_MASS + BLACK_HOLE_GROWTH_RATE * BLACK_HOLE_MULTIPLIER_INTERVAL;
+        assert!((**world.get::<Mass>(hole).unwrap() - mass).abs() < 0.1);
+        assert!((**world.get::<Radius>(hole).unwrap() - horizon_radius(mass)).abs() < 1e-3);
+        assert_eq!(world.resource::<Game>().score_multiplier, BLACK_HOLE_MULTIPLIER_START + 1);
+    }
+
+    #[test]
+    fn the_hole_swallows_bodies_whole_without_growing_its_radius() {
+        let mut world = black_hole_world(0.0);
+        world.insert_resource(Tuning::default());
+        world.insert_resource(GameRng::new(1, true));
+        world.insert_resource(Events::<SoundEffect>::default());
+        let hole = world.spawn((Planet::new(), BlackHole::default(), Mass(BLACK_HOLE_START_MASS), Radius(20.0), Transform::default())).id();
+        let body = world.spawn((Mass(30.0), Radius(5.0), Transform::from_xyz(15.0, 0.0, 0.0))).id();
+        let far = world.spawn((Mass(30.0), Radius(5.0), Transform::from_xyz(100.0, 0.0, 0.0))).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(planet_colision);
+        stage.run(&mut world);
+        assert!(world.get_entity(body).is_none());
+        assert!(world.get_entity(far).is_some());
+        assert_eq!(**world.get::<Mass>(hole).unwrap(), BLACK_HOLE_START_MASS + 30.0 * BLACK_HOLE_CONSUME_SCALE);
+        assert_eq!(**world.get::<Radius>(hole).unwrap(), 20.0);
+        assert!(world.get::<Tween>(hole).is_none());
+    }
+}
@@ -0,0 +1,263 @@
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy::render::view::RenderLayers;
+use bevy::transform::TransformSystem;
+
+use crate::locale::Strings;
+use crate::prefs::OptionChanged;
+use crate::sfx::SoundEffect;
+use crate::sim::SIM_ARENA_HALF_SIZE;
+use crate::theme::Theme;
+use crate::vector::{VectorLines, VectorPath, LAYER_BACKGROUND, VECTOR_RENDER_LAYER};
+use crate::{Mass, Planet, Ship, PLANET_MASS_COLLAPSE_TRIGGER, PLANET_START_MASS};
+
+const CAMERA_Z: f32 = 5.0;
+const CAMERA_FOLLOW_RATE: f32 = 3.0;         // how quickly the camera catches up with the ships, per second
+const CAMERA_FOLLOW_WEIGHT: f32 = 0.7;       // 1 centres on the ships, 0 on the planet
+const CAMERA_FOLLOW_MARGIN: f32 = 80.0;      // zoom out to keep the ships and planet this far inside the view
+const CAMERA_ZOOM_RATE: f32 = 1.5;
+const CAMERA_PLANET_ZOOM: f32 = 0.25;        // extra zoom out by the time the planet is about to collapse
+const CAMERA_MAX_ZOOM: f32 = 2.5;
+
+const SHAKE_DECAY: f32 = 1.5;                // trauma lost per second
+const SHAKE_MAX_OFFSET: f32 = 14.0;
+const SHAKE_MAX_ANGLE: f32 = 0.03;
+const SHAKE_FREQUENCY: f32 = 23.0;
+const SHAKE_EXPLOSION: f32 = 0.015;          // trauma per unit of explosion radius
+const SHAKE_SHIP_HIT: f32 = 0.5;
+const SHAKE_COLLAPSE: f32 = 0.9;
+const SHAKE_EJECT: f32 = 0.1;
+
+// shake can be turned off for players sensitive to motion
+#[derive(Resource)]
+pub struct CameraSettings {
+    pub follow: bool,
+    pub shake: bool
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self { follow: false, shake: true }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct CameraRig {
+    pub position: Vec2,
+    pub zoom: f32,
+    pub free: bool,          // steered by hand in the replay viewer, the follow logic leaves it alone
+    trauma: f32,
+    time: f32
+}
+
+// both cameras fit the arena into the window whatever its size, so the hud is laid out against the arena too
+fn arena_projection() -> OrthographicProjection {
+    OrthographicProjection {
+        scaling_mode: ScalingMode::Auto { min_width: SIM_ARENA_HALF_SIZE.x * 2.0, min_height: SIM_ARENA_HALF_SIZE.y * 2.0 },
+        ..default()
+    }
+}
+
+// the world camera sees only the vector shapes and moves about, the hud camera above it stays put and draws the text
+fn setup_camera(mut commands: Commands) {
+    commands.spawn((Camera2dBundle {
+                        projection: arena_projection(),
+                        transform: Transform::from_xyz(0.0, 0.0, CAMERA_Z),
+                        ..default()
+                    },
+                    CameraRig { zoom: 1.0, ..default() },
+                    UiCameraConfig { show_ui: false },
+                    RenderLayers::layer(VECTOR_RENDER_LAYER)));
+    commands.spawn(Camera2dBundle {
+        camera: Camera { priority: 1, ..default() },
+        camera_2d: Camera2d { clear_color: ClearColorConfig::None },
+        projection: arena_projection(),
+        transform: Transform::from_xyz(0.0, 0.0, CAMERA_Z),
+        ..default()
+    });
+}
+
+// how far to zoom out so a point stays inside the view around the centre
+fn zoom_to_fit(center: Vec2, point: Vec2, half_size: Vec2) -> f32 {
+    let offset = (point - center).abs() + Vec2::splat(CAMERA_FOLLOW_MARGIN);
+    f32::max(offset.x / half_size.x, offset.y / half_size.y)
+}
+
+type RigPlanetQuery<'w, 's> = Query<'w, 's, (&'static Transform, &'static Mass), (With<Planet>, Without<CameraRig>)>;
+type RigQuery<'w, 's> = Query<'w, 's, (&'static mut CameraRig, &'static mut Transform, &'static mut OrthographicProjection)>;
+
+#[allow(clippy::too_many_arguments)]
+fn update_camera(mut rig_query: RigQuery, ship_query: Query<&Transform, (With<Ship>, Without<CameraRig>)>, planet_query: RigPlanetQuery, mut sounds: EventReader<SoundEffect>, mut ship_count: Local<usize>, settings: Res<CameraSettings>, time: Res<Time>) {
+    let Ok((mut rig, mut transform, mut projection)) = rig_query.get_single_mut() else {
+        return;
+    };
+    let planet = planet_query.get_single().ok();
+    let planet_position = planet.map_or(Vec2::ZERO, |(planet_transform, _)| planet_transform.translation.truncate());
+    let ships: Vec<Vec2> = ship_query.iter().map(|ship_transform| ship_transform.translation.truncate()).collect();
+
+    // losing a ship shakes harder than any explosion, unless the whole game is being torn down
+    if ships.len() < *ship_count && planet.is_some() {
+        rig.trauma += SHAKE_SHIP_HIT * (*ship_count - ships.len()) as f32;
+    }
+    *ship_count = ships.len();
+    for sound in sounds.iter() {
+        rig.trauma += match sound {
+            SoundEffect::Explosion { radius } => radius * SHAKE_EXPLOSION,
+            SoundEffect::Collapse => SHAKE_COLLAPSE,
+            SoundEffect::Eject => SHAKE_EJECT,
+            _ => 0.0,
+        };
+    }
+    rig.trauma = (rig.trauma - SHAKE_DECAY * time.delta_seconds()).clamp(0.0, 1.0);
+    rig.time += time.delta_seconds();
+
+    let mut target = Vec2::ZERO;
+    let mut target_zoom = 1.0;
+    if let Some((_, Mass(planet_mass))) = planet {
+        let growth = (planet_mass - PLANET_START_MASS) / (PLANET_MASS_COLLAPSE_TRIGGER - PLANET_START_MASS);
+        target_zoom += CAMERA_PLANET_ZOOM * growth.clamp(0.0, 1.0);
+    }
+    if settings.follow && !ships.is_empty() {
+        let ship_centre = ships.iter().sum::<Vec2>() / ships.len() as f32;
+        target = planet_position.lerp(ship_centre, CAMERA_FOLLOW_WEIGHT);
+        for point in ships.iter().chain(planet.is_some().then_some(&planet_position)) {
+            target_zoom = f32::max(target_zoom, zoom_to_fit(target, *point, SIM_ARENA_HALF_SIZE));
+        }
+    }
+    target_zoom = target_zoom.min(CAMERA_MAX_ZOOM);
+
+    let follow = 1.0 - (-CAMERA_FOLLOW_RATE * time.delta_seconds()).exp();
+    let zoom = 1.0 - (-CAMERA_ZOOM_RATE * time.delta_seconds()).exp();
+    if !rig.free {
+        rig.position = rig.position.lerp(target, follow);
+        rig.zoom += (target_zoom - rig.zoom) * zoom;
+    }
+
+    let mut shake_offset = Vec2::ZERO;
+    let mut shake_angle = 0.0;
+    if settings.shake {
+        // a few detuned sines stand in for noise, scaled by trauma squared so small knocks stay subtle
+        let strength = rig.trauma * rig.trauma;
+        let t = rig.time * SHAKE_FREQUENCY;
+        shake_offset = Vec2::new((t * 1.0).sin() + (t * 2.3).sin() * 0.5, (t * 1.3 + 1.0).sin() + (t * 2.9).sin() * 0.5) * SHAKE_MAX_OFFSET * strength;
+        shake_angle = (t * 0.7 + 2.0).sin() * SHAKE_MAX_ANGLE * strength;
+    }
+
+    transform.translation = (rig.position + shake_offset).extend(CAMERA_Z);
+    transform.rotation = Quat::from_rotation_z(shake_angle);
+    projection.scale = rig.zoom;
+}
+
+// outlines the wrapping arena once the view reaches past it
+fn draw_arena(rig_query: Query<&CameraRig>, mut lines: ResMut<VectorLines>, theme: Res<Theme>) {
+    let Ok(rig) = rig_query.get_single() else {
+        return;
+    };
+    let half_size = SIM_ARENA_HALF_SIZE;
+    let view = half_size * rig.zoom;
+    if (rig.position.abs() + view).cmpgt(half_size + Vec2::ONE).any() {
+        let corners = [Vec2::new(-1.0, -1.0), Vec2::new(1.0, -1.0), Vec2::new(1.0, 1.0), Vec2::new(-1.0, 1.0)];
+        lines.path(VectorPath::polygon(corners.iter().map(|corner| *corner * half_size), theme.frame_faint), LAYER_BACKGROUND);
+    }
+}
+
+fn camera_options(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<CameraSettings>, mut options: EventWriter<OptionChanged>, strings: Res<Strings>) {
+    let message = if keyboard_input.just_pressed(KeyCode::F) {
+        settings.follow = !settings.follow;
+        strings.format("option.camera_follow", &[&strings.on_off(settings.follow)])
+    } else if keyboard_input.just_pressed(KeyCode::G) {
+        settings.shake = !settings.shake;
+        strings.format("option.screen_shake", &[&strings.on_off(settings.shake)])
+    } else {
+        return;
+    };
+    options.send(OptionChanged(message));
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraSettings>()
+            .add_startup_system(setup_camera)
+            .add_system(camera_options)
+            .add_system(draw_arena)
+            .add_system_to_stage(CoreStage::PostUpdate, update_camera.before(TransformSystem::TransformPropagate));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::{Duration, Instant};
+
+    use super::*;
+
+    // one stage for the whole test, so the system keeps its count of ships between frames
+    fn camera_world(settings: CameraSettings) -> (World, Entity, SystemStage) {
+        let mut world = World::new();
+        let start = Instant::now();
+        let mut time = Time::new(start);
+        time.update_with_instant(start);
+        time.update_with_instant(start + Duration::from_millis(100));
+        world.insert_resource(time);
+        world.insert_resource(settings);
+        world.insert_resource(Events::<SoundEffect>::default());
+        let rig = world.spawn((CameraRig { zoom: 1.0, ..default() }, Transform::default(), arena_projection())).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(update_camera);
+        (world, rig, stage)
+    }
+
+    #[test]
+    fn zooming_to_fit_keeps_the_margin_inside_the_view() {
+        let half_size = Vec2::new(400.0, 300.0);
+        assert_eq!(zoom_to_fit(Vec2::ZERO, Vec2::ZERO, half_size), CAMERA_FOLLOW_MARGIN / half_size.y);
+        assert_eq!(zoom_to_fit(Vec2::ZERO, Vec2::new(800.0 - CAMERA_FOLLOW_MARGIN, 0.0), half_size), 2.0);
+        assert_eq!(zoom_to_fit(Vec2::new(100.0, 0.0), Vec2::new(-100.0, 600.0 - CAMERA_FOLLOW_MARGIN), half_size), 2.0);
+    }
+
+    #[test]
+    fn explosions_shake_the_camera_unless_shake_is_off() {
+        for shake in [true, false] {
+            let (mut world, rig, mut stage) = camera_world(CameraSettings { follow: false, shake });
+            world.send_event(SoundEffect::Explosion { radius: 40.0 });
+            stage.run(&mut world);
+            let transform = *world.get::<Transform>(rig).unwrap();
+            let trauma = world.get::<CameraRig>(rig).unwrap().trauma;
+            assert!((trauma - (40.0 * SHAKE_EXPLOSION - SHAKE_DECAY * 0.1)).abs() < 1e-3);
+            assert_eq!(transform.translation.truncate() != Vec2::ZERO, shake);
+        }
+    }
+
+    #[test]
+    fn losing_a_ship_shakes_harder_than_tearing_the_game_down() {
+        let (mut world, rig, mut stage) = camera_world(CameraSettings { follow: false, shake: true });
+        let ship = world.spawn((Ship::new(0), Transform::default())).id();
+        let planet = world.spawn((Planet::new(), Mass(PLANET_START_MASS), Transform::default())).id();
+        stage.run(&mut world);
+        world.despawn(ship);
+        stage.run(&mut world);
+        assert!(world.get::<CameraRig>(rig).unwrap().trauma > SHAKE_SHIP_HIT - SHAKE_DECAY * 0.2);
+
+        world.get_mut::<CameraRig>(rig).unwrap().trauma = 0.0;
+        world.spawn((Ship::new(0), Transform::default()));
+        stage.run(&mut world);
+        world.despawn(planet);
+        let ship = world.query_filtered::<Entity, With<Ship>>().single(&world);
+        world.despawn(ship);
+        stage.run(&mut world);
+        assert_eq!(world.get::<CameraRig>(rig).unwrap().trauma, 0.0);
+    }
+
+    #[test]
+    fn following_eases_towards_the_ships_and_zooms_out_to_keep_them() {
+        let (mut world, rig, mut stage) = camera_world(CameraSettings { follow: true, shake: false });
+        world.spawn((Planet::new(), Mass(PLANET_START_MASS), Transform::default()));
+        world.spawn((Ship::new(0), Transform::from_xyz(SIM_ARENA_HALF_SIZE.x * 2.0, 0.0, 0.0)));
+        stage.run(&mut world);
+        let rig = world.get::<CameraRig>(rig).unwrap();
+        assert!(rig.position.x > 0.0 && rig.position.x < SIM_ARENA_HALF_SIZE.x * 2.0 * CAMERA_FOLLOW_WEIGHT);
+        assert!(rig.zoom > 1.0 && rig.zoom < CAMERA_MAX_ZOOM);
+    }
+}
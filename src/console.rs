@@ -0,0 +1,475 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use rand::Rng;
+
+use crate::theme::Theme;
+use crate::tween::Tween;
+use crate::{Asteroid, Game, GameRng, GameState, Lifetime, Mass, Planet, Radius, Ship, Tuning, Velocity, ASTEROID_LIFETIME_MS, FONT_PATH};
+
+const CONSOLE_HEIGHT: f32 = 40.0;            // percent of the window
+const CONSOLE_PADDING: f32 = 10.0;
+const CONSOLE_TEXT_SIZE: f32 = 14.0;
+const CONSOLE_VISIBLE_LINES: usize = 14;
+const CONSOLE_MAX_OUTPUT: usize = 200;
+const CONSOLE_BACKGROUND_ALPHA: f32 = 0.85;
+
+const COMMANDS: [&str; 12] = ["spawn", "set", "god", "timescale", "seed", "teleport", "config", "play", "wait", "exec", "clear", "help"];
+const SET_KEYS: [&str; 5] = ["planet.mass", "planet.radius", "planet.heat", "game.score", "game.multiplier"];
+
+// developer facing, so the usage lines stay in english like the commands themselves
+const HELP: [&str; 12] = [
+    "spawn asteroid <r> <m> <x> <y> <vx> <vy>",
+    "set <planet.mass|planet.radius|planet.heat|game.score|game.multiplier> <value>",
+    "god - ships can't be destroyed",
+    "timescale <factor>",
+    "seed <n> - reseed now and keep the seed for later runs",
+    "teleport reset - recharge every ship's teleport",
+    "config / config set <NAME> <value>",
+    "play - start a game from the title screen",
+    "wait <seconds> - pause a script",
+    "exec <file> - run a script",
+    "clear",
+    "help",
+];
+
+// ships that nothing can destroy, while god mode is on
+#[derive(Component)]
+pub struct Invulnerable;
+
+pub type VulnerableShipQuery<'w, 's> = Query<'w, 's, (Entity, &'static Radius, &'static Transform, &'static Velocity), (With<Ship>, Without<Invulnerable>)>;
+
+#[derive(Component)]
+pub struct ConsoleRoot;
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+#[derive(Resource, Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    history: Vec<String>,
+    browsing: Option<usize>,
+    output: VecDeque<String>,
+    pending: VecDeque<String>,     // typed or scripted lines, run one per frame
+    wait: f32,
+    god: bool
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.output.push_back(line.into());
+        while self.output.len() > CONSOLE_MAX_OUTPUT {
+            self.output.pop_front();
+        }
+    }
+
+    // blank lines and lines starting with # are skipped so scripts can be commented
+    pub fn queue_script(&mut self, path: &str) -> std::io::Result<()> {
+        let contents = std::fs::read_to_string(path)?;
+        self.pending.extend(contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string));
+        Ok(())
+    }
+}
+
+enum ConsoleCommand {
+    SpawnAsteroid { radius: f32, mass: f32, position: Vec2, velocity: Vec2 },
+    Set(String, f32),
+    God,
+    TimeScale(f32),
+    Seed(u64),
+    TeleportReset,
+    ConfigList,
+    ConfigSet(String, f32),
+    Play,
+    Wait(f32),
+    Exec(String),
+    Clear,
+    Help,
+}
+
+fn argument<T: FromStr>(words: &[&str], index: usize, name: &str) -> Result<T, String> {
+    let word = words.get(index).ok_or_else(|| format!("missing {}", name))?;
+    word.parse().map_err(|_| format!("bad {}: {}", name, word))
+}
+
+fn parse(line: &str) -> Result<ConsoleCommand, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let command = match words.as_slice() {
+        ["spawn", "asteroid", ..] => ConsoleCommand::SpawnAsteroid {
+            radius: argument(&words, 2, "radius")?,
+            mass: argument(&words, 3, "mass")?,
+            position: Vec2::new(argument(&words, 4, "x")?, argument(&words, 5, "y")?),
+            velocity: Vec2::new(argument(&words, 6, "vx")?, argument(&words, 7, "vy")?)
+        },
+        ["set", key, ..] if SET_KEYS.contains(key) => ConsoleCommand::Set(key.to_string(), argument(&words, 2, "value")?),
+        ["set", key, ..] => return Err(format!("unknown setting: {}", key)),
+        ["god"] => ConsoleCommand::God,
+        ["timescale", ..] => {
+            let factor: f32 = argument(&words, 1, "factor")?;
+            if !factor.is_finite() || factor < 0.0 {
+                return Err(format!("bad factor: {}", factor));
+            }
+            ConsoleCommand::TimeScale(factor)
+        }
+        ["seed", ..] => ConsoleCommand::Seed(argument(&words, 1, "seed")?),
+        ["teleport", "reset"] => ConsoleCommand::TeleportReset,
+        ["config"] => ConsoleCommand::ConfigList,
+        ["config", "set", name, ..] => ConsoleCommand::ConfigSet(name.to_string(), argument(&words, 3, "value")?),
+        ["play"] => ConsoleCommand::Play,
+        ["wait", ..] => ConsoleCommand::Wait(argument(&words, 1, "seconds")?),
+        ["exec", path] => ConsoleCommand::Exec(path.to_string()),
+        ["clear"] => ConsoleCommand::Clear,
+        ["help"] => ConsoleCommand::Help,
+        _ => return Err(format!("unknown command: {} (try help)", line)),
+    };
+    Ok(command)
+}
+
+// what the word being typed could be, given the words before it
+fn completions(previous: &[&str]) -> &'static [&'static str] {
+    match previous {
+        [] => &COMMANDS,
+        ["spawn"] => &["asteroid"],
+        ["set"] => &SET_KEYS,
+        ["teleport"] => &["reset"],
+        ["config"] => &["set"],
+        ["config", "set"] => &Tuning::NAMES,
+        _ => &[],
+    }
+}
+
+fn complete(console: &mut Console) {
+    let mut words: Vec<&str> = console.input.split_whitespace().collect();
+    let partial = if console.input.ends_with(' ') || words.is_empty() { "" } else { words.pop().unwrap() };
+    let matches: Vec<&str> = completions(&words).iter().copied().filter(|candidate| candidate.starts_with(partial)).collect();
+    let Some(first) = matches.first() else {
+        return;
+    };
+
+    let common = matches.iter().fold(first.to_string(), |common, candidate| {
+        common.chars().zip(candidate.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect()
+    });
+    let mut completed: Vec<&str> = words.clone();
+    completed.push(&common);
+    let mut input = completed.join(" ");
+    if matches.len() == 1 {
+        input.push(' ');
+    } else {
+        console.print(matches.join("  "));
+    }
+    console.input = input;
+}
+
+// runs before the game reads the keyboard so typing doesn't fly the ship
+pub fn console_input(mut console: ResMut<Console>, mut characters: EventReader<ReceivedCharacter>, mut keyboard_input: ResMut<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::Grave) || (console.open && keyboard_input.just_pressed(KeyCode::Escape)) {
+        console.open = !console.open;
+        characters.clear();
+        keyboard_input.reset_all();
+        return;
+    }
+    if !console.open {
+        return;
+    }
+
+    for character in characters.iter() {
+        if !character.char.is_control() && character.char != '`' {
+            console.input.push(character.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        complete(&mut console);
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) && !console.history.is_empty() {
+        let index = console.browsing.map_or(console.history.len() - 1, |index| index.saturating_sub(1));
+        console.browsing = Some(index);
+        console.input = console.history[index].clone();
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        if let Some(index) = console.browsing {
+            console.browsing = (index + 1 < console.history.len()).then_some(index + 1);
+            console.input = console.browsing.map_or(String::new(), |index| console.history[index].clone());
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) || keyboard_input.just_pressed(KeyCode::NumpadEnter) {
+        let line = std::mem::take(&mut console.input).trim().to_string();
+        console.browsing = None;
+        if !line.is_empty() {
+            if console.history.last() != Some(&line) {
+                console.history.push(line.clone());
+            }
+            console.pending.push_back(line);
+        }
+    }
+    keyboard_input.reset_all();
+}
+
+// one line per frame, so a script can start a game and change it on the next line
+#[allow(clippy::too_many_arguments)]
+pub fn run_console_commands(mut commands: Commands, mut console: ResMut<Console>, mut state: ResMut<State<GameState>>, mut time: ResMut<Time>, mut rng: ResMut<GameRng>, mut tuning: ResMut<Tuning>, mut game: ResMut<Game>, mut planet_query: Query<(Entity, &mut Mass, &mut Radius, &mut Planet)>, mut ship_query: Query<&mut Ship>) {
+    if console.wait > 0.0 {
+        console.wait -= time.raw_delta_seconds();
+        return;
+    }
+    let Some(line) = console.pending.pop_front() else {
+        return;
+    };
+    console.print(format!("> {}", line));
+
+    let result = parse(&line).and_then(|command| match command {
+        ConsoleCommand::SpawnAsteroid { radius, mass, position, velocity } => {
+            commands.spawn((Asteroid { seed: rng.rng.gen() },
+                            Radius(radius),
+                            Mass(mass),
+                            Transform::from_translation(position.extend(0.0)),
+                            Velocity(velocity),
+                            Lifetime(Duration::from_millis(ASTEROID_LIFETIME_MS))));
+            Ok(format!("spawned asteroid at {:.0}, {:.0}", position.x, position.y))
+        }
+        ConsoleCommand::Set(key, value) => {
+            if let Some(field) = key.strip_prefix("game.") {
+                match field {
+                    "score" => game.set_score(value as u32),
+                    _ => game.score_multiplier = value as u32,
+                }
+                return Ok(format!("{} = {}", key, value));
+            }
+            let (entity, mut mass, mut radius, mut planet) = planet_query.get_single_mut().map_err(|_| "no planet".to_string())?;
+            match key.as_str() {
+                "planet.mass" => **mass = value,
+                "planet.radius" => {
+                    // a growing or collapsing star would tween straight back over the new radius
+                    commands.entity(entity).remove::<Tween>();
+                    **radius = value;
+                }
+                _ => planet.heat = value as u32,
+            }
+            Ok(format!("{} = {}", key, value))
+        }
+        ConsoleCommand::God => {
+            console.god = !console.god;
+            Ok(format!("god mode {}", if console.god { "on" } else { "off" }))
+        }
+        ConsoleCommand::TimeScale(factor) => {
+            time.set_relative_speed(factor);
+            Ok(format!("timescale {}", factor))
+        }
+        ConsoleCommand::Seed(seed) => {
+            *rng = GameRng::new(seed, true);
+            Ok(format!("seed {}", seed))
+        }
+        ConsoleCommand::TeleportReset => {
+            for mut ship in &mut ship_query {
+                ship.teleport_cooldown = 0.0;
+            }
+            Ok("teleports recharged".to_string())
+        }
+        ConsoleCommand::ConfigList => {
+            for name in Tuning::NAMES {
+                console.print(format!("{} = {}", name, tuning.get(name).unwrap_or_default()));
+            }
+            Ok(String::new())
+        }
+        ConsoleCommand::ConfigSet(name, value) => {
+            if tuning.set(&name, value) {
+                Ok(format!("{} = {}", name, value))
+            } else {
+                Err(format!("unknown config: {}", name))
+            }
+        }
+        ConsoleCommand::Play => {
+            state.set(GameState::Playing).map_err(|err| format!("can't start a game: {:?}", err))?;
+            Ok("starting game".to_string())
+        }
+        ConsoleCommand::Wait(seconds) => {
+            console.wait = seconds;
+            Ok(String::new())
+        }
+        ConsoleCommand::Exec(path) => {
+            // the script runs ahead of anything already queued
+            let queued = std::mem::take(&mut console.pending);
+            console.queue_script(&path).map_err(|err| format!("could not read {}: {}", path, err))?;
+            console.pending.extend(queued);
+            Ok(format!("running {}", path))
+        }
+        ConsoleCommand::Clear => {
+            console.output.clear();
+            Ok(String::new())
+        }
+        ConsoleCommand::Help => {
+            for line in HELP {
+                console.print(line);
+            }
+            Ok(String::new())
+        }
+    });
+
+    match result {
+        Ok(message) if message.is_empty() => {}
+        Ok(message) => console.print(message),
+        Err(message) => {
+            warn!("console: {}", message);
+            console.print(message);
+        }
+    }
+}
+
+pub fn apply_god_mode(mut commands: Commands, console: Res<Console>, query: Query<(Entity, Option<&Invulnerable>), With<Ship>>) {
+    for (entity, invulnerable) in &query {
+        if console.god && invulnerable.is_none() {
+            commands.entity(entity).insert(Invulnerable);
+        } else if !console.god && invulnerable.is_some() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+// the screens despawn every text when they close, so the console is rebuilt whenever its text has gone
+pub fn update_console_view(mut commands: Commands, console: Res<Console>, root_query: Query<Entity, With<ConsoleRoot>>, mut text_query: Query<&mut Text, With<ConsoleText>>, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    let skip = console.output.len().saturating_sub(CONSOLE_VISIBLE_LINES);
+    let output = console.output.iter().skip(skip).cloned().collect::<Vec<_>>().join("\n");
+    let prompt = format!("\n> {}_", console.input);
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        if console.open {
+            text.sections[0].value = output;
+            text.sections[1].value = prompt;
+            return;
+        }
+    }
+    for entity in &root_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !console.open {
+        return;
+    }
+
+    let font: Handle<Font> = asset_server.load(FONT_PATH);
+    let mut background = theme.background;
+    background.set_a(CONSOLE_BACKGROUND_ALPHA);
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect { left: Val::Px(0.0), top: Val::Px(0.0), ..default() },
+            size: Size::new(Val::Percent(100.0), Val::Percent(CONSOLE_HEIGHT)),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::FlexEnd,
+            padding: UiRect::all(Val::Px(CONSOLE_PADDING)),
+            ..default()
+        },
+        background_color: background.into(),
+        z_index: ZIndex::Global(10),
+        ..default()
+    };
+    let text = Text::from_sections([
+        TextSection::new(output, TextStyle { font: font.clone(), font_size: CONSOLE_TEXT_SIZE, color: theme.hud_label }),
+        TextSection::new(prompt, TextStyle { font, font_size: CONSOLE_TEXT_SIZE, color: theme.text }),
+    ]);
+    commands.spawn((root, ConsoleRoot)).with_children(|root| {
+        root.spawn((TextBundle { text, ..default() }, ConsoleText));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::simulation_world;
+    use crate::tween::{Ease, TweenTarget};
+
+    fn console_world(players: usize) -> World {
+        let mut world = simulation_world(3, players);
+        world.init_resource::<Console>();
+        world.insert_resource(State::new(GameState::Playing));
+        world.insert_resource(Time::default());
+        world
+    }
+
+    // queues a line, runs it and returns the last thing the console printed
+    fn run(world: &mut World, line: &str) -> String {
+        world.resource_mut::<Console>().pending.push_back(line.to_string());
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(run_console_commands);
+        stage.run(world);
+        world.resource::<Console>().output.back().cloned().unwrap_or_default()
+    }
+
+    fn completed(input: &str) -> String {
+        let mut console = Console { input: input.to_string(), ..default() };
+        complete(&mut console);
+        console.input
+    }
+
+    #[test]
+    fn commands_parse_their_arguments() {
+        let Ok(ConsoleCommand::SpawnAsteroid { radius, mass, position, velocity }) = parse("spawn asteroid 10 5 1 2 3 4") else {
+            panic!("spawn asteroid did not parse");
+        };
+        assert_eq!((radius, mass, position, velocity), (10.0, 5.0, Vec2::new(1.0, 2.0), Vec2::new(3.0, 4.0)));
+        assert!(matches!(parse("set planet.heat 3"), Ok(ConsoleCommand::Set(key, value)) if key == "planet.heat" && value == 3.0));
+        assert!(matches!(parse("config set GRAVITY 2"), Ok(ConsoleCommand::ConfigSet(name, value)) if name == "GRAVITY" && value == 2.0));
+        assert!(matches!(parse("  seed   42 "), Ok(ConsoleCommand::Seed(42))));
+    }
+
+    #[test]
+    fn bad_lines_say_what_is_wrong() {
+        assert_eq!(parse("spawn asteroid 10").err().unwrap(), "missing mass");
+        assert_eq!(parse("set planet.colour 1").err().unwrap(), "unknown setting: planet.colour");
+        assert_eq!(parse("timescale -1").err().unwrap(), "bad factor: -1");
+        assert_eq!(parse("wait soon").err().unwrap(), "bad seconds: soon");
+        assert!(parse("teleport").is_err());
+    }
+
+    #[test]
+    fn tab_completes_as_far_as_the_candidates_agree() {
+        assert_eq!(completed("tel"), "teleport ");
+        assert_eq!(completed("se"), "se");
+        assert_eq!(completed("set planet.r"), "set planet.radius ");
+        assert_eq!(completed("config set BULLET"), "config set BULLET_VELOCITY ");
+        assert_eq!(completed("help me"), "help me");
+        assert_eq!(completions(&["spawn"]), ["asteroid"]);
+    }
+
+    #[test]
+    fn setting_the_score_keeps_the_players_scores_adding_up() {
+        let mut world = console_world(3);
+        assert_eq!(run(&mut world, "set game.score 100"), "game.score = 100");
+        let game = world.resource::<Game>();
+        assert_eq!(game.score, 100);
+        assert_eq!(game.player_scores[..3], [34, 33, 33]);
+        assert_eq!(game.player_scores.iter().sum::<u32>(), 100);
+    }
+
+    #[test]
+    fn setting_the_radius_stops_the_star_growing_over_it() {
+        let mut world = console_world(1);
+        let planet = world.query_filtered::<Entity, With<Planet>>().single(&world);
+        world.entity_mut(planet).insert(Tween::new(TweenTarget::Radius { from: 10.0, to: 90.0 }, Ease::Linear, 1.0));
+        run(&mut world, "set planet.radius 42");
+        assert!(world.get::<Tween>(planet).is_none());
+        assert_eq!(world.get::<Radius>(planet).unwrap().0, 42.0);
+    }
+
+    #[test]
+    fn commands_reach_what_they_change() {
+        let mut world = console_world(1);
+        run(&mut world, "config set BULLET_VELOCITY 450");
+        assert_eq!(world.resource::<Tuning>().bullet_velocity, 450.0);
+        run(&mut world, "timescale 0.5");
+        assert_eq!(world.resource::<Time>().relative_speed(), 0.5);
+        let asteroids = world.query::<&Asteroid>().iter(&world).count();
+        run(&mut world, "spawn asteroid 10 5 100 0 0 0");
+        assert_eq!(world.query::<&Asteroid>().iter(&world).count(), asteroids + 1);
+        assert_eq!(run(&mut world, "config set SPEED 1"), "unknown config: SPEED");
+        assert!(run(&mut world, "exec no-such-script.txt").starts_with("could not read no-such-script.txt"));
+    }
+}
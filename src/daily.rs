@@ -0,0 +1,446 @@
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+use crate::locale::Strings;
+use crate::replay::{Replay, REPLAY_PATH};
+use crate::save::Resume;
+use crate::store;
+use crate::theme::Theme;
+use crate::*;
+
+pub const DAILY_PATH: &str = "daily.json";
+const DAILY_BOARD_VERSION: u32 = 1;
+const DAILY_EXPORT_VERSION: u32 = 1;          // bump whenever anything exported here changes shape
+const DAILY_SALT: u64 = 0x6461_696c_7920_7365; // so day n's seed is not simply n
+const DAILY_MODIFIER_COUNT: usize = 2;
+const DAILY_BOARD_COUNT: usize = 10;
+const DAILY_BOARD_SIZE: f32 = 16.0;
+
+const LOW_GRAVITY_FACTOR: f32 = 0.6;
+const HIGH_GRAVITY_FACTOR: f32 = 1.5;
+const FAST_BULLET_FACTOR: f32 = 1.5;
+const RAPID_DIVISOR: u64 = 2;                 // spawn and fire delays
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Modifier {
+    LowGravity,
+    HighGravity,
+    RapidSpawns,
+    FastBullets,
+    RapidFire,
+}
+
+impl Modifier {
+    const ALL: [Modifier; 5] = [Modifier::LowGravity, Modifier::HighGravity, Modifier::RapidSpawns, Modifier::FastBullets, Modifier::RapidFire];
+
+    fn key(&self) -> &'static str {
+        match self {
+            Modifier::LowGravity => "daily.low_gravity",
+            Modifier::HighGravity => "daily.high_gravity",
+            Modifier::RapidSpawns => "daily.rapid_spawns",
+            Modifier::FastBullets => "daily.fast_bullets",
+            Modifier::RapidFire => "daily.rapid_fire",
+        }
+    }
+
+    fn conflicts(&self, other: Modifier) -> bool {
+        *self == other || matches!((self, other), (Modifier::LowGravity, Modifier::HighGravity) | (Modifier::HighGravity, Modifier::LowGravity))
+    }
+
+    fn apply(&self, tuning: &mut Tuning) {
+        match self {
+            Modifier::LowGravity => tuning.gravity *= LOW_GRAVITY_FACTOR,
+            Modifier::HighGravity => tuning.gravity *= HIGH_GRAVITY_FACTOR,
+            Modifier::RapidSpawns => {
+                tuning.asteroid_spawn_delay_min_ms /= RAPID_DIVISOR;
+                tuning.asteroid_spawn_delay_max_ms /= RAPID_DIVISOR;
+            }
+            Modifier::FastBullets => tuning.bullet_velocity *= FAST_BULLET_FACTOR,
+            Modifier::RapidFire => tuning.fire_delay_ms /= RAPID_DIVISOR,
+        }
+    }
+}
+
+// the seed and rules everyone plays on a given day, worked out from nothing but the date
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Challenge {
+    pub day: u64,
+    pub date: String,
+    pub seed: u64,
+    pub modifiers: Vec<Modifier>
+}
+
+impl Challenge {
+    pub fn for_day(day: u64) -> Self {
+        let mut rng = Pcg32::seed_from_u64(day ^ DAILY_SALT);
+        let seed = rng.gen();
+        let mut modifiers: Vec<Modifier> = Vec::new();
+        while modifiers.len() < DAILY_MODIFIER_COUNT {
+            let modifier = Modifier::ALL[rng.gen_range(0..Modifier::ALL.len())];
+            if !modifiers.iter().any(|chosen| chosen.conflicts(modifier)) {
+                modifiers.push(modifier);
+            }
+        }
+        Self { day, date: date_of(day), seed, modifiers }
+    }
+
+    // days are counted in utc so the whole team gets the same challenge wherever they are
+    pub fn today() -> Self {
+        let day = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() / 86400);
+        Self::for_day(day)
+    }
+
+    pub fn tuning(&self) -> Tuning {
+        let mut tuning = Tuning::default();
+        for modifier in &self.modifiers {
+            modifier.apply(&mut tuning);
+        }
+        tuning
+    }
+
+    pub fn describe(&self, strings: &Strings) -> String {
+        self.modifiers.iter().map(|modifier| strings.get(modifier.key())).collect::<Vec<_>>().join(", ")
+    }
+}
+
+// the calendar date of a day counted from the unix epoch
+fn date_of(day: u64) -> String {
+    let days = day + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day_of_month)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DailyResult {
+    pub challenge: Challenge,
+    pub score: u32,
+    pub players: usize,
+    pub mode: GameMode,
+    pub time: u64,
+    #[serde(default)]
+    pub imported: bool
+}
+
+impl DailyResult {
+    fn same_game(&self, other: &DailyResult) -> bool {
+        self.challenge.day == other.challenge.day && self.players == other.players && self.mode == other.mode
+    }
+}
+
+// every daily run played here or imported from someone else, kept apart from the normal high scores
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DailyBoard {
+    results: Vec<DailyResult>
+}
+
+impl DailyBoard {
+    pub fn load(path: &str) -> Self {
+        store::load(path, DAILY_BOARD_VERSION)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        store::save(path, DAILY_BOARD_VERSION, self)
+    }
+
+    fn record(&mut self, result: DailyResult) {
+        self.results.push(result);
+    }
+
+    // the best runs of a day for the same players and mode
+    fn standings(&self, result: &DailyResult) -> Vec<&DailyResult> {
+        let mut standings: Vec<&DailyResult> = self.results.iter().filter(|entry| entry.same_game(result)).collect();
+        standings.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        standings.truncate(DAILY_BOARD_COUNT);
+        standings
+    }
+
+    fn best(&self, day: u64) -> Option<u32> {
+        self.results.iter().filter(|entry| entry.challenge.day == day && !entry.imported).map(|entry| entry.score).max()
+    }
+}
+
+// a daily result together with the replay of the run, for passing around the team
+#[derive(Serialize, Deserialize)]
+struct DailyExport {
+    version: u32,
+    result: DailyResult,
+    replay: Replay
+}
+
+impl DailyExport {
+    fn load(path: &str) -> io::Result<Self> {
+        let export: DailyExport = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if export.version != DAILY_EXPORT_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("daily export version {} is not {}", export.version, DAILY_EXPORT_VERSION)));
+        }
+        // a run only compares with ours if it was played on the challenge this build would set for its day
+        if export.result.challenge != Challenge::for_day(export.result.challenge.day) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the challenge for {} does not match this build", export.result.challenge.date)));
+        }
+        export.replay.check()?;
+        Ok(export)
+    }
+
+    fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Daily {
+    pub challenge: Option<Challenge>,
+    previous_tuning: Option<Tuning>,      // put back on returning to the title screen
+    last_result: Option<DailyResult>
+}
+
+impl Daily {
+    // the challenge's rules stand in for the current ones until the title screen puts them back
+    pub fn begin(&mut self, challenge: Challenge, tuning: &mut Tuning) {
+        self.previous_tuning.get_or_insert_with(|| tuning.clone());
+        *tuning = challenge.tuning();
+        self.challenge = Some(challenge);
+    }
+
+    // a restored run plays on the rules it was saved with, daily or not, until the title screen
+    pub fn resume(&mut self, challenge: Option<Challenge>, tuning: &mut Tuning, saved: &Tuning) {
+        self.previous_tuning.get_or_insert_with(|| tuning.clone());
+        *tuning = saved.clone();
+        self.challenge = challenge;
+    }
+}
+
+#[derive(Component)]
+pub struct DailyHintText;
+
+#[derive(Component)]
+pub struct DailyExportText;
+
+pub fn start_daily(mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>, mut daily: ResMut<Daily>, mut tuning: ResMut<Tuning>) {
+    if keyboard_input.just_pressed(KeyCode::D) && state.set(GameState::Playing).is_ok() {
+        daily.begin(Challenge::today(), &mut tuning);
+    }
+}
+
+// runs after the new run rolled its seed, so the daily one replaces it before anything is spawned from it;
+// a continued run already has its random state back
+pub fn apply_daily(daily: Res<Daily>, mut rng: ResMut<GameRng>, resume: Option<Res<Resume>>) {
+    if resume.is_some() {
+        return;
+    }
+    if let Some(challenge) = &daily.challenge {
+        *rng = GameRng::new(challenge.seed, rng.fixed);
+    }
+}
+
+pub fn record_daily(mut daily: ResMut<Daily>, mut board: ResMut<DailyBoard>, game: Res<Game>) {
+    let Some(challenge) = daily.challenge.clone() else {
+        return;
+    };
+    let result = DailyResult { challenge, score: game.score, players: game.players, mode: game.mode, time: game.time, imported: false };
+    board.record(result.clone());
+    if let Err(err) = board.save(DAILY_PATH) {
+        warn!("could not save the daily results: {}", err);
+    }
+    daily.last_result = Some(result);
+}
+
+pub fn end_daily(mut daily: ResMut<Daily>, mut tuning: ResMut<Tuning>) {
+    if let Some(previous) = daily.previous_tuning.take() {
+        *tuning = previous;
+    }
+    daily.challenge = None;
+    daily.last_result = None;
+}
+
+pub fn setup_daily_hint(mut commands: Commands, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: TITLE_HINT_SIZE,
+        color: theme.hint,
+    };
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("", text_style)
+                .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(0.0, -190.0, 0.0),
+            ..default()
+        },
+        DailyHintText)
+    );
+}
+
+pub fn update_daily_hint(mut query: Query<&mut Text, With<DailyHintText>>, board: Res<DailyBoard>, strings: Res<Strings>) {
+    let challenge = Challenge::today();
+    let mut hint = strings.format("title.daily_hint", &[&challenge.date, &challenge.describe(&strings)]);
+    if let Some(best) = board.best(challenge.day) {
+        hint += &strings.format("title.daily_best", &[&strings.number(best)]);
+    }
+    for mut text in &mut query {
+        text.sections[0].value = hint.clone();
+    }
+}
+
+// the day's standings beside the stats, with this run marked
+pub fn setup_daily_results(mut commands: Commands, daily: Res<Daily>, board: Res<DailyBoard>, asset_server: Res<AssetServer>, strings: Res<Strings>, theme: Res<Theme>) {
+    let Some(result) = &daily.last_result else {
+        return;
+    };
+    let mut lines = vec![strings.format("daily.board", &[&result.challenge.date]), result.challenge.describe(&strings)];
+    for (place, entry) in board.standings(result).iter().enumerate() {
+        let key = if entry.imported { "daily.imported" } else { "daily.entry" };
+        let line = strings.format(key, &[&(place + 1), &strings.number(entry.score)]);
+        let mine = board.results.last().is_some_and(|last| std::ptr::eq(*entry, last));
+        lines.push(if mine { format!("> {}", line) } else { line });
+    }
+
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: DAILY_BOARD_SIZE,
+        color: theme.text,
+    };
+    commands.spawn(
+        Text2dBundle {
+            text: Text::from_section(lines.join("\n"), text_style)
+                .with_alignment(TextAlignment::TOP_RIGHT),
+            transform: Transform::from_xyz(SIM_ARENA_HALF_SIZE.x - 20.0, SIM_ARENA_HALF_SIZE.y - 20.0, 0.0),
+            ..default()
+        }
+    );
+
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: STATS_EXPORT_SIZE,
+        color: theme.hint,
+    };
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section(strings.get("daily.export_hint"), text_style)
+                .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(0.0, -250.0, 0.0),
+            ..default()
+        },
+        DailyExportText)
+    );
+}
+
+fn export_daily_run(result: &DailyResult) -> io::Result<String> {
+    let path = format!("daily-{}-{}.json", result.challenge.date, result.score);
+    let export = DailyExport { version: DAILY_EXPORT_VERSION, result: result.clone(), replay: Replay::load(REPLAY_PATH)? };
+    export.save(&path)?;
+    Ok(path)
+}
+
+pub fn export_daily(keyboard_input: Res<Input<KeyCode>>, daily: Res<Daily>, mut query: Query<&mut Text, With<DailyExportText>>, strings: Res<Strings>) {
+    let Some(result) = &daily.last_result else {
+        return;
+    };
+    if keyboard_input.just_pressed(KeyCode::D) {
+        let message = match export_daily_run(result) {
+            Ok(path) => strings.format("daily.exported", &[&path]),
+            Err(err) => strings.format("daily.export_failed", &[&err]),
+        };
+        for mut text in &mut query {
+            text.sections[0].value = message.clone();
+        }
+    }
+}
+
+// adds someone else's exported daily runs to the local standings, keeping each replay beside them instead of over our own
+pub fn import_daily_main(paths: &[String]) -> bool {
+    if paths.is_empty() {
+        println!("usage: --import-daily <file>...");
+        return false;
+    }
+    let mut board = DailyBoard::load(DAILY_PATH);
+    let mut imported = 0;
+    let mut ok = true;
+    for path in paths {
+        match DailyExport::load(path) {
+            Ok(export) => {
+                println!("{}: {} on {}, {} player(s)", path, export.result.score, export.result.challenge.date, export.result.players);
+                let replay_path = format!("daily-{}-{}.replay.json", export.result.challenge.date, export.result.score);
+                match export.replay.save(&replay_path) {
+                    Ok(()) => println!("replay saved as {}, watch it with --watch {}", replay_path, replay_path),
+                    Err(err) => {
+                        println!("could not save the replay: {}", err);
+                        ok = false;
+                    }
+                }
+                board.record(DailyResult { imported: true, ..export.result });
+                imported += 1;
+            }
+            Err(err) => {
+                println!("{}: {}", path, err);
+                ok = false;
+            }
+        }
+    }
+    if imported == 0 {
+        return false;
+    }
+    if let Err(err) = board.save(DAILY_PATH) {
+        println!("could not save {}: {}", DAILY_PATH, err);
+        ok = false;
+    }
+    ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::{capture_world, start_world};
+    use crate::sim::{run_ticks, set_ship_inputs, simulation_world, INPUT_FIRE, INPUT_LEFT, INPUT_THRUST};
+
+    // how fast the lone ship is falling towards the star after a moment of drifting under a challenge's rules
+    fn fall_speed(modifiers: Vec<Modifier>) -> f32 {
+        let challenge = Challenge { modifiers, ..Challenge::for_day(20_000) };
+        let mut world = simulation_world(challenge.seed, 1);
+        world.resource_scope(|world, mut daily: Mut<Daily>| daily.begin(challenge, &mut world.resource_mut::<Tuning>()));
+        run_ticks(&mut world, 30);
+        let Velocity(velocity) = world.query_filtered::<&Velocity, With<Ship>>().single(&world);
+        velocity.length()
+    }
+
+    #[test]
+    fn gravity_modifiers_change_how_the_star_pulls() {
+        let normal = fall_speed(Vec::new());
+        assert!(normal > 0.0);
+        assert!(fall_speed(vec![Modifier::LowGravity]) < normal);
+        assert!(fall_speed(vec![Modifier::HighGravity]) > normal);
+    }
+
+    // the attract run and the console leave the spawn timers and rules anywhere, the day's run starts over regardless
+    fn daily_run(title_ticks: u32) -> String {
+        let challenge = Challenge::for_day(20_000);
+        let mut world = simulation_world(99, 1);
+        run_ticks(&mut world, title_ticks);
+        world.resource_scope(|world, mut daily: Mut<Daily>| daily.begin(challenge.clone(), &mut world.resource_mut::<Tuning>()));
+        world.resource_mut::<Tuning>().gravity *= 3.0;
+        start_world(&mut world, Some(GameRng::new(challenge.seed, false)));
+        for tick in 0..900 {
+            set_ship_inputs(&mut world, &[if tick % 40 < 20 { INPUT_FIRE | INPUT_LEFT } else { INPUT_THRUST }]);
+            run_ticks(&mut world, 1);
+        }
+        assert!(world.query::<&Asteroid>().iter(&world).count() > 0);
+        serde_json::to_string(&capture_world(&mut world)).unwrap()
+    }
+
+    #[test]
+    fn every_daily_run_on_a_day_is_the_same_run() {
+        assert_eq!(daily_run(100), daily_run(700));
+    }
+}
@@ -0,0 +1,288 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::autopilot::{circular_orbit_speed, escape_direction, lowest_altitude, orbit_correction, predict_position, steer_towards, BotPlanet};
+use crate::console::VulnerableShipQuery;
+use crate::particles::{spawn_effect, ParticleEmitter, EXPLOSION, SHIP_TRAIL};
+use crate::sfx::SoundEffect;
+use crate::theme::Theme;
+use crate::vector::{VectorPath, VectorShape, LAYER_SHAPES};
+use crate::{
+    apply_ship_input, in_position_order, AngularVelocity, Asteroid, Bullet, BulletOwner, Game, GameRng, Lifetime, Mass, Planet, Radius, Ship, ShipInput, Stats, Tuning, Velocity,
+    BULLET_LIFETIME_MS, BULLET_MASS, BULLET_RADIUS, SHIP_TRAIL_ALPHA,
+};
+
+const ENEMY_CORNERS: [Vec3; 4] = [
+    Vec3::new(0.0, 10.0, 0.0),
+    Vec3::new(-6.0, 0.0, 0.0),
+    Vec3::new(0.0, -6.0, 0.0),
+    Vec3::new(6.0, 0.0, 0.0),
+];
+
+const ENEMY_RADIUS: f32 = 8.0;
+const ENEMY_MASS: f32 = 10.0;
+const ENEMY_MAX_COUNT: usize = 3;
+const ENEMY_FIRST_SPAWN_MS: u64 = 20000;
+const ENEMY_SPAWN_DELAY_MIN_MS: u64 = 15000;
+const ENEMY_SPAWN_DELAY_MAX_MS: u64 = 25000;
+const ENEMY_SPAWN_DISTANCE: f32 = 420.0;
+const ENEMY_FIRE_DELAY_MS: u64 = 900;
+const ENEMY_FIRE_RANGE: f32 = 380.0;
+const ENEMY_FIRE_CONE: f32 = 0.12;        // radians either side of the nose a target must be within to shoot
+const ENEMY_THRUST_CONE: f32 = 0.5;       // only thrust when roughly facing the way it wants to go
+const ENEMY_PLAN_TIME: f32 = 3.0;         // seconds of trajectory checked for a planet impact
+const ENEMY_SAFE_ALTITUDE: f32 = 40.0;
+const ENEMY_PURSUIT_SPEED: f32 = 90.0;    // pursuers stop accelerating once closing faster than this
+const ENEMY_ORBIT_ALTITUDE: f32 = 220.0;
+const ENEMY_ORBIT_TOLERANCE: f32 = 15.0;  // speed error an orbiting sniper tolerates before correcting its orbit
+
+const SCORE_ENEMY_PURSUIT: u32 = 150;
+const SCORE_ENEMY_ORBIT_SNIPE: u32 = 250;
+const SCORE_ENEMY_KAMIKAZE: u32 = 100;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum EnemyBehaviour {
+    Pursuit,
+    OrbitSnipe,
+    Kamikaze,
+}
+
+impl EnemyBehaviour {
+    const ALL: [EnemyBehaviour; 3] = [
+        EnemyBehaviour::Pursuit,
+        EnemyBehaviour::OrbitSnipe,
+        EnemyBehaviour::Kamikaze,
+    ];
+
+    fn color(&self, theme: &Theme) -> Color {
+        match self {
+            EnemyBehaviour::Pursuit => theme.enemy_pursuit,
+            EnemyBehaviour::OrbitSnipe => theme.enemy_orbit_snipe,
+            EnemyBehaviour::Kamikaze => theme.enemy_kamikaze,
+        }
+    }
+
+    fn score(&self) -> u32 {
+        match self {
+            EnemyBehaviour::Pursuit => SCORE_ENEMY_PURSUIT,
+            EnemyBehaviour::OrbitSnipe => SCORE_ENEMY_ORBIT_SNIPE,
+            EnemyBehaviour::Kamikaze => SCORE_ENEMY_KAMIKAZE,
+        }
+    }
+
+    fn shoots(&self) -> bool {
+        *self != EnemyBehaviour::Kamikaze
+    }
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Enemy {
+    behaviour: EnemyBehaviour,
+    fire_delay: Duration
+}
+
+impl Enemy {
+    pub fn trail_color(&self, theme: &Theme) -> Color {
+        let mut trail_color = self.behaviour.color(theme);
+        trail_color.set_a(SHIP_TRAIL_ALPHA);
+        trail_color
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct EnemyTimer { duration: Duration }
+
+impl Default for EnemyTimer {
+    fn default() -> Self {
+        Self { duration: Duration::from_millis(ENEMY_FIRST_SPAWN_MS) }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn enemy_spawner(mut commands: Commands, mut enemy_timer: ResMut<EnemyTimer>, enemy_query: Query<&Enemy>, planet_query: Query<(&Transform, &Mass, &Planet)>, mut rng: ResMut<GameRng>, tuning: Res<Tuning>, time: Res<Time>, theme: Res<Theme>) {
+    if enemy_timer.duration > time.delta() {
+        enemy_timer.duration -= time.delta();
+        return;
+    }
+
+    let rng = &mut rng.rng;
+    enemy_timer.duration = Duration::from_millis(rng.gen_range(ENEMY_SPAWN_DELAY_MIN_MS..ENEMY_SPAWN_DELAY_MAX_MS));
+
+    let (planet_transform, Mass(planet_mass), planet) = planet_query.single();
+    if planet.collapsing || enemy_query.iter().count() >= ENEMY_MAX_COUNT {
+        return;
+    }
+
+    // arrive on a circular orbit so the enemy isn't immediately dragged into the planet
+    let spawn_angle: f32 = rng.gen_range(0.0..2.0 * std::f32::consts::PI);
+    let radial = Vec2::new(spawn_angle.cos(), spawn_angle.sin());
+    let position = planet_transform.translation + (radial * ENEMY_SPAWN_DISTANCE).extend(0.0);
+    let orbit_speed = circular_orbit_speed(tuning.gravity, *planet_mass, ENEMY_MASS, ENEMY_SPAWN_DISTANCE);
+    let velocity = radial.perp() * orbit_speed;
+    let behaviour = EnemyBehaviour::ALL[rng.gen_range(0..EnemyBehaviour::ALL.len())];
+    let enemy = Enemy { behaviour, fire_delay: Duration::from_millis(ENEMY_FIRE_DELAY_MS) };
+    let trail_color = enemy.trail_color(&theme);
+
+    commands.spawn((enemy,
+                    ShipInput::default(),
+                    Radius(ENEMY_RADIUS),
+                    Mass(ENEMY_MASS),
+                    Transform::from_translation(position).with_rotation(Quat::from_rotation_z(spawn_angle)),
+                    Velocity(velocity),
+                    AngularVelocity(0.0),
+                    ParticleEmitter::new(&SHIP_TRAIL, Some(trail_color))));
+}
+
+type EnemyPlanQuery<'w, 's> = Query<'w, 's, (&'static Enemy, &'static Transform, &'static Velocity, &'static AngularVelocity, &'static mut ShipInput)>;
+
+pub fn enemy_ai(mut enemy_query: EnemyPlanQuery, ship_query: Query<(&Transform, &Velocity, &Mass), With<Ship>>, asteroid_query: Query<&Transform, With<Asteroid>>, planet_query: Query<(&Transform, &Mass, &Radius, &Planet)>, tuning: Res<Tuning>) {
+    let (planet_transform, Mass(planet_mass), Radius(planet_radius), planet) = planet_query.single();
+    let planet = BotPlanet {
+        position: planet_transform.translation.truncate(),
+        radius: *planet_radius,
+        mass: *planet_mass,
+        gravity: tuning.gravity,
+        collapsing: planet.collapsing
+    };
+
+    for (enemy, transform, Velocity(velocity), AngularVelocity(angular_velocity), mut input) in &mut enemy_query {
+        let position = transform.translation.truncate();
+        let facing = (transform.rotation * Vec3::Y).truncate();
+        *input = ShipInput::default();
+
+        if !planet.collapsing && lowest_altitude(position, *velocity, ENEMY_MASS, &planet, ENEMY_PLAN_TIME) < ENEMY_SAFE_ALTITUDE {
+            let error = steer_towards(facing, *angular_velocity, escape_direction(position, *velocity, &planet), &mut input);
+            input.thrust = error.abs() < ENEMY_THRUST_CONE;
+            continue;
+        }
+
+        let target = ship_query.iter()
+            .min_by(|(a, _, _), (b, _, _)| {
+                Vec2::distance(a.translation.truncate(), position).total_cmp(&Vec2::distance(b.translation.truncate(), position))
+            });
+        let Some((target_transform, Velocity(target_velocity), Mass(target_mass))) = target else {
+            continue;
+        };
+
+        let target_position = target_transform.translation.truncate();
+        let lead_time = Vec2::distance(target_position, position) / tuning.bullet_velocity;
+        let lead_position = predict_position(target_position, *target_velocity, *target_mass, &planet, lead_time);
+        let to_lead = (lead_position - position).normalize_or_zero();
+
+        match enemy.behaviour {
+            EnemyBehaviour::Pursuit => {
+                let error = steer_towards(facing, *angular_velocity, to_lead, &mut input);
+                let closing_speed = velocity.dot(to_lead);
+                input.thrust = error.abs() < ENEMY_THRUST_CONE && closing_speed < ENEMY_PURSUIT_SPEED;
+            }
+            EnemyBehaviour::OrbitSnipe => {
+                let correction = orbit_correction(position, *velocity, ENEMY_MASS, &planet, ENEMY_ORBIT_ALTITUDE);
+                if correction.length() > ENEMY_ORBIT_TOLERANCE {
+                    let error = steer_towards(facing, *angular_velocity, correction.normalize(), &mut input);
+                    input.thrust = error.abs() < ENEMY_THRUST_CONE;
+                } else {
+                    steer_towards(facing, *angular_velocity, to_lead, &mut input);
+                }
+            }
+            EnemyBehaviour::Kamikaze => {
+                let error = steer_towards(facing, *angular_velocity, to_lead, &mut input);
+                input.thrust = error.abs() < ENEMY_THRUST_CONE;
+            }
+        }
+
+        if enemy.behaviour.shoots() {
+            let in_sights = |target: Vec2| {
+                let offset = target - position;
+                offset.length() < ENEMY_FIRE_RANGE && facing.angle_between(offset).abs() < ENEMY_FIRE_CONE
+            };
+            input.fire = in_sights(lead_position) || asteroid_query.iter().any(|asteroid| in_sights(asteroid.translation.truncate()));
+        }
+    }
+}
+
+pub fn enemy_control(mut commands: Commands, mut query: Query<(&mut Transform, &mut Velocity, &mut AngularVelocity, &mut Enemy, &ShipInput)>, mut sounds: EventWriter<SoundEffect>, tuning: Res<Tuning>, time: Res<Time>) {
+    for (mut transform, mut velocity, mut angular_velocity, mut enemy, input) in &mut query {
+        apply_ship_input(&mut transform, &mut velocity, &mut angular_velocity, input, time.delta_seconds());
+
+        enemy.fire_delay = enemy.fire_delay.saturating_sub(time.delta());
+        if enemy.fire_delay.is_zero() && input.fire {
+            let bullet_velocity = transform.rotation * Vec3::new(0.0, tuning.bullet_velocity, 0.0);
+            let bullet_position = transform.translation + transform.rotation * ENEMY_CORNERS[0];
+            commands.spawn((Bullet { owner: BulletOwner::Enemy }, Lifetime(Duration::from_millis(BULLET_LIFETIME_MS)), Radius(BULLET_RADIUS), Mass(BULLET_MASS), Transform::from_translation(bullet_position), Velocity(bullet_velocity.truncate())));
+            enemy.fire_delay = Duration::from_millis(ENEMY_FIRE_DELAY_MS);
+            sounds.send(SoundEffect::Laser);
+        }
+    }
+}
+
+pub fn enemy_collision(mut commands: Commands, enemy_query: Query<(Entity, &Radius, &Transform, &Velocity, &Enemy)>, bullet_query: Query<(Entity, &Radius, &Transform, &Bullet)>, ship_query: VulnerableShipQuery, mut game: ResMut<Game>, mut stats: ResMut<Stats>, mut sounds: EventWriter<SoundEffect>) {
+    let bullets = in_position_order(bullet_query.iter(), |item| item.2);
+    let ships = in_position_order(ship_query.iter(), |item| item.2);
+    for (enemy_entity, Radius(enemy_radius), enemy_transform, enemy_velocity, enemy) in in_position_order(enemy_query.iter(), |item| item.2) {
+        let mut destroyed = false;
+        for &(bullet_entity, Radius(bullet_radius), bullet_transform, bullet) in &bullets {
+            let BulletOwner::Player(player) = bullet.owner else {
+                continue;
+            };
+            if Vec3::distance(enemy_transform.translation, bullet_transform.translation) < enemy_radius + bullet_radius {
+                commands.entity(bullet_entity).despawn();
+                game.add_score(player, enemy.behaviour.score());
+                stats.hits += 1;
+                stats.enemies_destroyed += 1;
+                destroyed = true;
+                break;
+            }
+        }
+
+        // ramming takes out both ships
+        if !destroyed {
+            for &(ship_entity, Radius(ship_radius), ship_transform, ship_velocity) in &ships {
+                if Vec3::distance(enemy_transform.translation, ship_transform.translation) < enemy_radius + ship_radius {
+                    commands.entity(ship_entity).despawn();
+                    spawn_effect(&mut commands, &EXPLOSION, ship_transform.translation, **ship_velocity);
+                    destroyed = true;
+                    break;
+                }
+            }
+        }
+
+        if destroyed {
+            commands.entity(enemy_entity).despawn();
+            sounds.send(SoundEffect::Explosion { radius: *enemy_radius });
+            spawn_effect(&mut commands, &EXPLOSION, enemy_transform.translation, **enemy_velocity);
+        }
+    }
+}
+
+pub fn enemy_shape(mut commands: Commands, query: Query<(Entity, &Enemy), Added<Enemy>>, theme: Res<Theme>) {
+    for (entity, enemy) in &query {
+        let outline = VectorPath::polygon(ENEMY_CORNERS.iter().map(|point| point.truncate()), enemy.behaviour.color(&theme));
+        commands.entity(entity).insert(VectorShape::new(outline, LAYER_SHAPES));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::simulation_world;
+
+    #[test]
+    fn enemies_fire_at_the_tuned_bullet_speed() {
+        let mut world = simulation_world(5, 1);
+        world.resource_mut::<Tuning>().bullet_velocity *= 2.0;
+        world.insert_resource(Time::default());
+        world.spawn((Enemy { behaviour: EnemyBehaviour::Pursuit, fire_delay: Duration::ZERO }, Transform::from_xyz(300.0, 0.0, 0.0), Velocity(Vec2::ZERO), AngularVelocity(0.0), ShipInput { fire: true, ..default() }));
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(enemy_control);
+        stage.run(&mut world);
+
+        let speeds: Vec<f32> = world.query::<(&Bullet, &Velocity)>().iter(&world)
+            .filter(|(bullet, _)| matches!(bullet.owner, BulletOwner::Enemy))
+            .map(|(_, Velocity(velocity))| velocity.length())
+            .collect();
+        assert_eq!(speeds.len(), 1);
+        assert!((speeds[0] - world.resource::<Tuning>().bullet_velocity).abs() < 1e-3);
+    }
+}
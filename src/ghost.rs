@@ -0,0 +1,327 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::particles::{ParticleEmitter, SHIP_TRAIL};
+use crate::prefs::{Preferences, PREFERENCES_PATH};
+use crate::store;
+use crate::theme::Theme;
+use crate::vector::{VectorPath, VectorShape, LAYER_SHAPES};
+use crate::*;
+
+pub const HIGH_SCORES_PATH: &str = "highscores.json";
+const HIGH_SCORES_VERSION: u32 = 1;
+const HIGH_SCORE_COUNT: usize = 10;
+const GHOST_SAMPLE_MS: u64 = 50;
+const GHOST_ALPHA: f32 = 0.35;
+const GHOST_TRAIL_ALPHA: f32 = SHIP_TRAIL_ALPHA * 0.4;
+const GHOST_MAX_STEP: f32 = 150.0;            // further than this between samples is a teleport, not a glide
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HighScore {
+    pub score: u32,
+    pub players: usize,
+    pub mode: GameMode,
+    pub seed: u64,
+    pub time: u64
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct GhostShip {
+    player: usize,
+    position: Vec2,
+    rotation: Quat
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct GhostSample {
+    at: u64,
+    score: u32,
+    ships: Vec<GhostShip>
+}
+
+// where every ship was and what the score stood at through a run, on the game clock
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GhostRun {
+    seed: u64,
+    players: usize,
+    mode: GameMode,
+    score: u32,
+    samples: Vec<GhostSample>
+}
+
+impl GhostRun {
+    fn new(seed: u64, game: &Game) -> Self {
+        Self { seed, players: game.players, mode: game.mode, score: 0, samples: Vec::new() }
+    }
+
+    fn same_game(&self, other: &GhostRun) -> bool {
+        self.seed == other.seed && self.players == other.players && self.mode == other.mode
+    }
+
+    // the samples either side of a moment, or none once the run is over
+    fn around(&self, at: u64) -> Option<(&GhostSample, &GhostSample)> {
+        let next = self.samples.partition_point(|sample| sample.at <= at);
+        match next {
+            0 => self.samples.first().map(|sample| (sample, sample)),
+            next if next < self.samples.len() => Some((&self.samples[next - 1], &self.samples[next])),
+            _ => None,
+        }
+    }
+
+    fn score_at(&self, at: u64) -> u32 {
+        let next = self.samples.partition_point(|sample| sample.at <= at);
+        next.checked_sub(1).map_or(0, |index| self.samples[index].score)
+    }
+}
+
+// the best scores so far and, for every seed they were set on, the best run to race
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HighScores {
+    pub table: Vec<HighScore>,
+    ghosts: Vec<GhostRun>
+}
+
+impl HighScores {
+    pub fn load(path: &str) -> Self {
+        store::load(path, HIGH_SCORES_VERSION)
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        store::save(path, HIGH_SCORES_VERSION, self)
+    }
+
+    fn ghost(&self, run: &GhostRun) -> Option<&GhostRun> {
+        self.ghosts.iter().find(|ghost| ghost.same_game(run))
+    }
+
+    // the personal best for this many players in this mode, whatever seed it was set on
+    fn best_ghost(&self, game: &Game) -> Option<&GhostRun> {
+        self.ghosts.iter()
+            .filter(|ghost| ghost.players == game.players && ghost.mode == game.mode)
+            .max_by_key(|ghost| ghost.score)
+    }
+
+    fn record(&mut self, score: HighScore, run: GhostRun) {
+        self.table.push(score);
+        self.table.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        self.table.truncate(HIGH_SCORE_COUNT);
+
+        match self.ghosts.iter_mut().find(|ghost| ghost.same_game(&run)) {
+            Some(ghost) if ghost.score < run.score => *ghost = run,
+            Some(_) => {}
+            None => self.ghosts.push(run),
+        }
+        // a ghost is only kept while its run is still on the table
+        let table = &self.table;
+        self.ghosts.retain(|ghost| table.iter().any(|entry| entry.seed == ghost.seed && entry.players == ghost.players && entry.mode == ghost.mode));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct GhostRace {
+    pub enabled: bool,
+    rival: Option<GhostRun>,      // the personal best on this seed, when racing it
+    track: Option<GhostRun>       // this run, sampled as it goes
+}
+
+impl GhostRace {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, ..default() }
+    }
+
+    // how far ahead of the ghost the live score is, while there is one
+    pub fn score_delta(&self, game: &Game) -> Option<i64> {
+        self.rival.as_ref().map(|rival| game.score as i64 - rival.score_at(game.time) as i64)
+    }
+}
+
+#[derive(Component)]
+pub struct Ghost(usize);
+
+pub fn toggle_ghost(keyboard_input: Res<Input<KeyCode>>, mut race: ResMut<GhostRace>, mut preferences: ResMut<Preferences>) {
+    if keyboard_input.just_pressed(KeyCode::O) {
+        race.enabled = !race.enabled;
+        preferences.ghost = race.enabled;
+        if let Err(err) = preferences.save(PREFERENCES_PATH) {
+            warn!("could not save preferences: {}", err);
+        }
+    }
+}
+
+// racing replays the personal best's seed, unless the seed was fixed or the run is a continued one
+pub fn pick_ghost_seed(race: Res<GhostRace>, high_scores: Res<HighScores>, game: Res<Game>, mut rng: ResMut<GameRng>, resume: Option<Res<Resume>>) {
+    if !race.enabled || rng.fixed || resume.is_some() {
+        return;
+    }
+    if let Some(best) = high_scores.best_ghost(&game) {
+        *rng = GameRng::new(best.seed, false);
+    }
+}
+
+// runs after the seed is settled, a continued run races on from wherever its clock stands
+pub fn start_ghost(mut commands: Commands, mut race: ResMut<GhostRace>, high_scores: Res<HighScores>, game: Res<Game>, rng: Res<GameRng>, theme: Res<Theme>) {
+    let track = GhostRun::new(rng.seed, &game);
+    race.rival = high_scores.ghost(&track).filter(|_| race.enabled).cloned();
+    race.track = Some(track);
+
+    let Some((start, _)) = race.rival.as_ref().and_then(|rival| rival.around(game.time)) else {
+        return;
+    };
+    for ship in &start.ships {
+        let mut color = theme.players[ship.player];
+        color.set_a(GHOST_ALPHA);
+        let mut trail_color = theme.players[ship.player];
+        trail_color.set_a(GHOST_TRAIL_ALPHA);
+        commands.spawn((Ghost(ship.player),
+                        Transform::from_translation(ship.position.extend(0.0)).with_rotation(ship.rotation),
+                        VectorShape::new(VectorPath::polygon(SHIP_CORNERS.iter().map(|point| point.truncate()), color), LAYER_SHAPES),
+                        ParticleEmitter::new(&SHIP_TRAIL, Some(trail_color))));
+    }
+}
+
+pub fn record_ghost(mut race: ResMut<GhostRace>, ship_query: Query<(&Ship, &Transform)>, game: Res<Game>) {
+    let Some(track) = &mut race.track else {
+        return;
+    };
+    if track.samples.last().is_some_and(|sample| game.time < sample.at + GHOST_SAMPLE_MS) {
+        return;
+    }
+    let ships = ship_query.iter()
+        .map(|(ship, transform)| GhostShip { player: ship.player, position: transform.translation.truncate(), rotation: transform.rotation })
+        .collect();
+    track.samples.push(GhostSample { at: game.time, score: game.score, ships });
+    track.score = game.score;
+}
+
+// ghosts glide between samples, vanish where their ship was lost and are gone once the best run ended
+pub fn move_ghosts(mut commands: Commands, race: Res<GhostRace>, mut ghost_query: Query<(Entity, &Ghost, &mut Transform)>, game: Res<Game>) {
+    let Some(rival) = &race.rival else {
+        return;
+    };
+    let Some((before, after)) = rival.around(game.time) else {
+        for (entity, _, _) in &ghost_query {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+    let span = after.at.saturating_sub(before.at).max(1);
+    let t = (game.time.saturating_sub(before.at) as f32 / span as f32).clamp(0.0, 1.0);
+
+    for (entity, Ghost(player), mut transform) in &mut ghost_query {
+        let find = |sample: &GhostSample| sample.ships.iter().find(|ship| ship.player == *player).cloned();
+        let Some(from) = find(before) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let position = match find(after) {
+            Some(to) if from.position.distance(to.position) < GHOST_MAX_STEP => {
+                transform.rotation = from.rotation.slerp(to.rotation, t);
+                from.position.lerp(to.position, t)
+            }
+            _ => {
+                transform.rotation = from.rotation;
+                from.position
+            }
+        };
+        transform.translation = position.extend(0.0);
+    }
+}
+
+// every finished run goes in the table, and becomes the ghost for its seed if it beat the last one
+pub fn finish_ghost(mut race: ResMut<GhostRace>, mut high_scores: ResMut<HighScores>, game: Res<Game>) {
+    race.rival = None;
+    let Some(track) = race.track.take() else {
+        return;
+    };
+    let score = HighScore { score: game.score, players: game.players, mode: game.mode, seed: track.seed, time: game.time };
+    high_scores.record(score, track);
+    if let Err(err) = high_scores.save(HIGH_SCORES_PATH) {
+        warn!("could not save high scores: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use super::*;
+    use crate::save::start_world;
+    use crate::sim::{run_ticks, set_ship_inputs, simulation_world, INPUT_FIRE, INPUT_LEFT, INPUT_THRUST};
+
+    fn ghost(seed: u64, players: usize, score: u32) -> GhostRun {
+        GhostRun { seed, players, mode: GameMode::Coop, score, samples: Vec::new() }
+    }
+
+    fn seed_for(enabled: bool, fixed: bool) -> u64 {
+        let mut world = World::new();
+        world.insert_resource(GhostRace::new(enabled));
+        world.insert_resource(HighScores { table: Vec::new(), ghosts: vec![ghost(11, 1, 500), ghost(22, 1, 900), ghost(33, 2, 2000)] });
+        world.insert_resource(Game::new());
+        world.insert_resource(GameRng::new(7, fixed));
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(pick_ghost_seed);
+        stage.run(&mut world);
+        world.resource::<GameRng>().seed
+    }
+
+    #[test]
+    fn racing_starts_on_the_personal_best_seed() {
+        assert_eq!(seed_for(true, false), 22);
+        assert_eq!(seed_for(false, false), 7);
+        assert_eq!(seed_for(true, true), 7);
+    }
+
+    #[test]
+    fn only_runs_on_the_table_keep_their_ghost() {
+        let mut high_scores = HighScores::default();
+        for seed in 0..(HIGH_SCORE_COUNT as u64 + 5) {
+            let score = 100 * (seed as u32 % 7 + 1);
+            high_scores.record(HighScore { score, players: 1, mode: GameMode::Coop, seed, time: 0 }, ghost(seed, 1, score));
+        }
+        assert_eq!(high_scores.table.len(), HIGH_SCORE_COUNT);
+        assert_eq!(high_scores.ghosts.len(), HIGH_SCORE_COUNT);
+        assert!(high_scores.ghosts.iter().all(|ghost| high_scores.table.iter().any(|entry| entry.seed == ghost.seed)));
+    }
+
+    // a run on the seed, started after the title screen's attract run has been going for a while
+    fn play_on(seed: u64, title_ticks: u32, mut each_tick: impl FnMut(&mut World)) {
+        let mut world = simulation_world(99, 1);
+        run_ticks(&mut world, title_ticks);
+        start_world(&mut world, Some(GameRng::new(seed, false)));
+        for tick in 0..900 {
+            set_ship_inputs(&mut world, &[if tick % 50 < 25 { INPUT_THRUST | INPUT_LEFT } else { INPUT_FIRE }]);
+            run_ticks(&mut world, 1);
+            each_tick(&mut world);
+        }
+    }
+
+    #[test]
+    fn the_same_play_on_the_ghost_seed_flies_with_the_ghost() {
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(record_ghost);
+        let mut recorded = None;
+        play_on(5, 100, |world| {
+            if world.get_resource::<GhostRace>().is_none() {
+                let track = GhostRun::new(5, world.resource::<Game>());
+                world.insert_resource(GhostRace { track: Some(track), ..default() });
+            }
+            stage.run(world);
+            recorded = world.resource::<GhostRace>().track.clone();
+        });
+        let rival = recorded.unwrap();
+
+        let mut compared = 0;
+        play_on(5, 700, |world| {
+            let time = world.resource::<Game>().time;
+            let Some(sample) = rival.samples.iter().find(|sample| sample.at == time) else {
+                return;
+            };
+            let ships: Vec<Vec2> = world.query_filtered::<&Transform, With<Ship>>().iter(world).map(|transform| transform.translation.truncate()).collect();
+            let ghosts: Vec<Vec2> = sample.ships.iter().map(|ship| ship.position).collect();
+            assert_eq!(ships, ghosts, "apart at {} ms", time);
+            compared += 1;
+        });
+        assert!(compared > 100);
+    }
+}
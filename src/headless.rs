@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+
+use crate::autopilot::Autopilot;
+use crate::sim::{drop_effects, run_ticks, simulation_world, SIM_TICK_RATE};
+use crate::{format_time, Game, Mass, Planet, Ship};
+
+const HEADLESS_DEFAULT_RUNS: u64 = 10;
+const HEADLESS_DEFAULT_SEED: u64 = 1;
+const HEADLESS_DEFAULT_SECONDS: u32 = 600;
+
+pub struct HeadlessRun {
+    pub seed: u64,
+    pub ticks: u32,
+    pub score: u32,
+    pub peak_planet_mass: f32,
+    pub survived: bool,
+    pub collapsed: bool
+}
+
+// plays one single player game on the game's own rules with the autopilot at the controls
+pub fn run_autopilot(seed: u64, max_ticks: u32) -> HeadlessRun {
+    let mut world = simulation_world(seed, 1);
+    let ships: Vec<Entity> = world.query_filtered::<Entity, With<Ship>>().iter(&world).collect();
+    for ship in ships {
+        world.entity_mut(ship).insert(Autopilot);
+    }
+
+    let mut ship_query = world.query::<&Ship>();
+    let mut planet_query = world.query::<(&Planet, &Mass)>();
+    let mut ticks = 0;
+    let mut peak_planet_mass = 0.0f32;
+    let mut collapsed = false;
+    let mut survived = true;
+    while ticks < max_ticks {
+        run_ticks(&mut world, 1);
+        drop_effects(&mut world);
+        ticks += 1;
+
+        let (planet, Mass(mass)) = planet_query.single(&world);
+        collapsed = planet.collapsing;
+        peak_planet_mass = if collapsed { planet.collapse_init_mass } else { peak_planet_mass.max(*mass) };
+        survived = ship_query.iter(&world).next().is_some();
+        if collapsed || !survived {
+            break;
+        }
+    }
+
+    HeadlessRun {
+        seed,
+        ticks,
+        score: world.resource::<Game>().score,
+        peak_planet_mass,
+        survived,
+        collapsed
+    }
+}
+
+// rusteroids --headless [runs] [first seed] [max seconds]
+pub fn headless_main(args: &[String]) {
+    let runs = args.first().and_then(|arg| arg.parse().ok()).unwrap_or(HEADLESS_DEFAULT_RUNS);
+    let first_seed = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(HEADLESS_DEFAULT_SEED);
+    let max_seconds = args.get(2).and_then(|arg| arg.parse().ok()).unwrap_or(HEADLESS_DEFAULT_SECONDS);
+    let max_ticks = max_seconds * SIM_TICK_RATE;
+
+    let mut total_ticks = 0u64;
+    let mut total_score = 0u64;
+    for seed in first_seed..first_seed + runs {
+        let run = run_autopilot(seed, max_ticks);
+        let outcome = if run.collapsed {
+            "star collapsed"
+        } else if !run.survived {
+            "ship destroyed"
+        } else {
+            "time limit"
+        };
+        println!("seed {:>6}  time {:>6}  score {:>7}  peak mass {:>6.0}  {}", run.seed, format_time(run.ticks as u64 * 1000 / SIM_TICK_RATE as u64), run.score, run.peak_planet_mass, outcome);
+        total_ticks += run.ticks as u64;
+        total_score += run.score as u64;
+    }
+
+    if let (Some(average_ticks), Some(average_score)) = (total_ticks.checked_div(runs), total_score.checked_div(runs)) {
+        println!("average time:  {}", format_time(average_ticks * 1000 / SIM_TICK_RATE as u64));
+        println!("average score: {}", average_score);
+    }
+}
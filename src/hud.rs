@@ -0,0 +1,309 @@
+use bevy::prelude::*;
+
+use crate::ghost::GhostRace;
+use crate::locale::{LocalizedText, Strings};
+use crate::theme::Theme;
+use crate::tween::lerp_color;
+use crate::{Game, Mass, Planet, Radius, Ship, Velocity, FONT_PATH, PLANET_MASS_COLLAPSE_TRIGGER, SHIP_TELEPORT_COOLDOWN};
+
+const HUD_PADDING: f32 = 12.0;
+const HUD_SIDE_WIDTH: f32 = 220.0;            // the corners are kept the same width so the gauge stays centred
+const HUD_TEXT_SIZE: f32 = 16.0;
+const HUD_LABEL_SIZE: f32 = 12.0;
+
+const HUD_MASS_GAUGE_WIDTH: f32 = 260.0;
+const HUD_MASS_GAUGE_HEIGHT: f32 = 6.0;
+const HUD_MASS_GAUGE_TOP: f32 = 36.0;         // clear of the score
+const HUD_MASS_GAUGE_MAX: f32 = PLANET_MASS_COLLAPSE_TRIGGER * 1.25;
+
+const HUD_TELEPORT_BAR_WIDTH: f32 = 120.0;
+const HUD_TELEPORT_BAR_HEIGHT: f32 = 4.0;
+const HUD_SHIP_LOST_ALPHA: f32 = 0.3;
+
+#[derive(Component)]
+pub struct HudRoot;
+
+#[derive(Component)]
+pub struct HudTimerText;
+
+#[derive(Component)]
+pub struct HudPowerUpText;
+
+#[derive(Component)]
+pub struct HudGhostText;
+
+#[derive(Component)]
+pub struct HudMassFill;
+
+#[derive(Component)]
+pub struct HudTeleportFill(usize);
+
+#[derive(Component)]
+pub struct HudShipText(usize);
+
+fn bar(size: Vec2, color: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+            ..default()
+        },
+        background_color: color.into(),
+        ..default()
+    }
+}
+
+fn bar_fill(color: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+            ..default()
+        },
+        background_color: color.into(),
+        ..default()
+    }
+}
+
+fn label(text: &str, font: &Handle<Font>, size: f32, color: Color) -> TextBundle {
+    TextBundle::from_section(text, TextStyle { font: font.clone(), font_size: size, color })
+}
+
+fn column(align_items: AlignItems) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            align_items,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+// laid out with bevy_ui so everything anchors to the window edges whatever its size
+pub fn setup_hud(mut commands: Commands, game: Res<Game>, asset_server: Res<AssetServer>, strings: Res<Strings>, theme: Res<Theme>) {
+    let font: Handle<Font> = asset_server.load(FONT_PATH);
+    let root = NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::SpaceBetween,
+            padding: UiRect::all(Val::Px(HUD_PADDING)),
+            ..default()
+        },
+        ..default()
+    };
+    let top = NodeBundle {
+        style: Style {
+            justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::FlexStart,
+            ..default()
+        },
+        ..default()
+    };
+    let side = NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(HUD_SIDE_WIDTH), Val::Auto),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        ..default()
+    };
+
+    commands.spawn((root, HudRoot)).with_children(|root| {
+        root.spawn(top).with_children(|top| {
+            top.spawn(side.clone()).with_children(|side| {
+                side.spawn((label(strings.get("hud.time"), &font, HUD_LABEL_SIZE, theme.hud_label), LocalizedText("hud.time")));
+                side.spawn((label("0:00", &font, HUD_TEXT_SIZE, theme.hud_text), HudTimerText));
+                side.spawn((label("", &font, HUD_TEXT_SIZE, theme.power_up), HudPowerUpText));
+                side.spawn((label("", &font, HUD_TEXT_SIZE, theme.hud_text), HudGhostText));
+            });
+
+            // the mark sits where the star starts to collapse
+            let mut gauge = column(AlignItems::Center);
+            gauge.style.margin = UiRect::top(Val::Px(HUD_MASS_GAUGE_TOP));
+            top.spawn(gauge).with_children(|gauge| {
+                gauge.spawn((label(strings.get("hud.star_mass"), &font, HUD_LABEL_SIZE, theme.hud_label), LocalizedText("hud.star_mass")));
+                gauge.spawn(bar(Vec2::new(HUD_MASS_GAUGE_WIDTH, HUD_MASS_GAUGE_HEIGHT), theme.hud_bar)).with_children(|bar| {
+                    bar.spawn((bar_fill(theme.mass), HudMassFill));
+                    bar.spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect { left: Val::Percent(100.0 * PLANET_MASS_COLLAPSE_TRIGGER / HUD_MASS_GAUGE_MAX), top: Val::Px(-2.0), ..default() },
+                            size: Size::new(Val::Px(2.0), Val::Px(HUD_MASS_GAUGE_HEIGHT + 4.0)),
+                            ..default()
+                        },
+                        background_color: theme.danger.into(),
+                        ..default()
+                    });
+                });
+            });
+
+            top.spawn(side);
+        });
+
+        root.spawn(NodeBundle::default()).with_children(|bottom| {
+            for (player, player_color) in theme.players.iter().enumerate().take(game.players) {
+                let mut panel = column(AlignItems::FlexStart);
+                panel.style.margin = UiRect::right(Val::Px(HUD_PADDING * 2.0));
+                bottom.spawn(panel).with_children(|panel| {
+                    panel.spawn(label(&strings.format("hud.teleport", &[&(player + 1)]), &font, HUD_LABEL_SIZE, *player_color));
+                    panel.spawn(bar(Vec2::new(HUD_TELEPORT_BAR_WIDTH, HUD_TELEPORT_BAR_HEIGHT), theme.hud_bar)).with_children(|bar| {
+                        bar.spawn((bar_fill(theme.hud_label), HudTeleportFill(player)));
+                    });
+                    panel.spawn((label("", &font, HUD_TEXT_SIZE, theme.hud_text), HudShipText(player)));
+                });
+            }
+        });
+    });
+}
+
+pub fn update_hud_status(mut timer_query: Query<&mut Text, (With<HudTimerText>, Without<HudPowerUpText>)>, mut power_up_query: Query<&mut Text, With<HudPowerUpText>>, game: Res<Game>, strings: Res<Strings>) {
+    let seconds = game.time / 1000;
+    for mut text in &mut timer_query {
+        text.sections[0].value = format!("{}:{:02}", seconds / 60, seconds % 60);
+    }
+
+    // score multipliers are the only boost so far, anything else that runs out goes in this list too
+    let mut power_ups = Vec::new();
+    if game.score_multiplier > 1 {
+        power_ups.push(strings.format("hud.score_multiplier", &[&game.score_multiplier]));
+    }
+    for mut text in &mut power_up_query {
+        text.sections[0].value = power_ups.join("\n");
+    }
+}
+
+// the live score against the ghost's at the same moment of its run
+pub fn update_ghost_delta(mut query: Query<&mut Text, With<HudGhostText>>, race: Res<GhostRace>, game: Res<Game>, strings: Res<Strings>, theme: Res<Theme>) {
+    let delta = race.score_delta(&game);
+    for mut text in &mut query {
+        let section = &mut text.sections[0];
+        match delta {
+            Some(delta) if delta >= 0 => {
+                section.value = strings.format("hud.ghost_ahead", &[&strings.number(delta)]);
+                section.style.color = theme.safe;
+            }
+            Some(delta) => {
+                section.value = strings.format("hud.ghost_behind", &[&strings.number(-delta)]);
+                section.style.color = theme.danger;
+            }
+            None => section.value.clear(),
+        }
+    }
+}
+
+pub fn update_mass_gauge(planet_query: Query<&Mass, With<Planet>>, mut fill_query: Query<(&mut Style, &mut BackgroundColor), With<HudMassFill>>, theme: Res<Theme>) {
+    let Ok(Mass(mass)) = planet_query.get_single() else {
+        return;
+    };
+    let fraction = (mass / HUD_MASS_GAUGE_MAX).clamp(0.0, 1.0);
+    let danger = (mass / PLANET_MASS_COLLAPSE_TRIGGER).clamp(0.0, 1.0);
+    for (mut style, mut color) in &mut fill_query {
+        style.size.width = Val::Percent(100.0 * fraction);
+        *color = lerp_color(theme.mass, theme.danger, danger * danger).into();
+    }
+}
+
+type TeleportFillQuery<'w, 's> = Query<'w, 's, (&'static mut Style, &'static mut BackgroundColor, &'static HudTeleportFill)>;
+
+// speed and altitude above the star's surface for each ship, greyed out once it's gone
+pub fn update_ship_panels(ship_query: Query<(&Ship, &Transform, &Velocity)>, planet_query: Query<(&Transform, &Radius), With<Planet>>, mut fill_query: TeleportFillQuery, mut text_query: Query<(&mut Text, &HudShipText)>, strings: Res<Strings>, theme: Res<Theme>) {
+    let planet = planet_query.get_single().ok();
+    for (mut style, mut color, HudTeleportFill(player)) in &mut fill_query {
+        let ship = ship_query.iter().find(|(ship, _, _)| ship.player == *player);
+        let charge = ship.map_or(0.0, |(ship, _, _)| 1.0 - ship.teleport_cooldown / SHIP_TELEPORT_COOLDOWN);
+        style.size.width = Val::Percent(100.0 * charge);
+        *color = if charge >= 1.0 { theme.teleport_ready } else { theme.hud_label }.into();
+    }
+
+    for (mut text, HudShipText(player)) in &mut text_query {
+        let section = &mut text.sections[0];
+        match ship_query.iter().find(|(ship, _, _)| ship.player == *player) {
+            Some((_, transform, velocity)) => {
+                let altitude = planet.map_or(0.0, |(planet_transform, Radius(radius))| transform.translation.distance(planet_transform.translation) - radius);
+                section.value = strings.format("hud.ship", &[&format!("{:>4.0}", velocity.length()), &format!("{:>4.0}", altitude.max(0.0))]);
+                section.style.color = theme.hud_text;
+            }
+            None => {
+                section.value = strings.get("hud.lost").to_string();
+                section.style.color.set_a(HUD_SHIP_LOST_ALPHA);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::Language;
+
+    fn hud_world() -> World {
+        let mut world = World::new();
+        world.insert_resource(Game::new());
+        world.insert_resource(Strings::load(Language::English));
+        world.insert_resource(crate::theme::CLASSIC);
+        world
+    }
+
+    fn text(world: &World, entity: Entity) -> String {
+        world.get::<Text>(entity).unwrap().sections[0].value.clone()
+    }
+
+    fn width(world: &World, entity: Entity) -> Val {
+        world.get::<Style>(entity).unwrap().size.width
+    }
+
+    #[test]
+    fn the_timer_reads_minutes_and_seconds_and_boosts_are_listed() {
+        let mut world = hud_world();
+        let timer = world.spawn((Text::from_section("", default()), HudTimerText)).id();
+        let power_ups = world.spawn((Text::from_section("", default()), HudPowerUpText)).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(update_hud_status);
+        world.resource_mut::<Game>().time = 125_900;
+        stage.run(&mut world);
+        assert_eq!(text(&world, timer), "2:05");
+        assert_eq!(text(&world, power_ups), "");
+
+        world.resource_mut::<Game>().score_multiplier = 3;
+        stage.run(&mut world);
+        assert_eq!(text(&world, power_ups), "SCORE x3");
+    }
+
+    #[test]
+    fn the_mass_gauge_fills_with_the_star_and_stops_at_its_end() {
+        let mut world = hud_world();
+        let fill = world.spawn((Style::default(), BackgroundColor::default(), HudMassFill)).id();
+        let planet = world.spawn((Planet::new(), Mass(HUD_MASS_GAUGE_MAX / 2.0))).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(update_mass_gauge);
+        stage.run(&mut world);
+        assert_eq!(width(&world, fill), Val::Percent(50.0));
+
+        world.get_mut::<Mass>(planet).unwrap().0 = HUD_MASS_GAUGE_MAX * 2.0;
+        stage.run(&mut world);
+        assert_eq!(width(&world, fill), Val::Percent(100.0));
+        let color = Vec4::from(world.get::<BackgroundColor>(fill).unwrap().0);
+        assert!(color.abs_diff_eq(Vec4::from(crate::theme::CLASSIC.danger), 1e-5));
+    }
+
+    #[test]
+    fn ship_panels_show_charge_speed_and_altitude_until_the_ship_is_lost() {
+        let mut world = hud_world();
+        let fill = world.spawn((Style::default(), BackgroundColor::default(), HudTeleportFill(0))).id();
+        let panel = world.spawn((Text::from_section("", default()), HudShipText(0))).id();
+        world.spawn((Planet::new(), Transform::default(), Radius(50.0)));
+        let mut ship = Ship::new(0);
+        ship.teleport_cooldown = SHIP_TELEPORT_COOLDOWN / 4.0;
+        let ship = world.spawn((ship, Transform::from_xyz(150.0, 0.0, 0.0), Velocity(Vec2::new(30.0, 40.0)))).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(update_ship_panels);
+        stage.run(&mut world);
+        assert_eq!(width(&world, fill), Val::Percent(75.0));
+        assert_eq!(text(&world, panel), "SPD   50  ALT  100");
+
+        world.despawn(ship);
+        stage.run(&mut world);
+        assert_eq!(width(&world, fill), Val::Percent(0.0));
+        assert_eq!(text(&world, panel), "LOST");
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashSet;
+
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+
+use crate::camera::CameraRig;
+use crate::enemy::Enemy;
+use crate::locale::Strings;
+use crate::particles::Particle;
+use crate::plasma::Plasma;
+use crate::prefs::OptionChanged;
+use crate::sim::SIM_ARENA_HALF_SIZE;
+use crate::theme::Theme;
+use crate::vector::{VectorLines, VectorPath, LAYER_EFFECTS};
+use crate::{Asteroid, Bullet, Lifetime, Mass, Planet, Radius, Ship, Velocity, FONT_PATH, PLANET_MASS_COLLAPSE_TRIGGER};
+
+const INSPECTOR_LABEL_SIZE: f32 = 11.0;
+const INSPECTOR_LABEL_GAP: f32 = 6.0;         // between a body's edge and its label, on screen
+const INSPECTOR_PANEL_SIZE: f32 = 14.0;
+const INSPECTOR_PANEL_TOP: f32 = 110.0;       // clear of the hud timer and power-ups
+const INSPECTOR_VELOCITY_SCALE: f32 = 0.5;    // seconds of travel each velocity arrow shows
+const INSPECTOR_PICK_MARGIN: f32 = 10.0;      // clicks this close to a body still select it, on screen
+
+// developer overlay showing what the simulation is doing, toggled with F3
+#[derive(Resource, Default)]
+pub struct Inspector {
+    pub visible: bool,
+    pub selected: Option<Entity>
+}
+
+#[derive(Component)]
+pub struct InspectorLabel(Entity);
+
+#[derive(Component)]
+pub struct InspectorPanel;
+
+type BodyQuery<'w, 's> = Query<'w, 's, (Entity, &'static Transform, &'static Radius, Option<&'static Mass>, Option<&'static Velocity>, Option<&'static Lifetime>, Option<&'static Planet>)>;
+type LabelQuery<'w, 's> = Query<'w, 's, (Entity, &'static InspectorLabel, &'static mut Text, &'static mut Transform), Without<Radius>>;
+type KindItem<'a> = (Option<&'a Ship>, Option<&'a Planet>, Option<&'a Asteroid>, Option<&'a Bullet>, Option<&'a Enemy>, Option<&'a Plasma>, Option<&'a Particle>);
+type KindQuery<'w, 's> = Query<'w, 's, KindItem<'static>>;
+
+const KINDS: [&str; 8] = ["inspector.ships", "inspector.planets", "inspector.asteroids", "inspector.bullets", "inspector.enemies", "inspector.plasma", "inspector.particles", "inspector.other"];
+
+// index into KINDS for whatever an entity is
+fn kind((ship, planet, asteroid, bullet, enemy, plasma, particle): KindItem) -> usize {
+    [ship.is_some(), planet.is_some(), asteroid.is_some(), bullet.is_some(), enemy.is_some(), plasma.is_some(), particle.is_some()]
+        .iter()
+        .position(|is_kind| *is_kind)
+        .unwrap_or(KINDS.len() - 1)
+}
+
+fn camera_view(rig_query: &Query<&CameraRig>) -> (Vec2, f32) {
+    rig_query.get_single().map_or((Vec2::ZERO, 1.0), |rig| (rig.position, rig.zoom))
+}
+
+fn describe(mass: Option<&Mass>, radius: f32, velocity: Option<&Velocity>, lifetime: Option<&Lifetime>, planet: Option<&Planet>, strings: &Strings) -> String {
+    let mut lines = vec![strings.format("inspector.mass_radius", &[&mass.map_or("-".to_string(), |Mass(mass)| format!("{:.1}", mass)), &format!("{:.1}", radius)])];
+    if let Some(velocity) = velocity {
+        lines.push(strings.format("inspector.velocity", &[&format!("{:.0}", velocity.length()), &format!("{:.0}", velocity.y.atan2(velocity.x).to_degrees())]));
+    }
+    if let Some(Lifetime(lifetime)) = lifetime {
+        lines.push(strings.format("inspector.lifetime", &[&format!("{:.2}", lifetime.as_secs_f32())]));
+    }
+    if let Some(planet) = planet {
+        let growth = 100.0 * mass.map_or(0.0, |Mass(mass)| *mass) / PLANET_MASS_COLLAPSE_TRIGGER;
+        lines.push(strings.format("inspector.growth", &[&format!("{:.0}", growth), &planet.heat]));
+        if planet.collapsing {
+            lines.push(strings.format("inspector.collapse", &[&format!("{:.2}", planet.collapse_timer), &format!("{:.1}", planet.collapse_init_mass)]));
+        }
+    }
+    lines.join("\n")
+}
+
+pub fn toggle_inspector(keyboard_input: Res<Input<KeyCode>>, mut inspector: ResMut<Inspector>, mut options: EventWriter<OptionChanged>, strings: Res<Strings>) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        inspector.visible = !inspector.visible;
+        options.send(OptionChanged(strings.format("option.inspector", &[&strings.on_off(inspector.visible)])));
+    }
+}
+
+// clicking picks the body under the cursor, clicking empty space clears the selection
+pub fn select_entity(mouse_input: Res<Input<MouseButton>>, mut inspector: ResMut<Inspector>, windows: Res<Windows>, camera_query: Query<(&Camera, &GlobalTransform, &CameraRig)>, bodies: Query<(Entity, &Transform, &Radius)>) {
+    if !inspector.visible || !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let window = windows.get_primary().unwrap();
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    let Ok((camera, camera_transform, rig)) = camera_query.get_single() else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+    let (point, camera_zoom) = (ray.origin.truncate(), rig.zoom);
+
+    inspector.selected = bodies.iter()
+        .map(|(entity, transform, Radius(radius))| (entity, transform.translation.truncate().distance(point) - radius))
+        .filter(|(_, distance)| *distance <= INSPECTOR_PICK_MARGIN * camera_zoom)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity);
+}
+
+// collision radii and velocity arrows in the world, the selected body picked out
+pub fn draw_inspector(inspector: Res<Inspector>, bodies: BodyQuery, mut lines: ResMut<VectorLines>, theme: Res<Theme>) {
+    if !inspector.visible {
+        return;
+    }
+    for (entity, transform, Radius(radius), _, velocity, _, _) in &bodies {
+        let position = transform.translation.truncate();
+        let color = if inspector.selected == Some(entity) { theme.toast } else { theme.frame };
+        lines.path(VectorPath::circle(position, *radius, 24, color), LAYER_EFFECTS);
+        if let Some(Velocity(velocity)) = velocity {
+            lines.path(VectorPath::line(position, position + *velocity * INSPECTOR_VELOCITY_SCALE, theme.warning), LAYER_EFFECTS);
+        }
+    }
+}
+
+// a text label beside every body, kept in step with the bodies as they come and go
+#[allow(clippy::too_many_arguments)]
+pub fn update_inspector_labels(mut commands: Commands, inspector: Res<Inspector>, bodies: BodyQuery, mut label_query: LabelQuery, rig_query: Query<&CameraRig>, asset_server: Res<AssetServer>, strings: Res<Strings>, theme: Res<Theme>) {
+    if !inspector.visible {
+        for (entity, _, _, _) in &label_query {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let (camera_position, camera_zoom) = camera_view(&rig_query);
+    let label_position = |transform: &Transform, radius: f32| {
+        let screen = (transform.translation.truncate() - camera_position) / camera_zoom;
+        (screen + Vec2::new(radius / camera_zoom + INSPECTOR_LABEL_GAP, 0.0)).extend(1.0)
+    };
+
+    let mut labelled = HashSet::new();
+    for (entity, InspectorLabel(target), mut text, mut transform) in &mut label_query {
+        match bodies.get(*target) {
+            Ok((_, body_transform, Radius(radius), mass, velocity, lifetime, planet)) => {
+                text.sections[0].value = describe(mass, *radius, velocity, lifetime, planet, &strings);
+                transform.translation = label_position(body_transform, *radius);
+                labelled.insert(*target);
+            }
+            Err(_) => commands.entity(entity).despawn(),
+        }
+    }
+
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: INSPECTOR_LABEL_SIZE,
+        color: theme.hint,
+    };
+    for (entity, transform, Radius(radius), mass, velocity, lifetime, planet) in &bodies {
+        if labelled.contains(&entity) {
+            continue;
+        }
+        commands.spawn(
+            (Text2dBundle {
+                text: Text::from_section(describe(mass, *radius, velocity, lifetime, planet, &strings), text_style.clone())
+                    .with_alignment(TextAlignment::CENTER_LEFT),
+                transform: Transform::from_translation(label_position(transform, *radius)),
+                ..default()
+            },
+            InspectorLabel(entity))
+        );
+    }
+}
+
+// entity counts, frame timing and the selected body's details down the left side
+#[allow(clippy::too_many_arguments)]
+pub fn update_inspector_panel(mut commands: Commands, mut inspector: ResMut<Inspector>, mut panel_query: Query<(Entity, &mut Text), With<InspectorPanel>>, kind_query: KindQuery, bodies: BodyQuery, diagnostics: Res<Diagnostics>, asset_server: Res<AssetServer>, strings: Res<Strings>, theme: Res<Theme>) {
+    if !inspector.visible {
+        for (entity, _) in &panel_query {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let mut counts = [0; KINDS.len()];
+    for item in &kind_query {
+        counts[kind(item)] += 1;
+    }
+    let fps = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS).and_then(|fps| fps.smoothed()).unwrap_or(0.0);
+    let frame_time = diagnostics.get(FrameTimeDiagnosticsPlugin::FRAME_TIME).and_then(|frame_time| frame_time.smoothed()).unwrap_or(0.0);
+
+    let mut lines = vec![strings.format("inspector.frame", &[&format!("{:.0}", fps), &format!("{:.1}", frame_time)])];
+    lines.push(strings.format("inspector.entities", &[&kind_query.iter().count()]));
+    for (key, count) in KINDS.iter().zip(counts) {
+        lines.push(strings.format("inspector.count", &[&strings.get(key), &count]));
+    }
+
+    if let Some(selected) = inspector.selected {
+        match (kind_query.get(selected), bodies.get(selected)) {
+            (Ok(item), Ok((_, transform, Radius(radius), mass, velocity, lifetime, planet))) => {
+                lines.push(String::new());
+                lines.push(strings.format("inspector.selected", &[&strings.get(KINDS[kind(item)]), &format!("{:?}", selected)]));
+                lines.push(strings.format("inspector.position", &[&format!("{:.0}", transform.translation.x), &format!("{:.0}", transform.translation.y)]));
+                lines.push(describe(mass, *radius, velocity, lifetime, planet, &strings));
+            }
+            _ => inspector.selected = None,
+        }
+    }
+    let report = lines.join("\n");
+
+    if let Ok((_, mut text)) = panel_query.get_single_mut() {
+        text.sections[0].value = report;
+        return;
+    }
+    let half_size = SIM_ARENA_HALF_SIZE;
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: INSPECTOR_PANEL_SIZE,
+        color: theme.hint,
+    };
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section(report, text_style)
+                .with_alignment(TextAlignment::TOP_LEFT),
+            transform: Transform::from_xyz(-half_size.x + 20.0, half_size.y - INSPECTOR_PANEL_TOP, 1.0),
+            ..default()
+        },
+        InspectorPanel)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::Duration;
+
+    use super::*;
+    use crate::locale::Language;
+
+    #[test]
+    fn bodies_are_counted_under_their_first_kind() {
+        let ship = Ship::new(0);
+        let planet = Planet::new();
+        let asteroid = Asteroid { seed: 1 };
+        assert_eq!(KINDS[kind((Some(&ship), None, None, None, None, None, None))], "inspector.ships");
+        assert_eq!(KINDS[kind((None, Some(&planet), Some(&asteroid), None, None, None, None))], "inspector.planets");
+        assert_eq!(KINDS[kind((None, None, Some(&asteroid), None, None, None, None))], "inspector.asteroids");
+        assert_eq!(KINDS[kind((None, None, None, None, None, None, None))], "inspector.other");
+    }
+
+    #[test]
+    fn a_label_lists_only_what_the_body_has() {
+        let strings = Strings::load(Language::English);
+        assert_eq!(describe(None, 4.0, None, None, None, &strings), "m -  r 4.0");
+
+        let velocity = Velocity(Vec2::new(0.0, 20.0));
+        let lifetime = Lifetime(Duration::from_millis(1250));
+        assert_eq!(describe(Some(&Mass(2.0)), 4.0, Some(&velocity), Some(&lifetime), None, &strings), "m 2.0  r 4.0\nv 20 at 90 deg\nlife 1.25 s");
+    }
+
+    #[test]
+    fn a_planet_label_shows_its_growth_and_collapse() {
+        let strings = Strings::load(Language::English);
+        let mut planet = Planet::new();
+        planet.heat = 3;
+        let mass = Mass(PLANET_MASS_COLLAPSE_TRIGGER / 2.0);
+        assert_eq!(describe(Some(&mass), 50.0, None, None, Some(&planet), &strings).lines().last(), Some("growth 50 %  heat 3"));
+
+        planet.collapsing = true;
+        planet.collapse_timer = 0.5;
+        planet.collapse_init_mass = 2600.0;
+        assert_eq!(describe(Some(&mass), 50.0, None, None, Some(&planet), &strings).lines().last(), Some("collapsing 0.50 s from m 2600.0"));
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+
+use crate::vector::{VectorLines, VectorPath, LAYER_BACKGROUND};
+
+const LENS_EINSTEIN_SCALE: f32 = 2.0;      // einstein radius per sqrt unit of mass
+const LENS_MAX_MAGNIFICATION: f32 = 12.0;  // perfectly aligned sources would be infinitely bright
+const LENS_ARC_LENGTH: f32 = 2.0;          // length of an unmagnified star, images are stretched along the ring by their magnification
+const LENS_ARC_SEGMENTS: u32 = 6;
+const LENS_MIN_MAGNIFICATION: f32 = 0.02;  // fainter images aren't worth drawing
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LensImage {
+    pub position: Vec2,
+    pub magnification: f32
+}
+
+pub fn einstein_radius(mass: f32) -> f32 {
+    LENS_EINSTEIN_SCALE * mass.max(0.0).sqrt()
+}
+
+// the two images a point mass lens makes of a source, the outer one on the source's side and the inner one opposite
+pub fn lens_images(source: Vec2, lens: Vec2, einstein_radius: f32) -> [LensImage; 2] {
+    let offset = source - lens;
+    let beta = offset.length();
+    if einstein_radius <= 0.0 {
+        return [LensImage { position: source, magnification: 1.0 }, LensImage { position: lens, magnification: 0.0 }];
+    }
+
+    let direction = if beta > 0.0 { offset / beta } else { Vec2::X };
+    let root = (beta * beta + 4.0 * einstein_radius * einstein_radius).sqrt();
+    let outer = (beta + root) / 2.0;
+    let inner = (beta - root) / 2.0;
+
+    let u = beta / einstein_radius;
+    let (outer_magnification, inner_magnification) = if u > 0.0 {
+        let base = (u * u + 2.0) / (2.0 * u * (u * u + 4.0).sqrt());
+        (base + 0.5, base - 0.5)
+    } else {
+        (LENS_MAX_MAGNIFICATION, LENS_MAX_MAGNIFICATION)
+    };
+
+    [
+        LensImage { position: lens + direction * outer, magnification: outer_magnification.min(LENS_MAX_MAGNIFICATION) },
+        LensImage { position: lens + direction * inner, magnification: inner_magnification.min(LENS_MAX_MAGNIFICATION) },
+    ]
+}
+
+// draws a lensed star as an arc around the lens, so well aligned stars smear into an einstein ring
+pub fn draw_lensed_star(lines: &mut VectorLines, source: Vec2, lens: Vec2, einstein_radius: f32, occluder_radius: f32, color: Color) {
+    for image in lens_images(source, lens, einstein_radius) {
+        let offset = image.position - lens;
+        let distance = offset.length();
+        if image.magnification < LENS_MIN_MAGNIFICATION || distance <= occluder_radius {
+            continue;
+        }
+
+        let mut image_color = color;
+        image_color.set_a((color.a() * image.magnification.sqrt()).min(1.0));
+        let half_angle = (LENS_ARC_LENGTH * image.magnification / distance / 2.0).min(std::f32::consts::PI);
+        let angle = offset.y.atan2(offset.x);
+        let points = (0..=LENS_ARC_SEGMENTS).map(|i| {
+            let point_angle = angle - half_angle + 2.0 * half_angle * (i as f32) / (LENS_ARC_SEGMENTS as f32);
+            lens + Vec2::new(point_angle.cos(), point_angle.sin()) * distance
+        });
+        lines.path(VectorPath::polyline(points, image_color), LAYER_BACKGROUND);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < EPSILON, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn an_aligned_source_becomes_an_einstein_ring() {
+        let lens = Vec2::new(3.0, -4.0);
+        for image in lens_images(lens, lens, 10.0) {
+            assert_near(image.position.distance(lens), 10.0);
+            assert_eq!(image.magnification, LENS_MAX_MAGNIFICATION);
+        }
+    }
+
+    #[test]
+    fn a_distant_source_is_barely_lensed() {
+        let lens = Vec2::ZERO;
+        let source = Vec2::new(0.0, 1000.0);
+        let [outer, inner] = lens_images(source, lens, 10.0);
+        assert!(outer.position.distance(source) < 0.2);
+        assert_near(outer.magnification, 1.0);
+        assert!(inner.position.length() < 0.2 && inner.position.y < 0.0);
+        assert_near(inner.magnification, 0.0);
+    }
+
+    // both images sit where the lens equation puts them, with the point lens magnifications
+    #[test]
+    fn images_follow_the_point_lens_formulas() {
+        let einstein_radius = 10.0;
+        let source = Vec2::new(einstein_radius, 0.0);
+        let [outer, inner] = lens_images(source, Vec2::ZERO, einstein_radius);
+        for image in [outer, inner] {
+            let theta = image.position.x;
+            assert_near(theta - einstein_radius * einstein_radius / theta, source.x);
+        }
+        let base = 3.0 / (2.0 * 5f32.sqrt());
+        assert_near(outer.magnification, base + 0.5);
+        assert_near(inner.magnification, base - 0.5);
+        assert_near(outer.magnification - inner.magnification, 1.0);
+    }
+
+    #[test]
+    fn magnification_is_capped_near_alignment() {
+        for image in lens_images(Vec2::new(0.01, 0.0), Vec2::ZERO, 10.0) {
+            assert_eq!(image.magnification, LENS_MAX_MAGNIFICATION);
+        }
+    }
+
+    #[test]
+    fn no_mass_means_no_lens() {
+        assert_eq!(einstein_radius(0.0), 0.0);
+        assert_eq!(einstein_radius(-50.0), 0.0);
+        assert_near(einstein_radius(100.0), 10.0 * LENS_EINSTEIN_SCALE);
+        let source = Vec2::new(5.0, 5.0);
+        let [image, hidden] = lens_images(source, Vec2::ZERO, einstein_radius(-50.0));
+        assert_eq!((image.position, image.magnification), (source, 1.0));
+        assert_eq!(hidden.magnification, 0.0);
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use bevy::asset::FileAssetIo;
+use bevy::prelude::*;
+use num_format::{Locale, ToFormattedString};
+
+const LOCALE_DIR: &str = "assets/locale";    // relative to the same base path bevy loads assets from
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Language {
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+
+    pub fn number_locale(&self) -> Locale {
+        match self {
+            Language::English => Locale::en,
+            Language::German => Locale::de,
+        }
+    }
+
+    // accepts bare codes as well as posix locale names like de_DE.UTF-8
+    pub fn from_code(code: &str) -> Option<Language> {
+        let code = code.split(['_', '-', '.']).next()?.to_lowercase();
+        Language::ALL.iter().copied().find(|language| language.code() == code)
+    }
+
+    pub fn next(&self) -> Language {
+        let index = Language::ALL.iter().position(|language| language == self).unwrap_or(0);
+        Language::ALL[(index + 1) % Language::ALL.len()]
+    }
+}
+
+// the first supported language in the usual posix locale variables
+pub fn system_language() -> Option<Language> {
+    ["LC_ALL", "LC_MESSAGES", "LANG", "LANGUAGE"].iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .flat_map(|value| value.split(':').map(str::to_string).collect::<Vec<_>>())
+        .find_map(|code| Language::from_code(&code))
+}
+
+fn table_path(language: Language) -> PathBuf {
+    FileAssetIo::get_base_path().join(LOCALE_DIR).join(format!("{}.json", language.code()))
+}
+
+fn load_table(language: Language) -> Result<HashMap<String, String>, String> {
+    let path = table_path(language);
+    let contents = std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    serde_json::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// keys in the reference table that the other one lacks
+pub fn missing_keys(reference: &HashMap<String, String>, table: &HashMap<String, String>) -> Vec<String> {
+    let mut missing: Vec<String> = reference.keys().filter(|key| !table.contains_key(*key)).cloned().collect();
+    missing.sort();
+    missing
+}
+
+// the ui text for the chosen language, falling back to english and then to the key itself
+#[derive(Resource)]
+pub struct Strings {
+    pub language: Language,
+    table: HashMap<String, String>,
+    fallback: HashMap<String, String>
+}
+
+impl Strings {
+    pub fn load(language: Language) -> Self {
+        let fallback = load_table(Language::English).unwrap_or_else(|err| {
+            warn!("could not load strings: {}", err);
+            HashMap::new()
+        });
+        if language == Language::English {
+            return Self { language, table: HashMap::new(), fallback };
+        }
+
+        let table = load_table(language).unwrap_or_else(|err| {
+            warn!("could not load strings: {}", err);
+            HashMap::new()
+        });
+        for key in missing_keys(&fallback, &table) {
+            warn!("missing {} string: {}", language.code(), key);
+        }
+        Self { language, table, fallback }
+    }
+
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.table.get(key)
+            .or_else(|| self.fallback.get(key))
+            .map_or(key, String::as_str)
+    }
+
+    // fills in {0}, {1} and so on, so translations can reorder the arguments
+    pub fn format(&self, key: &str, args: &[&dyn Display]) -> String {
+        let mut text = self.get(key).to_string();
+        for (i, arg) in args.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", i), &arg.to_string());
+        }
+        text
+    }
+
+    pub fn number<T: ToFormattedString>(&self, number: T) -> String {
+        number.to_formatted_string(&self.language.number_locale())
+    }
+
+    pub fn on_off(&self, on: bool) -> &str {
+        self.get(if on { "option.on" } else { "option.off" })
+    }
+}
+
+// text that only needs looking up again when the language changes
+#[derive(Component)]
+pub struct LocalizedText(pub &'static str);
+
+pub fn refresh_localized_text(strings: Res<Strings>, mut query: Query<(&mut Text, &LocalizedText)>) {
+    if !strings.is_changed() {
+        return;
+    }
+    for (mut text, LocalizedText(key)) in &mut query {
+        text.sections[0].value = strings.get(key).to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every bundled table must have every key the english one does, and nothing it does not
+    #[test]
+    fn every_language_has_every_string() {
+        let reference = load_table(Language::English).unwrap();
+        for language in Language::ALL {
+            let table = load_table(language).unwrap();
+            assert_eq!(missing_keys(&reference, &table), Vec::<String>::new(), "missing from {}", language.code());
+            assert_eq!(missing_keys(&table, &reference), Vec::<String>::new(), "unknown in {}", language.code());
+        }
+    }
+}
@@ -1,12 +1,70 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod achievements;
+mod autopilot;
+mod blackhole;
+mod camera;
+mod console;
+mod daily;
+mod enemy;
+mod ghost;
+mod headless;
+mod hud;
+mod inspector;
+mod lensing;
+mod locale;
+mod music;
+mod net;
+mod particles;
+mod plasma;
+mod prefs;
+mod radar;
+mod replay;
+mod save;
+mod sfx;
+mod sim;
+mod stats;
+mod store;
+mod theme;
+mod tween;
+mod vector;
+
 use bevy::prelude::*;
+use bevy::ecs::system::SystemState;
 use bevy::utils::Duration;
-use bevy_prototype_debug_lines::*;
-use rand_pcg::Pcg32;
 use rand::{Rng, SeedableRng};
-use num_format::{Locale, ToFormattedString};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
 use bevy::window::PresentMode;
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::input::InputSystem;
+use achievements::*;
+use autopilot::*;
+use blackhole::*;
+use camera::*;
+use console::*;
+use daily::*;
+use enemy::*;
+use ghost::*;
+use headless::*;
+use hud::*;
+use inspector::*;
+use lensing::*;
+use locale::*;
+use music::*;
+use net::*;
+use particles::*;
+use plasma::*;
+use prefs::*;
+use radar::*;
+use replay::*;
+use save::*;
+use sfx::*;
+use sim::*;
+use stats::*;
+use theme::*;
+use tween::*;
+use vector::*;
 
 // drawing constants
 const SHIP_CORNERS: [Vec3; 3] = [
@@ -21,19 +79,7 @@ const ASTEROID_RADIUS_VARIANCE: f32 = 2.0;
 
 const GAME_NAME: &str = "SCHWARZSCHILD";
 
-const BACKGROUND_COLOR: Color = Color::rgb(0.0,0.0,0.0);
-const TITLE_COLOR: Color = Color::hsl(351.0, 0.68, 0.53);
-const SCORE_COLOR: Color = Color::hsl(351.0, 0.68, 0.53);
-const GAME_OVER_COLOR: Color = Color::hsl(351.0, 0.68, 0.53);
-const GAME_OVER_SCORE_COLOR: Color = Color::hsl(351.0, 0.68, 0.53);
-const SHIP_COLOR: Color = Color::hsl(171.0, 0.68, 0.53);
-const SHIP_TRAIL_COLOR: Color = Color::hsla(171.0, 0.68, 0.53, 0.5);
-const SHIP_HELP_COLOR: Color = Color::hsla(12.0, 0.78, 0.55, 0.1);
-const SHIP_TELEPORT_READY_COLOR: Color = Color::hsl(351.0, 0.68, 0.53);
-const BULLET_COLOR: Color = Color::rgb(1.0, 1.0, 1.0);
-const ASTEROID_COLOR: Color = Color::hsl(128.0, 0.39, 0.40);
-const STAR_COLOR: Color = Color::hsl(67.00, 0.76, 0.79);
-const BACKGROUND_STAR_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.15);
+const SHIP_TRAIL_ALPHA: f32 = 0.5;
 
 const BACKGROUND_STAR_COUNT: u32 = 65;
 const BACKGROUND_STAR_PARALLAX: f32 = 0.07;
@@ -66,6 +112,7 @@ const PLANET_RADIUS_CONSUME_SCALE: f32 = 0.3;
 const PLANET_MASS_CONSUME_SCALE: f32 = 5.0;
 const PLANET_MASS_COLLAPSE_TRIGGER: f32 = 2500.0;
 const PLANET_COLLAPSE_TIME_MS: f32 = 1500.0;
+const PLANET_GROWTH_TIME: f32 = 0.6;
 const PLANET_COLLAPSE_SIZE: f32 = 2.0;
 const PLANET_COLLAPSE_MASS: f32 = 30000.0; 
 
@@ -73,6 +120,7 @@ const ASTEROID_SPAWN_DISTANCE: f32 = 640.0;
 const ASTEROID_LIFETIME_MS: u64 = 60000;
 const ASTEROID_SPAWN_DELAY_MIN_MS: u64 = 2000;
 const ASTEROID_SPAWN_DELAY_MAX_MS: u64 = 4000;
+const ASTEROID_FIRST_SPAWN_MS: u64 = 5000;
 const ASTEROID_RADIUS_MIN: f32 = 10.0;
 const ASTEROID_RADIUS_MAX: f32 = 20.0;
 const ASTEROID_MASS_MIN: f32 = 10.0;
@@ -88,11 +136,7 @@ const ASTEROID_FRACTURE_MIN_RADIUS: f32 = 4.0;      // any asteroid smaller than
 const ASTEROID_FRACTURE_VEL_MIN: f32 = 10.0;        // min velocity to randomly apply to each fractured part
 const ASTEROID_FRACTURE_VEL_MAX: f32 = 30.0;        // max velocity to randomly apply to each fractured part
 
-const TRAIL_MAX_LIFE_MS: u64 = 3000;
-const TRAIL_START_ALPHA: f32 = 0.2;
-
-const EXPLOSION_MAX_LIFE_MS: u64 = 500;
-const EXPLOSION_MAX_RADIUS: f32 = 40.0;
+const TRAJECTORY_STEP: f32 = 1.0 / 20.0;
 
 const GRAVITY_VIS_RATE: f32 = 0.5;
 const GRAVITY_VIS_MASS_FACTOR: f32 = 1.0015;
@@ -104,45 +148,196 @@ const SCORE_ASTEROID_RADIUS_MIN: f32 = 4.0;
 const SCORE_ASTEROID_RADIUS_MAX: f32 = 20.0;
 const GAMEOVER_DELAY_MS: u64 = 3000;
 
-const TELEPORT_FX_TIME: f32 = 0.6;
-const TELEPORT_FX_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.1);
-const TELEPORT_FX_SIZE: f32 = 150.0;
+const MAX_PLAYERS: usize = 4;
+const PLAYER_START_DISTANCE: f32 = 300.0;
+const PLAYER_CONTROLS: [PlayerControls; MAX_PLAYERS] = [
+    PlayerControls { left: KeyCode::Left, right: KeyCode::Right, thrust: KeyCode::Up, fire: KeyCode::Space, teleport: KeyCode::X },
+    PlayerControls { left: KeyCode::A, right: KeyCode::D, thrust: KeyCode::W, fire: KeyCode::S, teleport: KeyCode::Q },
+    PlayerControls { left: KeyCode::J, right: KeyCode::L, thrust: KeyCode::I, fire: KeyCode::K, teleport: KeyCode::U },
+    PlayerControls { left: KeyCode::Numpad4, right: KeyCode::Numpad6, thrust: KeyCode::Numpad8, fire: KeyCode::Numpad5, teleport: KeyCode::Numpad0 },
+];
+const SCORE_PLAYER_KILL: u32 = 100;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const VERSION_SIZE: f32 = 20.0;
-const VERSION_COLOR: Color = Color::hsl(351.0, 0.68, 0.53);
+const TITLE_HINT_SIZE: f32 = 20.0;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 enum GameState {
     Title,
     Playing,
     GameOver,
+    Achievements,
+    Online,
+    Replay,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+enum GameMode {
+    #[default]
+    Coop,
+    Versus,
+}
+
+impl GameMode {
+    fn name(&self) -> &'static str {
+        match self {
+            GameMode::Coop => "mode.coop",
+            GameMode::Versus => "mode.versus",
+        }
+    }
+}
+
+struct PlayerControls {
+    left: KeyCode,
+    right: KeyCode,
+    thrust: KeyCode,
+    fire: KeyCode,
+    teleport: KeyCode
+}
+
+enum GameEvent {
+    AsteroidDestroyed,
+    Teleported { player: usize },
 }
 
-#[derive(Resource, Default)]
+#[derive(Resource, Clone, Serialize, Deserialize)]
 struct AsteroidTimer { duration: Duration }
 
-#[derive(Resource, Default)]
+impl Default for AsteroidTimer {
+    fn default() -> Self {
+        Self { duration: Duration::from_millis(ASTEROID_FIRST_SPAWN_MS) }
+    }
+}
+
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
 struct Game {
     score: u32,
+    player_scores: [u32; MAX_PLAYERS],
+    players: usize,
+    mode: GameMode,
+    winner: Option<usize>,
+    tick: u64,
     time: u64,
     gameover_time: u64,
     draw_trajectory: bool,
-    clear_trail: bool
+    black_hole_phase: bool,
+    score_multiplier: u32
 }
 
 impl Game {
     fn new() -> Self {
         Self {
             score: 0,
+            player_scores: [0; MAX_PLAYERS],
+            players: 1,
+            mode: GameMode::Coop,
+            winner: None,
+            tick: 0,
             time: 0,
             gameover_time: 0,
             draw_trajectory: false,
-            clear_trail: false
+            black_hole_phase: false,
+            score_multiplier: 1
+        }
+    }
+
+    fn is_versus(&self) -> bool {
+        self.players > 1 && self.mode == GameMode::Versus
+    }
+
+    fn add_score(&mut self, player: usize, score: u32) {
+        let score = score * self.score_multiplier;
+        self.score += score;
+        self.player_scores[player] += score;
+    }
+
+    // shared out as evenly as it goes so the players' scores still add up to the total
+    fn set_score(&mut self, score: u32) {
+        let players = self.players.max(1) as u32;
+        self.score = score;
+        self.player_scores = [0; MAX_PLAYERS];
+        for (player, player_score) in self.player_scores.iter_mut().take(players as usize).enumerate() {
+            *player_score = score / players + u32::from((player as u32) < score % players);
+        }
+    }
+}
+
+// every gameplay random number comes from here so a run can be repeated from its seed
+#[derive(Resource, Clone, Serialize, Deserialize)]
+struct GameRng {
+    seed: u64,
+    fixed: bool,          // keep the seed for the next run instead of rolling a new one
+    rng: Pcg32
+}
+
+impl GameRng {
+    fn new(seed: u64, fixed: bool) -> Self {
+        Self { seed, fixed, rng: Pcg32::seed_from_u64(seed) }
+    }
+
+    fn restart(&mut self) {
+        if !self.fixed {
+            self.seed = rand::thread_rng().gen();
+        }
+        self.rng = Pcg32::seed_from_u64(self.seed);
+    }
+}
+
+// the gameplay constants the developer console can change while the game runs
+#[derive(Resource, Clone, Serialize, Deserialize)]
+struct Tuning {
+    gravity: f32,
+    bullet_velocity: f32,
+    fire_delay_ms: u64,
+    asteroid_spawn_delay_min_ms: u64,
+    asteroid_spawn_delay_max_ms: u64
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            gravity: GRAVITY,
+            bullet_velocity: BULLET_VELOCITY,
+            fire_delay_ms: SHIP_FIRE_DELAY,
+            asteroid_spawn_delay_min_ms: ASTEROID_SPAWN_DELAY_MIN_MS,
+            asteroid_spawn_delay_max_ms: ASTEROID_SPAWN_DELAY_MAX_MS
         }
     }
 }
 
+impl Tuning {
+    const NAMES: [&'static str; 5] = ["GRAVITY", "BULLET_VELOCITY", "SHIP_FIRE_DELAY", "ASTEROID_SPAWN_DELAY_MIN_MS", "ASTEROID_SPAWN_DELAY_MAX_MS"];
+
+    fn get(&self, name: &str) -> Option<f32> {
+        match name {
+            "GRAVITY" => Some(self.gravity),
+            "BULLET_VELOCITY" => Some(self.bullet_velocity),
+            "SHIP_FIRE_DELAY" => Some(self.fire_delay_ms as f32),
+            "ASTEROID_SPAWN_DELAY_MIN_MS" => Some(self.asteroid_spawn_delay_min_ms as f32),
+            "ASTEROID_SPAWN_DELAY_MAX_MS" => Some(self.asteroid_spawn_delay_max_ms as f32),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "GRAVITY" => self.gravity = value,
+            "BULLET_VELOCITY" => self.bullet_velocity = value,
+            "SHIP_FIRE_DELAY" => self.fire_delay_ms = value.max(0.0) as u64,
+            "ASTEROID_SPAWN_DELAY_MIN_MS" => self.asteroid_spawn_delay_min_ms = value.max(1.0) as u64,
+            "ASTEROID_SPAWN_DELAY_MAX_MS" => self.asteroid_spawn_delay_max_ms = value.max(1.0) as u64,
+            _ => return false,
+        }
+        true
+    }
+
+    fn asteroid_spawn_delay(&self, rng: &mut Pcg32) -> Duration {
+        let min = self.asteroid_spawn_delay_min_ms;
+        Duration::from_millis(rng.gen_range(min..=self.asteroid_spawn_delay_max_ms.max(min)))
+    }
+}
+
 #[derive(Component, Deref, DerefMut)]
 struct Lifetime(Duration);
 
@@ -152,50 +347,61 @@ struct Radius(f32);
 #[derive(Component, Deref, DerefMut)]
 struct Mass(f32);
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 struct Planet {
     collapsing: bool,
-    collapse_init_size: f32,
     collapse_init_mass: f32,
     collapse_timer: f32,
-    growth_factor: f32,
-    growth_target: f32,
-    growth_start: f32
+    heat: u32
 }
 
 impl Planet {
     fn new() -> Self {
         Self {
             collapsing: false,
-            collapse_init_size: 0.0,
             collapse_init_mass: 0.0,
             collapse_timer: 0.0,
-            growth_factor: 0.0,
-            growth_target: 0.0,
-            growth_start: 0.0
+            heat: 0
         }
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 struct Ship {
+    player: usize,
     fire_delay: Duration,
     teleport_cooldown: f32
 }
 
 impl Ship {
-    fn new() -> Self {
+    fn new(player: usize) -> Self {
         Self {
+            player,
             fire_delay: Duration::from_millis(0),
             teleport_cooldown: SHIP_TELEPORT_COOLDOWN
         }
     }
 }
 
-#[derive(Component)]
-struct Bullet;
+#[derive(Component, Default)]
+struct ShipInput {
+    left: bool,
+    right: bool,
+    thrust: bool,
+    fire: bool,
+    teleport: bool
+}
 
-#[derive(Component)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+enum BulletOwner {
+    Player(usize),
+    Enemy,
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+struct Bullet { owner: BulletOwner }
+
+#[derive(Component, Clone, Serialize, Deserialize)]
 struct Asteroid { seed: u64 }
 
 #[derive(Component, Deref, DerefMut)]
@@ -204,19 +410,6 @@ struct Velocity(Vec2);
 #[derive(Component, Deref, DerefMut)]
 struct AngularVelocity(f32);
 
-#[derive(Component)]
-struct Trail { last_pos: Vec3 }
-
-#[derive(Component)]
-struct TrailLine {
-    start: Vec3,
-    end: Vec3,
-    alpha: f32
-}
-
-#[derive(Component)]
-struct Explosion;
-
 #[derive(Component)]
 struct GravityVis {
     radius: f32
@@ -226,31 +419,17 @@ struct GravityVis {
 struct ScoreText;
 
 #[derive(Component)]
-struct DebugPlanetData;
+struct TitleOptionsText;
 
 #[derive(Component)]
 struct Star;
 
-#[derive(Component)]
-struct TeleportFX {
-    enter: Vec3,
-    exit: Vec3,
-    factor: f32
-}
-
-fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle {
-        transform: Transform::from_xyz(0.0, 0.0, 5.0),
-        ..Default::default()
-    });
-}
-
-fn setup_title(mut commands: Commands, asset_server: Res<AssetServer>, windows: Res<Windows>) {
+fn setup_title(mut commands: Commands, asset_server: Res<AssetServer>, strings: Res<Strings>, theme: Res<Theme>) {
     let font = asset_server.load(FONT_PATH);
     let text_style = TextStyle {
         font,
         font_size: TITLE_SIZE,
-        color: TITLE_COLOR,
+        color: theme.title,
     };
     let text_alignment = TextAlignment::CENTER;
 
@@ -266,25 +445,114 @@ fn setup_title(mut commands: Commands, asset_server: Res<AssetServer>, windows:
     let text_style = TextStyle {
         font,
         font_size: VERSION_SIZE,
-        color: VERSION_COLOR,
+        color: theme.title,
     };
     let text_alignment = TextAlignment::BOTTOM_RIGHT;
 
-    let window_half_width = windows.get_primary().unwrap().width() / 2.0;
-    let window_half_height = windows.get_primary().unwrap().height() / 2.0;
+    let Vec2 { x: arena_half_width, y: arena_half_height } = SIM_ARENA_HALF_SIZE;
     commands.spawn(
         Text2dBundle {
             text: Text::from_section(VERSION, text_style.clone())
                 .with_alignment(text_alignment),
-            transform: Transform::from_xyz(window_half_width - 20.0, -window_half_height + 10.0, 0.0),
+            transform: Transform::from_xyz(arena_half_width - 20.0, -arena_half_height + 10.0, 0.0),
             ..default()
         }
     );
+
+    let font = asset_server.load(FONT_PATH);
+    let text_style = TextStyle {
+        font,
+        font_size: TITLE_HINT_SIZE,
+        color: theme.hint,
+    };
+    let text_alignment = TextAlignment::BOTTOM_LEFT;
+
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section(strings.get("title.achievements_hint"), text_style.clone())
+                .with_alignment(text_alignment),
+            transform: Transform::from_xyz(-arena_half_width + 20.0, -arena_half_height + 10.0, 0.0),
+            ..default()
+        },
+        LocalizedText("title.achievements_hint"))
+    );
+
+    if save_exists() {
+        commands.spawn(
+            (Text2dBundle {
+                text: Text::from_section(strings.get("title.continue_hint"), text_style.clone())
+                    .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_xyz(0.0, -140.0, 0.0),
+                ..default()
+            },
+            LocalizedText("title.continue_hint"))
+        );
+    }
+    if replay_exists() {
+        commands.spawn(
+            (Text2dBundle {
+                text: Text::from_section(strings.get("title.replay_hint"), text_style.clone())
+                    .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_xyz(0.0, -165.0, 0.0),
+                ..default()
+            },
+            LocalizedText("title.replay_hint"))
+        );
+    }
+
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("", text_style.clone())
+                .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(0.0, -100.0, 0.0),
+            ..default()
+        },
+        TitleOptionsText)
+    );
 }
 
-fn update_title(mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>) {
+#[allow(clippy::too_many_arguments)]
+fn update_title(mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>, mut game: ResMut<Game>, mut query: Query<&mut Text, With<TitleOptionsText>>, mut strings: ResMut<Strings>, mut preferences: ResMut<Preferences>, race: Res<GhostRace>, theme: Res<Theme>) {
+    let player_keys = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+    for (i, key) in player_keys.iter().enumerate() {
+        if keyboard_input.just_pressed(*key) {
+            game.players = i + 1;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::M) {
+        game.mode = match game.mode {
+            GameMode::Coop => GameMode::Versus,
+            GameMode::Versus => GameMode::Coop,
+        };
+    }
+    if keyboard_input.just_pressed(KeyCode::B) {
+        game.black_hole_phase = !game.black_hole_phase;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::L) {
+        *strings = Strings::load(strings.language.next());
+        preferences.language = Some(strings.language.code().to_string());
+        if let Err(err) = preferences.save(PREFERENCES_PATH) {
+            warn!("could not save preferences: {}", err);
+        }
+    }
+
+    let black_hole = strings.on_off(game.black_hole_phase);
+    let language = strings.get("language.name");
+    let theme_name = strings.get(&theme.key()).to_string();
+    let ghost = strings.on_off(race.enabled);
+    for mut text in &mut query {
+        text.sections[0].value = if game.players > 1 {
+            strings.format("title.options_multi", &[&game.players, &strings.get(game.mode.name()), &black_hole, &language, &theme_name, &ghost])
+        } else {
+            strings.format("title.options_single", &[&black_hole, &language, &theme_name, &ghost])
+        };
+    }
+
     if keyboard_input.just_pressed(KeyCode::Space) {
         state.set(GameState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::A) {
+        state.set(GameState::Achievements).unwrap();
     }
 }
 
@@ -294,87 +562,155 @@ fn teardown_title(mut commands: Commands, entities: Query<Entity, With<Text>>) {
     }
 }
 
-fn setup_playing(mut commands: Commands, mut game: ResMut<Game>, asset_server: Res<AssetServer>) {
-    let player_start = Vec3::new(0.0, 300.0, 0.0);
-    commands.spawn((Ship::new(),
-                    Radius(SHIP_RADIUS),
-                    Mass(SHIP_MASS),
-                    Transform::from_translation(player_start),
-                    Velocity(Vec2::new(0.0,0.0)),
-                    AngularVelocity(0.0),
-                    Trail { last_pos: player_start }));
+fn setup_playing(mut commands: Commands, mut run: RunState, resume: Option<Res<Resume>>, theme: Res<Theme>) {
+    // a continued run brings its own bodies, scores and random state with it
+    if resume.is_none() {
+        run.start(&mut commands, None, &theme);
+    }
+}
+
+// the ships and the star every run starts from
+fn spawn_run(commands: &mut Commands, players: usize, theme: &Theme) {
+    for player in 0..players {
+        let start_rotation = Quat::from_rotation_z(2.0 * std::f32::consts::PI * (player as f32) / (players as f32));
+        let player_start = start_rotation * Vec3::new(0.0, PLAYER_START_DISTANCE, 0.0);
+        commands.spawn((Ship::new(player),
+                        ShipInput::default(),
+                        Radius(SHIP_RADIUS),
+                        Mass(SHIP_MASS),
+                        Transform::from_translation(player_start).with_rotation(start_rotation),
+                        Velocity(Vec2::new(0.0,0.0)),
+                        AngularVelocity(0.0),
+                        ParticleEmitter::new(&SHIP_TRAIL, Some(ship_trail_color(player, theme)))));
+    }
     commands.spawn((Planet::new(),
                     Radius(PLANET_START_RADIUS),
                     Mass(PLANET_START_MASS),
                     Transform::from_xyz(0.0, 0.0, 0.0),
                     GravityVis { radius: 0.0 } ));
+}
 
+fn setup_score(mut commands: Commands, game: Res<Game>, asset_server: Res<AssetServer>, theme: Res<Theme>) {
     let font = asset_server.load(FONT_PATH);
     let text_style = TextStyle {
         font,
         font_size: SCORE_SIZE,
-        color: SCORE_COLOR,
+        color: theme.title,
     };
     let text_alignment = TextAlignment::CENTER;
 
+    let sections = (0..game.players).map(|player| {
+        let mut text_style = text_style.clone();
+        if game.players > 1 {
+            text_style.color = theme.players[player];
+        }
+        TextSection::new("", text_style)
+    });
+
     commands.spawn(
         (Text2dBundle {
-            text: Text::from_section("", text_style.clone())
+            text: Text::from_sections(sections)
                 .with_alignment(text_alignment),
             transform: Transform::from_xyz(0.0, 330.0, 0.0),
             ..default()
         },
         ScoreText)
     );
+}
 
-    game.gameover_time = 0;
-    game.score = 0;
-    game.time = 0;
+fn ship_trail_color(player: usize, theme: &Theme) -> Color {
+    let mut trail_color = theme.players[player];
+    trail_color.set_a(SHIP_TRAIL_ALPHA);
+    trail_color
 }
 
-fn check_player(mut state: ResMut<State<GameState>>, query: Query<&Ship>, planet_query: Query<&Planet>, mut game: ResMut<Game>, time: Res<Time>) {
-    let mut game_over = query.is_empty();
+fn run_over(game: &Game, query: &Query<&Ship>, planet_query: &Query<&Planet>) -> bool {
+    let game_over = if game.is_versus() {
+        query.iter().count() <= 1
+    } else {
+        query.is_empty()
+    };
 
-    for planet in &planet_query {
-        if planet.collapsing {
-            game_over = true;
-        }
+    // with the black hole phase on the collapse is survivable, the game only ends when the ships are gone
+    game_over || planet_query.iter().any(|planet| planet.collapsing && !game.black_hole_phase)
+}
+
+fn tick_ms(tick: u64) -> u64 {
+    tick * 1000 / SIM_TICK_RATE as u64
+}
+
+// the run's clocks count whole ticks, so a replayed or resimulated run reads the same time at the same point
+fn advance_clock(mut game: ResMut<Game>, query: Query<&Ship>, planet_query: Query<&Planet>) {
+    game.tick += 1;
+    let elapsed = tick_ms(game.tick) - tick_ms(game.tick - 1);
+    if run_over(&game, &query, &planet_query) {
+        game.gameover_time += elapsed;
+    } else {
+        game.time += elapsed;
+    }
+}
+
+fn check_player(mut state: ResMut<State<GameState>>, query: Query<&Ship>, mut game: ResMut<Game>) {
+    if game.gameover_time < GAMEOVER_DELAY_MS {
+        return;
+    }
+    if game.is_versus() {
+        game.winner = match query.get_single() {
+            Ok(ship) => Some(ship.player),
+            Err(_) => (0..game.players).max_by_key(|player| game.player_scores[*player]),
+        };
     }
+    state.set(GameState::GameOver).unwrap();
+}
 
-    if game_over {
-        game.gameover_time += time.delta().as_millis() as u64;
-        if game.gameover_time >= GAMEOVER_DELAY_MS {
-            state.set(GameState::GameOver).unwrap();
+fn update_score(mut query: Query<&mut Text, With<ScoreText>>, game: Res<Game>, strings: Res<Strings>) {
+    let mut score_text = query.single_mut();
+    if game.players > 1 {
+        for (player, section) in score_text.sections.iter_mut().enumerate() {
+            section.value = strings.format("score.player", &[&(player + 1), &strings.number(game.player_scores[player])]) + "   ";
         }
     } else {
-        game.time += time.delta().as_millis() as u64;
+        score_text.sections[0].value = strings.format("score.single", &[&strings.number(game.score)]);
     }
 }
 
-fn update_score(mut query: Query<&mut Text, With<ScoreText>>, game: Res<Game>) {
-    let mut score_text = query.single_mut();
-    score_text.sections[0].value = format!("Score: {}", game.score.to_formatted_string(&Locale::en));
+fn score_summary(game: &Game, strings: &Strings) -> String {
+    if game.players > 1 {
+        let scores: Vec<String> = (0..game.players)
+            .map(|player| strings.format("score.player", &[&(player + 1), &strings.number(game.player_scores[player])]))
+            .collect();
+        scores.join("   ")
+    } else {
+        strings.format("score.single", &[&strings.number(game.score)])
+    }
 }
 
-fn teardown_playing(mut commands: Commands, entities: Query<Entity, Or<(With<Ship>, With<Planet>, With<Bullet>, With<Asteroid>, With<Explosion>, With<TrailLine>, With<Text>)>>) {
+type TeardownQuery<'w, 's> = Query<'w, 's, Entity, Or<(With<Ship>, With<Planet>, With<Bullet>, With<Asteroid>, With<Enemy>, With<Plasma>, With<Particle>, With<Text>, With<HudRoot>, With<Ghost>)>>;
+
+fn teardown_playing(mut commands: Commands, entities: TeardownQuery) {
     for entity in &entities {
         commands.entity(entity).despawn_recursive();
     }
 }
 
-fn setup_gameover(mut commands: Commands, asset_server: Res<AssetServer>, game: Res<Game>) {
+fn setup_gameover(mut commands: Commands, asset_server: Res<AssetServer>, game: Res<Game>, stats: Res<Stats>, strings: Res<Strings>, theme: Res<Theme>) {
     let font = asset_server.load(FONT_PATH);
     let text_style = TextStyle {
         font,
         font_size: GAME_OVER_SIZE,
-        color: GAME_OVER_COLOR,
+        color: theme.title,
     };
     let text_alignment = TextAlignment::CENTER;
 
+    let title = match game.winner {
+        Some(player) => strings.format("gameover.winner", &[&(player + 1)]),
+        None => strings.get("gameover.title").to_string(),
+    };
     commands.spawn(
         Text2dBundle {
-            text: Text::from_section("GAME OVER", text_style.clone())
+            text: Text::from_section(title, text_style.clone())
                 .with_alignment(text_alignment),
+            transform: Transform::from_xyz(0.0, 180.0, 0.0),
             ..default()
         }
     );
@@ -383,22 +719,62 @@ fn setup_gameover(mut commands: Commands, asset_server: Res<AssetServer>, game:
     let text_style = TextStyle {
         font,
         font_size: GAME_OVER_SCORE_SIZE,
-        color: GAME_OVER_SCORE_COLOR,
+        color: theme.title,
     };
     commands.spawn(
         (Text2dBundle {
-            text: Text::from_section(format!("Score: {}", game.score.to_formatted_string(&Locale::en)), text_style.clone())
+            text: Text::from_section(score_summary(&game, &strings), text_style.clone())
                 .with_alignment(text_alignment),
-            transform: Transform::from_xyz(0.0, -40.0, 0.0),
+            transform: Transform::from_xyz(0.0, 140.0, 0.0),
             ..default()
         },
         ScoreText)
     );
+
+    let font = asset_server.load(FONT_PATH);
+    let text_style = TextStyle {
+        font,
+        font_size: STATS_SIZE,
+        color: theme.text,
+    };
+    commands.spawn(
+        Text2dBundle {
+            text: Text::from_section(stats.report(&strings), text_style.clone())
+                .with_alignment(text_alignment),
+            transform: Transform::from_xyz(0.0, -20.0, 0.0),
+            ..default()
+        }
+    );
+
+    let font = asset_server.load(FONT_PATH);
+    let text_style = TextStyle {
+        font,
+        font_size: STATS_EXPORT_SIZE,
+        color: theme.hint,
+    };
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section(strings.get("gameover.export_hint"), text_style.clone())
+                .with_alignment(text_alignment),
+            transform: Transform::from_xyz(0.0, -200.0, 0.0),
+            ..default()
+        },
+        StatsExportText)
+    );
+    commands.spawn(
+        Text2dBundle {
+            text: Text::from_section(strings.get("gameover.replay_hint"), text_style.clone())
+                .with_alignment(text_alignment),
+            transform: Transform::from_xyz(0.0, -225.0, 0.0),
+            ..default()
+        }
+    );
 }
 
-fn update_gameover(mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>) {
+// a finished online match can't carry on as a local run, so its peers go back to the title screen
+fn update_gameover(mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>, net: Option<Res<NetPlay>>) {
     if keyboard_input.just_pressed(KeyCode::Space) {
-        state.set(GameState::Playing).unwrap();
+        state.set(if net.is_some() { GameState::Title } else { GameState::Playing }).unwrap();
     }
 }
 
@@ -424,69 +800,89 @@ fn lifetime_control(mut commands: Commands, time: Res<Time>, mut query: Query<(E
     }
 }
 
-fn ship_control(mut commands: Commands, mut query: Query<(&mut Transform, &mut Velocity, &mut AngularVelocity, &mut Ship)>, keyboard_input: Res<Input<KeyCode>>, mut game: ResMut<Game>, time: Res<Time>) {
-    for (mut transform, mut velocity, mut ship_angular_velocity, mut ship) in &mut query {
-        let mut angular_velocity = **ship_angular_velocity;
-        let mut apply_drag = true;
-        if keyboard_input.pressed(KeyCode::Left) {
-           angular_velocity += SHIP_ROTATION_ACCEL * time.delta_seconds();
-           apply_drag = false;
-        }
-        if keyboard_input.pressed(KeyCode::Right) {
-           angular_velocity += -SHIP_ROTATION_ACCEL * time.delta_seconds();  
-           apply_drag = false;
-        }
-        angular_velocity = angular_velocity.clamp(-SHIP_ROTATION_SPEED, SHIP_ROTATION_SPEED);
-        transform.rotation = transform.rotation * Quat::from_rotation_z(angular_velocity * time.delta_seconds());
-        if apply_drag {
-            if angular_velocity > 0.0 {
-                angular_velocity -= SHIP_ROTATION_DECEL * time.delta_seconds();
-                angular_velocity = angular_velocity.max(0.0);
-            } else if angular_velocity < 0.0 {
-                angular_velocity += SHIP_ROTATION_DECEL * time.delta_seconds();
-                angular_velocity = angular_velocity.min(0.0);
-            }
-        }
-        **ship_angular_velocity = angular_velocity;
+fn player_input(mut query: Query<(&Ship, &mut ShipInput), Without<Autopilot>>, keyboard_input: Res<Input<KeyCode>>) {
+    for (ship, mut input) in &mut query {
+        let controls = &PLAYER_CONTROLS[ship.player];
+        input.left = keyboard_input.pressed(controls.left);
+        input.right = keyboard_input.pressed(controls.right);
+        input.thrust = keyboard_input.pressed(controls.thrust);
+        input.fire = keyboard_input.pressed(controls.fire);
+        // a press is held until a tick has used it, a frame can pass without one
+        input.teleport |= keyboard_input.just_pressed(controls.teleport);
+    }
+}
 
-        if keyboard_input.pressed(KeyCode::Up) {
-            let thrust = transform.rotation * Vec3{ x: 0.0, y: SHIP_MAX_THRUST, z: 0.0 } * time.delta_seconds();
-            velocity.x += thrust.x;
-            velocity.y += thrust.y;
-        }
+fn toggle_trajectory(keyboard_input: Res<Input<KeyCode>>, mut game: ResMut<Game>) {
+    if keyboard_input.just_pressed(KeyCode::H) {
+        game.draw_trajectory = !game.draw_trajectory;
+    }
+}
+
+fn ship_control(mut commands: Commands, mut query: Query<(&mut Transform, &mut Velocity, &mut AngularVelocity, &mut Ship, &mut ShipInput)>, mut stats: ResMut<Stats>, mut events: EventWriter<GameEvent>, time: Res<Time>) {
+    for (mut transform, mut velocity, mut ship_angular_velocity, mut ship, mut input) in &mut query {
+        apply_ship_input(&mut transform, &mut velocity, &mut ship_angular_velocity, &input, time.delta_seconds());
 
         if ship.teleport_cooldown > 0.0 {
             ship.teleport_cooldown -= time.delta_seconds();
             ship.teleport_cooldown = ship.teleport_cooldown.max(0.0);
         }
 
-        if ship.teleport_cooldown <= 0.0 && keyboard_input.just_pressed(KeyCode::X) {
+        if ship.teleport_cooldown <= 0.0 && input.teleport {
             let init_location = transform.translation;
             transform.translation = -transform.translation;
-            **velocity = **velocity * -1.0;
+            **velocity *= -1.0;
             ship.teleport_cooldown = SHIP_TELEPORT_COOLDOWN;
-            game.clear_trail = true;
-            commands.spawn(TeleportFX {
-                enter: init_location,
-                exit: transform.translation,
-                factor: 0.0
-            });
+            stats.teleports += 1;
+            events.send(GameEvent::Teleported { player: ship.player });
+            spawn_effect(&mut commands, &TELEPORT_ENTER, init_location, Vec2::ZERO);
+            spawn_effect(&mut commands, &TELEPORT_EXIT, transform.translation, Vec2::ZERO);
         }
+        input.teleport = false;
     }
+}
 
-    if keyboard_input.just_pressed(KeyCode::H) {
-        game.draw_trajectory = !game.draw_trajectory;
+fn apply_ship_input(transform: &mut Transform, velocity: &mut Velocity, ship_angular_velocity: &mut AngularVelocity, input: &ShipInput, delta_seconds: f32) {
+    let (angular_velocity, next_angular_velocity) = ship_rotation_step(**ship_angular_velocity, input.left, input.right, delta_seconds);
+    transform.rotation *= Quat::from_rotation_z(angular_velocity * delta_seconds);
+    **ship_angular_velocity = next_angular_velocity;
+
+    if input.thrust {
+        let thrust = transform.rotation * Vec3{ x: 0.0, y: SHIP_MAX_THRUST, z: 0.0 } * delta_seconds;
+        velocity.x += thrust.x;
+        velocity.y += thrust.y;
     }
 }
 
-fn apply_gravity(planet_query: Query<(&Transform, &Mass), With<Planet>>, mut entity_query: Query<(&Transform, &Mass, &mut Velocity)>, time: Res<Time>) {
+// returns the angular velocity to rotate by this step and the decayed angular velocity to carry into the next
+fn ship_rotation_step(mut angular_velocity: f32, left: bool, right: bool, delta_seconds: f32) -> (f32, f32) {
+    if left {
+        angular_velocity += SHIP_ROTATION_ACCEL * delta_seconds;
+    }
+    if right {
+        angular_velocity += -SHIP_ROTATION_ACCEL * delta_seconds;
+    }
+    angular_velocity = angular_velocity.clamp(-SHIP_ROTATION_SPEED, SHIP_ROTATION_SPEED);
+    let mut next_angular_velocity = angular_velocity;
+    if !left && !right {
+        if next_angular_velocity > 0.0 {
+            next_angular_velocity -= SHIP_ROTATION_DECEL * delta_seconds;
+            next_angular_velocity = next_angular_velocity.max(0.0);
+        } else if next_angular_velocity < 0.0 {
+            next_angular_velocity += SHIP_ROTATION_DECEL * delta_seconds;
+            next_angular_velocity = next_angular_velocity.min(0.0);
+        }
+    }
+    (angular_velocity, next_angular_velocity)
+}
+
+fn apply_gravity(planet_query: Query<(&Transform, &Mass), With<Planet>>, mut entity_query: Query<(&Transform, &Mass, &mut Velocity)>, tuning: Res<Tuning>, time: Res<Time>) {
     for (planet_transform, planet_mass) in &planet_query {
         let planet_mass = **planet_mass;
         for (entity_transform, entity_mass, mut entity_velocity) in &mut entity_query {
             let entity_mass = **entity_mass;
             let gravity_vector = (planet_transform.translation - entity_transform.translation).normalize();
             let distance = (planet_transform.translation - entity_transform.translation).length();
-            let gravity_force = GRAVITY * (planet_mass * entity_mass) / f32::max(1.0, distance * distance);
+            let gravity_force = tuning.gravity * (planet_mass * entity_mass) / f32::max(1.0, distance * distance);
             entity_velocity.x += gravity_vector.x * gravity_force * time.delta_seconds();
             entity_velocity.y += gravity_vector.y * gravity_force * time.delta_seconds();
         }
@@ -513,144 +909,145 @@ fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time: Res<Time>
     }
 }
 
-fn space_clamp(mut query: Query<&mut Transform, With<Ship>>, windows: Res<Windows>, mut game: ResMut<Game>) {
-    let window_half_width = windows.get_primary().unwrap().width() / 2.0;
-    let window_half_height = windows.get_primary().unwrap().height() / 2.0;
+type WrappingQuery<'w, 's> = Query<'w, 's, &'static mut Transform, Or<(With<Ship>, With<Enemy>)>>;
+
+fn space_clamp(mut query: WrappingQuery) {
+    let Vec2 { x: arena_half_width, y: arena_half_height } = SIM_ARENA_HALF_SIZE;
     for mut transform in &mut query {
-        if transform.translation.x < -window_half_width {
-            transform.translation.x = window_half_width + transform.translation.x % window_half_width;
-            game.clear_trail = true;
+        if transform.translation.x < -arena_half_width {
+            transform.translation.x = arena_half_width + transform.translation.x % arena_half_width;
         }
-        if transform.translation.x > window_half_width {
-            transform.translation.x = -window_half_width + transform.translation.x % window_half_width;
-            game.clear_trail = true;
+        if transform.translation.x > arena_half_width {
+            transform.translation.x = -arena_half_width + transform.translation.x % arena_half_width;
         }
-        if transform.translation.y < -window_half_height {
-            transform.translation.y = window_half_height + transform.translation.y % window_half_height;
-            game.clear_trail = true;
+        if transform.translation.y < -arena_half_height {
+            transform.translation.y = arena_half_height + transform.translation.y % arena_half_height;
         }
-        if transform.translation.y > window_half_height {
-            transform.translation.y = -window_half_height + transform.translation.y % window_half_height;
-            game.clear_trail = true;
+        if transform.translation.y > arena_half_height {
+            transform.translation.y = -arena_half_height + transform.translation.y % arena_half_height;
         }
     }
 }
 
-fn fire_control(mut query: Query<(&mut Ship, &Transform)>, mut commands: Commands, keyboard_input: Res<Input<KeyCode>>, time: Res<Time>) {
-    for (mut ship, transform) in &mut query {
+fn fire_control(mut query: Query<(&mut Ship, &ShipInput, &Transform)>, mut commands: Commands, mut stats: ResMut<Stats>, mut sounds: EventWriter<SoundEffect>, tuning: Res<Tuning>, time: Res<Time>) {
+    for (mut ship, input, transform) in &mut query {
         if !ship.fire_delay.is_zero() {
             if ship.fire_delay > time.delta() {
-                ship.fire_delay = ship.fire_delay - time.delta();
+                ship.fire_delay -= time.delta();
             } else {
                 ship.fire_delay = Duration::new(0, 0);
             }
         }
-        if ship.fire_delay.is_zero() && keyboard_input.pressed(KeyCode::Space) {
-            let bullet_velocity = transform.rotation * Vec3::new(0.0, BULLET_VELOCITY, 0.0);
+        if ship.fire_delay.is_zero() && input.fire {
+            let bullet_velocity = transform.rotation * Vec3::new(0.0, tuning.bullet_velocity, 0.0);
             let bullet_position = transform.translation + transform.rotation * SHIP_CORNERS[0];
-            commands.spawn((Bullet, Lifetime(Duration::from_millis(BULLET_LIFETIME_MS)), Radius(BULLET_RADIUS), Mass(BULLET_MASS), Transform::from_translation(bullet_position), Velocity ( Vec2::new(bullet_velocity.x, bullet_velocity.y) )));
-            ship.fire_delay = Duration::from_millis(SHIP_FIRE_DELAY);
+            commands.spawn((Bullet { owner: BulletOwner::Player(ship.player) }, Lifetime(Duration::from_millis(BULLET_LIFETIME_MS)), Radius(BULLET_RADIUS), Mass(BULLET_MASS), Transform::from_translation(bullet_position), Velocity ( Vec2::new(bullet_velocity.x, bullet_velocity.y) )));
+            ship.fire_delay = Duration::from_millis(tuning.fire_delay_ms);
+            stats.shots_fired += 1;
+            sounds.send(SoundEffect::Laser);
         }
     }
 }
 
-fn planet_colision(mut commands: Commands, mut planet_query: Query<(&Radius, &mut Mass, &Transform, &mut Planet)>, entity_query: Query<(Entity, &Radius, &Mass, &Transform, Option<&Bullet>), Without<Planet>>) {
-    for (planet_radius, mut planet_mass, planet_transform, mut planet) in &mut planet_query {
+// queries hand entities back in storage order, which differs between a run and a restored copy of it,
+// so collisions that could go more than one way are settled in position order instead
+fn in_position_order<T>(items: impl Iterator<Item = T>, transform: impl Fn(&T) -> &Transform) -> Vec<T> {
+    let mut items: Vec<T> = items.collect();
+    items.sort_by(|a, b| {
+        let (a, b) = (transform(a).translation, transform(b).translation);
+        a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y))
+    });
+    items
+}
+
+type PlanetCollisionQuery<'w, 's> = Query<'w, 's, (Entity, &'static Radius, &'static mut Mass, &'static Transform, &'static mut Planet, Option<&'static BlackHole>)>;
+
+// everything the star can swallow, ships in god mode aside
+type ConsumableQuery<'w, 's> = Query<'w, 's, (Entity, &'static Radius, &'static Mass, &'static Transform, Option<&'static Bullet>), (Without<Planet>, Without<Invulnerable>)>;
+
+fn planet_colision(mut commands: Commands, mut planet_query: PlanetCollisionQuery, entity_query: ConsumableQuery, mut sounds: EventWriter<SoundEffect>, mut rng: ResMut<GameRng>, tuning: Res<Tuning>) {
+    for (planet_entity, planet_radius, mut planet_mass, planet_transform, mut planet, black_hole) in &mut planet_query {
         let mut planet_radius_value = **planet_radius;
         let mut planet_mass_value = **planet_mass;
-        for (entity, entity_radius, entity_mass, entity_transform, optional_bullet) in &entity_query {
+        for (entity, entity_radius, entity_mass, entity_transform, optional_bullet) in in_position_order(entity_query.iter(), |item| item.3) {
             let entity_radius = **entity_radius;
             let entity_mass = **entity_mass;
             let distance = Vec3::distance(planet_transform.translation, entity_transform.translation);
             if distance < (planet_radius_value + entity_radius) {
                 commands.entity(entity).despawn();
-                if optional_bullet.is_none() {
+                if black_hole.is_some() {
+                    **planet_mass += entity_mass * BLACK_HOLE_CONSUME_SCALE;
+                } else if optional_bullet.is_none() {
                     planet_radius_value += entity_radius * PLANET_RADIUS_CONSUME_SCALE;
                     planet_mass_value += entity_mass * PLANET_MASS_CONSUME_SCALE;
+                } else if !planet.collapsing {
+                    // sustained fire heats the star until it sheds a blob of plasma back at the shooter
+                    planet.heat += 1;
+                    if planet.heat >= PLANET_EJECT_HITS && planet_mass_value - PLANET_EJECT_MASS >= PLANET_START_MASS {
+                        planet.heat = 0;
+                        planet_mass_value -= PLANET_EJECT_MASS;
+                        planet_radius_value -= PLANET_EJECT_RADIUS;
+                        let direction = (entity_transform.translation - planet_transform.translation).truncate();
+                        spawn_plasma(&mut commands, &mut rng.rng, tuning.gravity, planet_transform.translation, planet_radius_value, planet_mass_value, direction);
+                        sounds.send(SoundEffect::Eject);
+                    }
                 }
             }
         }
 
         if !planet.collapsing && **planet_radius != planet_radius_value {
-            planet.growth_start = **planet_radius;
-            planet.growth_factor = 0.0;
-            planet.growth_target = planet_radius_value;
             **planet_mass = planet_mass_value;
             if **planet_mass >= PLANET_MASS_COLLAPSE_TRIGGER {
                 planet.collapsing = true;
-                planet.collapse_init_size = **planet_radius;
                 planet.collapse_init_mass = **planet_mass;
                 planet.collapse_timer = 0.0;
-            }
-        }
-    }
-}
-
-fn bounce(mut x: f32) -> f32 {
-    const N1: f32 = 7.5625;
-    const D1: f32 = 2.75;
-
-    if x < (1.0 / D1) {
-        return N1 * x * x;
-    } else if x < (2.0 / D1) {
-        x -= 1.5 / D1;
-        return N1 * x * x + 0.75;
-    } else if x < (2.5 / D1) {
-        x -= 2.25 / D1;
-        return N1 * x * x + 0.9375;
-    } else {
-        x -= 2.625 / D1;
-        return N1 * x * x + 0.984375;
-    }
-}
-
-fn planet_growth(mut planets: Query<(&mut Planet, &mut Radius)>, time: Res<Time>) {
-    for (mut planet, mut radius) in &mut planets {
-        if !planet.collapsing && planet.growth_factor < 1.0 {
-            if planet.growth_target < **radius {
-                planet.growth_target = **radius;
-                planet.growth_factor = 1.0;
-                planet.growth_start = **radius;
+                commands.entity(planet_entity).insert(Tween::new(TweenTarget::Radius { from: **planet_radius, to: PLANET_COLLAPSE_SIZE }, Ease::PowIn(8.0), PLANET_COLLAPSE_TIME_MS / 1000.0));
+                sounds.send(SoundEffect::Collapse);
+            } else if planet_radius_value > **planet_radius {
+                commands.entity(planet_entity).insert(Tween::new(TweenTarget::Radius { from: **planet_radius, to: planet_radius_value }, Ease::BounceOut, PLANET_GROWTH_TIME));
+                sounds.send(SoundEffect::Thud);
             } else {
-                planet.growth_factor += 1.0 / 0.6 * time.delta_seconds();
-                planet.growth_factor = planet.growth_factor.min(1.0);
-                **radius = planet.growth_start + (planet.growth_target - planet.growth_start) * bounce(planet.growth_factor);
+                commands.entity(planet_entity).insert(Tween::new(TweenTarget::Radius { from: **planet_radius, to: planet_radius_value }, Ease::QuadOut, PLANET_GROWTH_TIME));
             }
         }
     }
 }
 
-fn planet_collapse(mut planets: Query<(&mut Planet, &mut Radius, &mut Mass)>, time: Res<Time>) {
-    for (mut planet, mut radius, mut mass) in &mut planets {
+fn planet_collapse(mut planets: Query<(&mut Planet, &mut Mass), Without<BlackHole>>, game: Res<Game>, time: Res<Time>) {
+    let collapse_mass = if game.black_hole_phase { BLACK_HOLE_START_MASS } else { PLANET_COLLAPSE_MASS };
+    for (mut planet, mut mass) in &mut planets {
         if planet.collapsing {
             planet.collapse_timer = f32::min(PLANET_COLLAPSE_TIME_MS, planet.collapse_timer + (time.delta().as_millis() as f32));
             let factor = planet.collapse_timer / PLANET_COLLAPSE_TIME_MS;
-            **radius = PLANET_COLLAPSE_SIZE + (1.0 - factor.powf(8.0)) * (planet.collapse_init_size - PLANET_COLLAPSE_SIZE);
-            **mass = planet.collapse_init_mass + factor * (PLANET_COLLAPSE_MASS - planet.collapse_init_mass);
+            **mass = planet.collapse_init_mass + factor * (collapse_mass - planet.collapse_init_mass);
         }
     }
 }
 
-fn asteroid_collision(mut commands: Commands, asteroid_query: Query<(Entity, &Radius, &Mass, &Transform, &Velocity), With<Asteroid>>, bullet_query: Query<(Entity, &Radius, &Transform), With<Bullet>>, mut game: ResMut<Game>) {
-    for (asteroid_entity, asteroid_radius, asteroid_mass, asteroid_transform, asteroid_velocity) in &asteroid_query {
+#[allow(clippy::too_many_arguments)]
+fn asteroid_collision(mut commands: Commands, asteroid_query: Query<(Entity, &Radius, &Mass, &Transform, &Velocity), With<Asteroid>>, bullet_query: Query<(Entity, &Radius, &Transform, &Bullet)>, mut game: ResMut<Game>, mut stats: ResMut<Stats>, mut events: EventWriter<GameEvent>, mut sounds: EventWriter<SoundEffect>, mut rng: ResMut<GameRng>) {
+    let bullets = in_position_order(bullet_query.iter(), |item| item.2);
+    for (asteroid_entity, asteroid_radius, asteroid_mass, asteroid_transform, asteroid_velocity) in in_position_order(asteroid_query.iter(), |item| item.3) {
         let asteroid_radius = **asteroid_radius;
         let asteroid_mass = **asteroid_mass;
-        for (bullet_entity, bullet_radius, bullet_transform) in &bullet_query {
+        for &(bullet_entity, bullet_radius, bullet_transform, bullet) in &bullets {
             let bullet_radius = **bullet_radius;
             let distance = Vec3::distance(asteroid_transform.translation, bullet_transform.translation);
             if distance < (asteroid_radius + bullet_radius) {
                 commands.entity(asteroid_entity).despawn();
                 commands.entity(bullet_entity).despawn();
-                commands.spawn((Explosion,
-                                Transform::from_translation(asteroid_transform.translation),
-                                Velocity(Vec2::new(asteroid_velocity.x, asteroid_velocity.y)),
-                                Lifetime(Duration::from_millis(EXPLOSION_MAX_LIFE_MS))));
-
-                let score_factor = (asteroid_radius - SCORE_ASTEROID_RADIUS_MIN) / (SCORE_ASTEROID_RADIUS_MAX - SCORE_ASTEROID_RADIUS_MIN);
-                let score = (1.0 - score_factor) * (SCORE_BOUNDS_MAX - SCORE_BOUNDS_MIN);
-                game.score += score as u32;
+                spawn_effect(&mut commands, &EXPLOSION, asteroid_transform.translation, **asteroid_velocity);
+                spawn_effect(&mut commands, &DEBRIS_SPARKS, asteroid_transform.translation, **asteroid_velocity);
+                sounds.send(SoundEffect::Explosion { radius: asteroid_radius });
+
+                if let BulletOwner::Player(player) = bullet.owner {
+                    game.add_score(player, asteroid_score(asteroid_radius));
+                    stats.hits += 1;
+                    stats.record_asteroid(asteroid_radius);
+                    events.send(GameEvent::AsteroidDestroyed);
+                }
                 if asteroid_radius > ASTEROID_FRACTURE_MIN_RADIUS {
-                    let mut rng = rand::thread_rng();
+                    let rng = &mut rng.rng;
                     let new_radius = asteroid_radius * ASTEROID_FRACTURE_RADIUS_FACTOR;
                     let new_mass = asteroid_mass * ASTEROID_FRACTURE_MASS_FACTOR;
                     let max_angle = 2.0 * std::f32::consts::PI;
@@ -677,17 +1074,50 @@ fn asteroid_collision(mut commands: Commands, asteroid_query: Query<(Entity, &Ra
     }
 }
 
-fn asteroid_spawner(mut commands: Commands, mut asteroid_timer: ResMut<AsteroidTimer>, time: Res<Time>) {
+fn asteroid_score(radius: f32) -> u32 {
+    let score_factor = (radius - SCORE_ASTEROID_RADIUS_MIN) / (SCORE_ASTEROID_RADIUS_MAX - SCORE_ASTEROID_RADIUS_MIN);
+    let score = (1.0 - score_factor) * (SCORE_BOUNDS_MAX - SCORE_BOUNDS_MIN);
+    score as u32
+}
+
+fn ship_collision(mut commands: Commands, ship_query: Query<(Entity, &Radius, &Transform, &Velocity, &Ship), Without<Invulnerable>>, bullet_query: Query<(Entity, &Radius, &Transform, &Bullet)>, mut game: ResMut<Game>, mut sounds: EventWriter<SoundEffect>) {
+    let bullets = in_position_order(bullet_query.iter(), |item| item.2);
+    for (ship_entity, ship_radius, ship_transform, ship_velocity, ship) in in_position_order(ship_query.iter(), |item| item.2) {
+        for &(bullet_entity, bullet_radius, bullet_transform, bullet) in &bullets {
+            // players only shoot each other in versus, enemies shoot everyone
+            let hostile = match bullet.owner {
+                BulletOwner::Player(player) => game.is_versus() && player != ship.player,
+                BulletOwner::Enemy => true,
+            };
+            if !hostile {
+                continue;
+            }
+            let distance = Vec3::distance(ship_transform.translation, bullet_transform.translation);
+            if distance < (**ship_radius + **bullet_radius) {
+                commands.entity(ship_entity).despawn();
+                commands.entity(bullet_entity).despawn();
+                spawn_effect(&mut commands, &EXPLOSION, ship_transform.translation, **ship_velocity);
+                sounds.send(SoundEffect::Explosion { radius: **ship_radius });
+                if let BulletOwner::Player(player) = bullet.owner {
+                    game.add_score(player, SCORE_PLAYER_KILL);
+                }
+                break;
+            }
+        }
+    }
+}
+
+fn asteroid_spawner(mut commands: Commands, mut asteroid_timer: ResMut<AsteroidTimer>, mut rng: ResMut<GameRng>, tuning: Res<Tuning>, time: Res<Time>) {
     if !asteroid_timer.duration.is_zero() {
         if asteroid_timer.duration > time.delta() {
-            asteroid_timer.duration = asteroid_timer.duration - time.delta();
+            asteroid_timer.duration -= time.delta();
         } else {
             asteroid_timer.duration = Duration::new(0, 0);
         }
     }
 
     if asteroid_timer.duration.is_zero() {
-        let mut rng = rand::thread_rng();
+        let rng = &mut rng.rng;
         let max_angle = 2.0 * std::f32::consts::PI;
         let spawn_angle: f32 = rng.gen_range(0.0..max_angle);
         let spawn_x = ASTEROID_SPAWN_DISTANCE * spawn_angle.cos();
@@ -706,7 +1136,7 @@ fn asteroid_spawner(mut commands: Commands, mut asteroid_timer: ResMut<AsteroidT
                         Velocity(Vec2::new(asteroid_velocity_x, asteroid_velocity_y)),
                         Lifetime(Duration::from_millis(ASTEROID_LIFETIME_MS))
                         ));
-        asteroid_timer.duration = Duration::from_millis(rng.gen_range(ASTEROID_SPAWN_DELAY_MIN_MS..ASTEROID_SPAWN_DELAY_MAX_MS));
+        asteroid_timer.duration = tuning.asteroid_spawn_delay(rng);
     }
 }
 
@@ -716,59 +1146,85 @@ fn asteroid_drag(planet_query: Query<(&Transform, &Radius), With<Planet>>, mut a
         for (asteroid_transform, asteroid_radius, mut asteroid_velocity) in &mut asteroid_query {
             let asteroid_radius = **asteroid_radius;
             let distance = Vec3::distance(planet_transform.translation, asteroid_transform.translation) - planet_radius;
-            let drag_factor = time.delta_seconds() * (ASTEROID_DRAG_CONSTANT + asteroid_radius * ASTEROID_DRAG_RADIUS_CONTRIBUTION) / distance;
-            let mut asteroid_speed = asteroid_velocity.length();
-            if asteroid_speed > drag_factor {
-                asteroid_speed -= drag_factor;
-            } else {
-                asteroid_speed = 0.0;
-            }
+            let asteroid_speed = asteroid_drag_speed(asteroid_velocity.length(), distance, asteroid_radius, time.delta_seconds());
             **asteroid_velocity = asteroid_velocity.normalize() * asteroid_speed;
         }
     }
 }
 
-fn gravity_velocity(pos1: Vec3, mass1: f32, pos2: Vec3, mass2: f32) -> Vec2 {
+fn asteroid_drag_speed(mut speed: f32, distance: f32, radius: f32, delta_seconds: f32) -> f32 {
+    let drag_factor = delta_seconds * (ASTEROID_DRAG_CONSTANT + radius * ASTEROID_DRAG_RADIUS_CONTRIBUTION) / distance;
+    if speed > drag_factor {
+        speed -= drag_factor;
+    } else {
+        speed = 0.0;
+    }
+    speed
+}
+
+fn gravity_velocity(gravity: f32, pos1: Vec3, mass1: f32, pos2: Vec3, mass2: f32) -> Vec2 {
     let delta = pos1 - pos2;
     let direction = delta.normalize();
     let distance = delta.length();
-    let force = GRAVITY * (mass1 * mass2) / f32::max(1.0, distance * distance);
-    return Vec2::new(direction.x * force, direction.y * force);
+    let force = gravity * (mass1 * mass2) / f32::max(1.0, distance * distance);
+    Vec2::new(direction.x * force, direction.y * force)
+}
+
+// steps a body forward under the planet's gravity, yielding its predicted position every TRAJECTORY_STEP seconds
+struct Trajectory {
+    gravity: f32,
+    position: Vec3,
+    velocity: Vec2,
+    mass: f32,
+    planet_position: Vec3,
+    planet_mass: f32
+}
+
+impl Trajectory {
+    fn new(gravity: f32, position: Vec3, velocity: Vec2, mass: f32, planet_position: Vec3, planet_mass: f32) -> Self {
+        Self { gravity, position, velocity, mass, planet_position, planet_mass }
+    }
+}
+
+impl Iterator for Trajectory {
+    type Item = Vec3;
+
+    fn next(&mut self) -> Option<Vec3> {
+        let added_velocity = gravity_velocity(self.gravity, self.planet_position, self.planet_mass, self.position, self.mass);
+        self.velocity += added_velocity * TRAJECTORY_STEP;
+        self.position.x += self.velocity.x * TRAJECTORY_STEP;
+        self.position.y += self.velocity.y * TRAJECTORY_STEP;
+        Some(self.position)
+    }
+}
+
+fn ship_shape(mut commands: Commands, query: Query<(Entity, &Ship), Added<Ship>>, theme: Res<Theme>) {
+    for (entity, ship) in &query {
+        let outline = VectorPath::polygon(SHIP_CORNERS.iter().map(|point| point.truncate()), theme.players[ship.player]);
+        commands.entity(entity).insert(VectorShape::new(outline, LAYER_SHAPES));
+    }
 }
 
-fn ship_render(query: Query<(&Transform, &Mass, &Velocity, &Ship)>, planet_query: Query<(&Transform, &Mass, &Planet)>, mut lines: ResMut<DebugLines>, game: Res<Game>) {
+fn ship_render(query: Query<(&Transform, &Mass, &Velocity, &Ship)>, planet_query: Query<(&Transform, &Mass, &Planet)>, mut lines: ResMut<VectorLines>, game: Res<Game>, tuning: Res<Tuning>, theme: Res<Theme>) {
     let (planet_transform, Mass(planet_mass), planet) = planet_query.single();
     for (transform, Mass(mass), Velocity(velocity), ship) in &query {
-        let points: Vec<Vec3> = SHIP_CORNERS.iter().map(|point| transform.transform_point(*point)).collect();
-        for i in 0..points.len() {
-            let point1 = points[i];
-            let point2 = points[(i + 1) % points.len()];
-            lines.line_colored(point1, point2, 0.0, SHIP_COLOR);
-        }
-
         if ship.teleport_cooldown <= 0.0 {
             let offset = transform.rotation * Vec3::new(0.0, -SHIP_RADIUS, 0.0 );
-            draw_circle(&mut lines, transform.translation + offset, 2.0, SHIP_TELEPORT_READY_COLOR, 5);
+            lines.path(VectorPath::circle((transform.translation + offset).truncate(), 2.0, 5, theme.teleport_ready), LAYER_SHAPES);
         }
 
         if game.draw_trajectory && !planet.collapsing {
-            let step = 1.0 / 20.0;
-            let mut pos = transform.translation;
-            let mut vel = *velocity;
+            let mut line_start = transform.translation;
             let mut start_vector = (planet_transform.translation - transform.translation).normalize();
             let mut cumulative_angle = 0.0;
             let orbit_radius = (planet_transform.translation - transform.translation).length();
             let orbit_circumference = 2.0 * std::f32::consts::PI * orbit_radius;
-            let mut distance = f32::max(9000.0, orbit_circumference);
-            while distance > 0.0 {
-                let line_start = pos;
-                let added_velocity = gravity_velocity(planet_transform.translation, *planet_mass, pos, *mass);
-                vel.x += added_velocity.x * step;
-                vel.y += added_velocity.y * step;
-                let travel = Vec2::new(vel.x * step, vel.y * step);
-                distance -= travel.length();
-                pos.x += travel.x;
-                pos.y += travel.y;
+            let max_distance = f32::max(9000.0, orbit_circumference);
+            let mut distance = max_distance;
+            let mut path = VectorPath::default();
+            path.push(line_start.truncate(), theme.ship_help);
+            for pos in Trajectory::new(tuning.gravity, transform.translation, *velocity, *mass, planet_transform.translation, *planet_mass) {
+                distance -= (pos - line_start).length();
                 let new_vector = (planet_transform.translation - pos).normalize();
                 let d = start_vector.dot(new_vector);
                 let a = d.acos();
@@ -777,91 +1233,41 @@ fn ship_render(query: Query<(&Transform, &Mass, &Velocity, &Ship)>, planet_query
                 if cumulative_angle > (2.0 * std::f32::consts::PI) {
                     break;
                 }
-                lines.line_colored(line_start, pos, 0.0, SHIP_HELP_COLOR);
+                // fade out towards the end of the prediction
+                let mut color = theme.ship_help;
+                color.set_a(theme.ship_help.a() * (distance / max_distance).max(0.0));
+                path.push(pos.truncate(), color);
+                line_start = pos;
+                if distance <= 0.0 {
+                    break;
+                }
             }
+            lines.path(path, LAYER_EFFECTS);
         }
     }
 }
 
-fn draw_circle(lines: &mut ResMut<DebugLines>, position: Vec3, radius: f32, color: Color, segments: u32) {
-    let mut prev_point = position + Vec3::new(radius, 0.0, 0.0);
-    for i in 1..segments {
-        let angle = 2.0 * std::f32::consts::PI * (i as f32) / (segments as f32);
-        let x = radius * angle.cos();
-        let y = radius * angle.sin();
-        let this_point = position + Vec3::new(x, y, 0.0);
-        lines.line_colored(prev_point, this_point, 0.0, color);
-        prev_point = this_point;
-    }
-    lines.line_colored(prev_point, position + Vec3::new(radius, 0.0, 0.0), 0.0, color);
-}
-
-fn draw_irregular_circle(lines: &mut ResMut<DebugLines>, seed: u64, position: Vec3, radius_min: f32, radius_max: f32, color: Color, segments: u32) {
-    let mut rng = Pcg32::seed_from_u64(seed);
-    let first_point = position + Vec3::new(rng.gen_range(radius_min..radius_max), 0.0, 0.0);
-    let mut prev_point = first_point;
-    for i in 1..segments {
-        let point_radius = rng.gen_range(radius_min..radius_max);
-        let angle = 2.0 * std::f32::consts::PI * (i as f32) / (segments as f32);
-        let x = point_radius * angle.cos();
-        let y = point_radius * angle.sin();
-        let this_point = position + Vec3::new(x, y, 0.0);
-        lines.line_colored(prev_point, this_point, 0.0, color);
-        prev_point = this_point;
-    }
-    lines.line_colored(prev_point, first_point, 0.0, color);
-}
-
-fn planet_render(query: Query<(&Radius, &Transform), With<Planet>>, mut lines: ResMut<DebugLines>) {
-    for (planet_radius, transform) in &query {
-        let position = transform.translation;
-        let radius = **planet_radius;
-        draw_circle(&mut lines, position, radius, STAR_COLOR, PLANET_POINT_COUNT);
-    }
-}
-
-fn bullet_render(query: Query<(&Radius, &Transform), With<Bullet>>, mut lines: ResMut<DebugLines>) {
-    for (radius, transform) in &query {
-        let position = transform.translation;
-        let radius = **radius;
-        draw_circle(&mut lines, position, radius, BULLET_COLOR, BULLET_POINT_COUNT);
-    }
-}
+type ResizedQuery<'w, 's, T> = Query<'w, 's, (Entity, &'static Radius), (With<T>, Changed<Radius>)>;
 
-fn asteroid_render(query: Query<(&Radius, &Transform, &Asteroid)>, mut lines: ResMut<DebugLines>) {
-    for (radius, transform, asteroid) in &query {
-        let position = transform.translation;
-        let radius = **radius;
-        draw_irregular_circle(&mut lines, asteroid.seed, position, radius - ASTEROID_RADIUS_VARIANCE, radius + ASTEROID_RADIUS_VARIANCE, ASTEROID_COLOR, ASTEROID_POINT_COUNT);
+fn planet_shape(mut commands: Commands, query: ResizedQuery<Planet>, black_hole_query: Query<(), With<BlackHole>>, theme: Res<Theme>) {
+    for (entity, Radius(radius)) in &query {
+        let color = if black_hole_query.contains(entity) { theme.black_hole_horizon } else { theme.star };
+        let outline = VectorPath::circle(Vec2::ZERO, *radius, PLANET_POINT_COUNT, color);
+        commands.entity(entity).insert(VectorShape::new(outline, LAYER_SHAPES));
     }
 }
 
-fn draw_trail(mut commands: Commands, mut query: Query<(&Transform, &mut Trail)>) {
-    for (transform, mut trail) in &mut query {
-        let line_distance = Vec3::distance(trail.last_pos, transform.translation);
-        if line_distance < 10.0 { // dont spawn lines if we make large jumps in distance (teleports)
-            commands.spawn((TrailLine{ start: transform.translation, end: trail.last_pos, alpha: TRAIL_START_ALPHA },
-                            Lifetime(Duration::from_millis(TRAIL_MAX_LIFE_MS))));
-        }
-        trail.last_pos = transform.translation;
+fn bullet_shape(mut commands: Commands, query: ResizedQuery<Bullet>, theme: Res<Theme>) {
+    for (entity, Radius(radius)) in &query {
+        let outline = VectorPath::circle(Vec2::ZERO, *radius, BULLET_POINT_COUNT, theme.bullet);
+        commands.entity(entity).insert(VectorShape::new(outline, LAYER_SHAPES));
     }
 }
 
-fn draw_trail_lines(query: Query<(Entity, &TrailLine, &Lifetime)>, mut lines: ResMut<DebugLines>) {
-    for (_entity, line, lifetime) in &query {
-        let alpha = line.alpha * (lifetime.as_millis() as f32 / TRAIL_MAX_LIFE_MS as f32);
-        let color = Color::rgba(SHIP_TRAIL_COLOR.r(), SHIP_TRAIL_COLOR.g(), SHIP_TRAIL_COLOR.b(), SHIP_TRAIL_COLOR.a() * alpha);
-        lines.line_colored(line.start, line.end, 0.0, color);
-    }
-}
-
-fn draw_explosion(query: Query<(&Transform, &Lifetime), With<Explosion>>, mut lines: ResMut<DebugLines>) {
-    for (transform, lifetime) in &query {
-        let age = (EXPLOSION_MAX_LIFE_MS - lifetime.as_millis() as u64) as f32;
-        let factor = 1.0 - (1.0 - age / EXPLOSION_MAX_LIFE_MS as f32).powf(2.0);
-        let radius = factor * EXPLOSION_MAX_RADIUS;
-        let alpha = 0.5 * lifetime.as_millis() as f32 / EXPLOSION_MAX_LIFE_MS as f32;
-        draw_circle(&mut lines, transform.translation, radius, Color::rgba(1.0, 1.0, 1.0, alpha), 20);
+fn asteroid_shape(mut commands: Commands, query: Query<(Entity, &Radius, &Asteroid), Changed<Radius>>, theme: Res<Theme>) {
+    for (entity, Radius(radius), asteroid) in &query {
+        let points = irregular_circle_points(asteroid.seed, radius - ASTEROID_RADIUS_VARIANCE, radius + ASTEROID_RADIUS_VARIANCE, ASTEROID_POINT_COUNT);
+        commands.entity(entity).insert(VectorShape::new(VectorPath::polygon(points, theme.asteroid), LAYER_SHAPES));
     }
 }
 
@@ -877,108 +1283,374 @@ fn update_gravity_vis(mut query: Query<(&Mass, &mut GravityVis)>, time: Res<Time
     }
 }
 
-fn visualise_gravity(query: Query<(&Planet, &Transform, &Radius, &GravityVis)>, mut lines: ResMut<DebugLines>) {
+fn visualise_gravity(query: Query<(&Planet, &Transform, &Radius, &GravityVis)>, mut lines: ResMut<VectorLines>, theme: Res<Theme>) {
     for (planet, transform, Radius(radius), gravity_vis) in &query {
         if !planet.collapsing {
             let radius = radius + GRAVITY_VIS_SIZE * gravity_vis.radius;
-            draw_circle(&mut lines, transform.translation, radius, Color::rgba(1.0, 1.0, 1.0, 0.05), 40);
+            lines.circle(transform.translation, radius, theme.gravity_ring, 40);
         }
     }
 }
 
-fn setup_stars(mut commands: Commands, windows: Res<Windows>) {
+fn setup_stars(mut commands: Commands) {
     let star_count = BACKGROUND_STAR_COUNT;
-    let window_half_width = windows.get_primary().unwrap().width() / 2.0;
-    let window_half_height = windows.get_primary().unwrap().height() / 2.0;
+    let Vec2 { x: arena_half_width, y: arena_half_height } = SIM_ARENA_HALF_SIZE;
     let mut rng = rand::thread_rng();
     for _i in 0..star_count {
-        let x = rng.gen_range(-window_half_width..window_half_width);
-        let y = rng.gen_range(-window_half_height..window_half_height);
+        let x = rng.gen_range(-arena_half_width..arena_half_width);
+        let y = rng.gen_range(-arena_half_height..arena_half_height);
         commands.spawn((Star, Transform::from_xyz(x, y, 0.0)));
     }
 }
 
-fn draw_stars(player_query: Query<&Transform, With<Ship>>, query: Query<&Transform, With<Star>>, mut lines: ResMut<DebugLines>) {
+fn draw_stars(player_query: Query<&Transform, With<Ship>>, planet_query: Query<(&Transform, &Mass, &Radius), With<Planet>>, query: Query<&Transform, With<Star>>, camera_query: Query<&CameraRig>, mut lines: ResMut<VectorLines>, theme: Res<Theme>) {
     let mut star_offset = Vec3::new(0.0, 0.0, 0.0);
     if !player_query.is_empty() {
-        let player_transform = player_query.single();
-        star_offset = player_transform.translation * BACKGROUND_STAR_PARALLAX;
+        let player_centre = player_query.iter().map(|transform| transform.translation).sum::<Vec3>() / player_query.iter().count() as f32;
+        star_offset = player_centre * BACKGROUND_STAR_PARALLAX;
     }
     
+    // the starfield is seen through the planet's gravity
+    let lens = planet_query.get_single().ok();
+    // the stars are far enough away to stay put on screen however the camera moves and zooms
+    let (camera_position, camera_zoom) = camera_query.get_single().map_or((Vec3::ZERO, 1.0), |rig| (rig.position.extend(0.0), rig.zoom));
     for transform in &query {
-        let star_location = transform.translation - star_offset;
-        draw_circle(&mut lines, star_location, 1.0, BACKGROUND_STAR_COLOR, 2);
+        let star_location = camera_position + (transform.translation - star_offset) * camera_zoom;
+        if let Some((planet_transform, Mass(planet_mass), Radius(planet_radius))) = lens {
+            draw_lensed_star(&mut lines, star_location.truncate(), planet_transform.translation.truncate(), einstein_radius(*planet_mass), *planet_radius, theme.background_star);
+        } else {
+            lines.path(VectorPath::circle(star_location.truncate(), 1.0, 2, theme.background_star), LAYER_BACKGROUND);
+        }
     }
 }
 
-fn render_teleport_fx(mut commands: Commands, mut query: Query<(Entity, &mut TeleportFX)>, mut lines: ResMut<DebugLines>, time: Res<Time>) {
-    for (entity, mut teleport_fx) in &mut query {
-        teleport_fx.factor += (1.0 / TELEPORT_FX_TIME) * time.delta_seconds();
-        teleport_fx.factor = teleport_fx.factor.min(1.0);
-        let factor = f32::clamp(1.0 - (1.0 - teleport_fx.factor) * (1.0 - teleport_fx.factor), 0.0, 1.0);
-        let enter_color = Color::rgba(TELEPORT_FX_COLOR.r(), TELEPORT_FX_COLOR.g(), TELEPORT_FX_COLOR.b(), TELEPORT_FX_COLOR.a() * (1.0 - factor));
-        let exit_color = Color::rgba(TELEPORT_FX_COLOR.r(), TELEPORT_FX_COLOR.g(), TELEPORT_FX_COLOR.b(), TELEPORT_FX_COLOR.a() * factor);
-        draw_circle(&mut lines, teleport_fx.enter, TELEPORT_FX_SIZE * factor, enter_color, 20);
-        draw_circle(&mut lines, teleport_fx.exit, TELEPORT_FX_SIZE * (1.0 - factor), exit_color, 20);
+// the game's rules, chained into one fixed order so the same inputs always play out the same way
+fn rule_systems() -> SystemSet {
+    SystemSet::new()
+        .with_system(advance_clock)
+        .with_system(autopilot_input.after(advance_clock))
+        .with_system(enemy_ai.after(autopilot_input))
+        .with_system(ship_control.after(enemy_ai))
+        .with_system(fire_control.after(ship_control))
+        .with_system(enemy_control.after(fire_control))
+        .with_system(apply_gravity.after(enemy_control))
+        .with_system(asteroid_drag.after(apply_gravity))
+        .with_system(apply_velocity.after(asteroid_drag))
+        .with_system(space_clamp.after(apply_velocity))
+        .with_system(update_tweens.after(space_clamp))
+        .with_system(update_black_hole.after(update_tweens))
+        .with_system(planet_colision.after(update_black_hole))
+        .with_system(planet_collapse.after(planet_colision))
+        .with_system(form_black_hole.after(planet_collapse))
+        .with_system(cool_planet.after(form_black_hole))
+        .with_system(asteroid_collision.after(cool_planet))
+        .with_system(ship_collision.after(asteroid_collision))
+        .with_system(enemy_collision.after(ship_collision))
+        .with_system(plasma_collision.after(enemy_collision))
+        .with_system(lifetime_control.after(plasma_collision))
+        .with_system(asteroid_spawner.after(lifetime_control))
+        .with_system(enemy_spawner.after(asteroid_spawner))
+}
+
+// steps the rules by however many ticks this frame was worth, for the attract mode demo
+fn run_simulation(world: &mut World) {
+    let delta = world.resource::<Time>().delta();
+    let ticks = world.resource_mut::<Simulation>().due(delta);
+    run_ticks(world, ticks);
+}
+
+type PlayerInputState = SystemState<(Query<'static, 'static, (&'static Ship, &'static mut ShipInput), Without<Autopilot>>, Res<'static, Input<KeyCode>>)>;
 
-        if teleport_fx.factor >= 1.0 {
-            commands.entity(entity).despawn_recursive();
+// the keyboard is read once a frame, every tick the frame was worth is recorded before it runs
+fn run_playing(world: &mut World, input_state: &mut PlayerInputState) {
+    let (query, keyboard_input) = input_state.get_mut(world);
+    player_input(query, keyboard_input);
+
+    let delta = world.resource::<Time>().delta();
+    world.resource_scope(|world, mut simulation: Mut<Simulation>| {
+        for _tick in 0..simulation.due(delta) {
+            record_tick(world);
+            simulation.tick(world);
         }
-    }
+    });
+}
+
+// the per frame side shared by real games, replays and the attract mode demo
+fn render_systems(set: SystemSet) -> SystemSet {
+    set
+        .with_system(toggle_trajectory)
+        .with_system(update_gravity_vis)
+        .with_system(emit_particles)
+        .with_system(update_particles)
+
+        .with_system(draw_stars)
+        .with_system(ship_shape)
+        .with_system(ship_render)
+        .with_system(planet_shape)
+        .with_system(bullet_shape)
+        .with_system(asteroid_shape)
+        .with_system(enemy_shape)
+        .with_system(plasma_shape)
+        .with_system(draw_particles)
+        .with_system(visualise_gravity)
+        .with_system(black_hole_render)
 }
 
 fn main() {
-    App::new()
-    .add_plugins(DefaultPlugins.set(WindowPlugin {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--net-loopback") {
+        loopback_main(&args[index + 1..]);
+        return;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--headless") {
+        headless_main(&args[index + 1..]);
+        return;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--render-music") {
+        render_music_main(&args[index + 1..]);
+        return;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--import-daily") {
+        std::process::exit(if import_daily_main(&args[index + 1..]) { 0 } else { 1 });
+    }
+
+    let audio_backend = if args.iter().any(|arg| arg == "--no-audio") { AudioBackend::Null } else { AudioBackend::Device };
+
+    // a language given on the command line wins over the saved one, which wins over the system's
+    let preferences = Preferences::load(PREFERENCES_PATH);
+    let language = args.iter().position(|arg| arg == "--lang")
+        .and_then(|index| args.get(index + 1))
+        .or(preferences.language.as_ref())
+        .and_then(|code| Language::from_code(code))
+        .or_else(system_language)
+        .unwrap_or(Language::English);
+    // rusteroids --exec <file> runs console commands at startup, for repeatable test scenarios
+    let mut console = Console::default();
+    if let Some(path) = args.iter().position(|arg| arg == "--exec").and_then(|index| args.get(index + 1)) {
+        if let Err(err) = console.queue_script(path) {
+            eprintln!("could not read {}: {}", path, err);
+        }
+    }
+    // rusteroids --watch <file> opens straight into the replay viewer, for replays other than the latest run's
+    let playback = args.iter().position(|arg| arg == "--watch").map(|index| {
+        let Some(path) = args.get(index + 1) else {
+            eprintln!("usage: --watch <file>");
+            std::process::exit(1);
+        };
+        Playback::load(path).unwrap_or_else(|err| {
+            eprintln!("could not load {}: {}", path, err);
+            std::process::exit(1);
+        })
+    });
+    let first_state = if playback.is_some() { GameState::Replay } else { GameState::Title };
+    let theme = preferences.theme.as_deref().and_then(Theme::from_name).unwrap_or(&CLASSIC).clone();
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
             window: WindowDescriptor {
                 title: GAME_NAME.to_string(),
                 present_mode: PresentMode::AutoVsync,
                 ..default()
             },
             ..default()
-        }))
-    .add_plugin(DebugLinesPlugin::default())
-    .insert_resource(ClearColor(BACKGROUND_COLOR))
+        }).disable::<bevy::audio::AudioPlugin>())
+    .add_plugin(VectorPlugin)
+    .add_plugin(CameraPlugin)
+    .add_plugin(FrameTimeDiagnosticsPlugin)
+    .add_plugin(SfxPlugin { backend: audio_backend })
+    .add_plugin(MusicPlugin { backend: audio_backend })
+    .insert_resource(ClearColor(theme.background))
+    .insert_resource(theme)
+    .insert_resource(Strings::load(language))
     .insert_resource(Game::new())
-    .insert_resource(AsteroidTimer{ duration: Duration::from_secs(5) })
-    .add_state(GameState::Title)
-    .add_startup_system(setup_camera)
-    .add_system_set(SystemSet::on_enter(GameState::Title).with_system(setup_title))
-    .add_system_set(SystemSet::on_update(GameState::Title).with_system(update_title))
-    .add_system_set(SystemSet::on_exit(GameState::Title).with_system(teardown_title))
-    .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(setup_playing).with_system(setup_stars))
-    .add_system_set(SystemSet::on_update(GameState::Playing)
-        .with_system(ship_control)
-        .with_system(apply_gravity)
-        .with_system(apply_velocity)
-        .with_system(asteroid_drag)
-        .with_system(planet_colision)
-        .with_system(asteroid_collision)
-        .with_system(fire_control)
-        .with_system(lifetime_control)
-        .with_system(space_clamp)
-        .with_system(asteroid_spawner)
-        .with_system(planet_growth)
-        .with_system(planet_collapse)
-        .with_system(update_gravity_vis)
+    .insert_resource(GameRng::new(rand::thread_rng().gen(), false))
+    .init_resource::<Tuning>()
+    .insert_resource(console)
+    .insert_resource(Stats::new())
+    .insert_resource(Achievements::load(ACHIEVEMENTS_PATH))
+    .insert_resource(AchievementTracker::default())
+    .insert_resource(GhostRace::new(preferences.ghost))
+    .insert_resource(HighScores::load(HIGH_SCORES_PATH))
+    .insert_resource(DailyBoard::load(DAILY_PATH))
+    .init_resource::<Daily>()
+    .insert_resource(preferences)
+    .init_resource::<Radar>()
+    .init_resource::<Inspector>()
+    .add_event::<GameEvent>()
+    .add_event::<AchievementUnlocked>()
+    .add_event::<TweenCompleted>()
+    .add_event::<OptionChanged>()
+    .init_resource::<AsteroidTimer>()
+    .insert_resource(EnemyTimer::default())
+    .init_resource::<Simulation>()
+    .add_state(first_state)
+    .add_system_set(SystemSet::on_enter(GameState::Title).with_system(setup_title).with_system(setup_online_hint).with_system(setup_attract).with_system(setup_stars).with_system(reset_music).with_system(end_daily).with_system(setup_daily_hint))
+    .add_system_set(render_systems(SystemSet::on_update(GameState::Title))
+        .with_system(run_simulation)
+        .with_system(update_title)
+        .with_system(continue_game)
+        .with_system(start_daily)
+        .with_system(update_daily_hint)
+        .with_system(watch_replay)
+        .with_system(toggle_ghost)
+        .with_system(switch_theme)
+        .with_system(start_online)
+        .with_system(restart_attract)
+    )
+    .add_system_set(SystemSet::on_exit(GameState::Title).with_system(teardown_title).with_system(teardown_attract))
+    .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(restore_game.before(setup_playing).before(setup_score).before(setup_hud)).with_system(setup_playing).with_system(setup_score).with_system(start_recording).with_system(pick_ghost_seed.after(setup_playing)).with_system(apply_daily.after(pick_ghost_seed)).with_system(start_ghost.after(apply_daily).after(restore_game)).with_system(setup_stars).with_system(reset_achievement_tracker).with_system(reset_music).with_system(setup_hud))
+    .add_system_set(render_systems(SystemSet::on_update(GameState::Playing))
+        .with_system(run_playing)
+        .with_system(record_ghost.after(check_player))
+        .with_system(move_ghosts.after(check_player))
+        .with_system(update_ghost_delta)
         .with_system(update_score)
         .with_system(check_player)
-
-        .with_system(draw_stars)
-        .with_system(ship_render)
-        .with_system(planet_render)
-        .with_system(bullet_render)
-        .with_system(asteroid_render)
-        .with_system(draw_trail)
-        .with_system(draw_trail_lines)
-        .with_system(draw_explosion)
-        .with_system(visualise_gravity)
-        .with_system(render_teleport_fx)
+        .with_system(save_on_quit)
+        .with_system(track_stats)
+        .with_system(track_orbit)
+        .with_system(check_achievements)
+        .with_system(spawn_toasts)
+        .with_system(despawn_toasts)
+        .with_system(play_sounds)
+        .with_system(thrust_sound)
+        .with_system(update_music)
+        .with_system(draw_indicators)
+        .with_system(draw_radar)
+        .with_system(update_hud_status)
+        .with_system(update_mass_gauge)
+        .with_system(update_ship_panels)
+    )
+    .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(teardown_playing).with_system(silence_thrust).with_system(finish_recording).with_system(finish_ghost).with_system(record_daily))
+    .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(setup_gameover).with_system(setup_daily_results))
+    .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(update_gameover).with_system(export_stats).with_system(watch_replay).with_system(export_daily))
+    .add_system_set(SystemSet::on_exit(GameState::GameOver).with_system(teardown_gameover).with_system(teardown_online))
+    .add_system_set(SystemSet::on_enter(GameState::Achievements).with_system(setup_achievements_view))
+    .add_system_set(SystemSet::on_update(GameState::Achievements).with_system(update_achievements_view))
+    .add_system_set(SystemSet::on_exit(GameState::Achievements).with_system(teardown_achievements_view))
+    .add_system_set(SystemSet::on_enter(GameState::Online).with_system(setup_online).with_system(setup_stars))
+    .add_system_set(render_systems(SystemSet::on_update(GameState::Online))
+        .with_system(update_online)
+        .with_system(check_player)
+        .with_system(update_net_status)
+        .with_system(update_score)
+        .with_system(draw_indicators)
+        .with_system(draw_radar)
+    )
+    .add_system_set(SystemSet::on_exit(GameState::Online).with_system(teardown_playing))
+    .add_system_set(SystemSet::on_enter(GameState::Replay).with_system(start_replay).with_system(setup_stars).with_system(setup_score.after(start_replay)).with_system(setup_hud.after(start_replay)))
+    .add_system_set(render_systems(SystemSet::on_update(GameState::Replay))
+        .with_system(run_replay)
+        .with_system(replay_controls)
+        .with_system(free_camera)
+        .with_system(update_replay_panel)
+        .with_system(update_score)
+        .with_system(draw_indicators)
+        .with_system(draw_radar)
+        .with_system(update_hud_status)
+        .with_system(update_mass_gauge)
+        .with_system(update_ship_panels)
     )
-    .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(teardown_playing))
-    .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(setup_gameover))
-    .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(update_gameover))
-    .add_system_set(SystemSet::on_exit(GameState::GameOver).with_system(teardown_gameover))
-    .add_system(bevy::window::close_on_esc)
-    .run();
+    .add_system_set(SystemSet::on_exit(GameState::Replay).with_system(teardown_playing).with_system(stop_replay))
+    .add_system(toggle_radar)
+    .add_system(show_option_text)
+    .add_system(expire_option_text)
+    .add_system(toggle_inspector)
+    .add_system(select_entity)
+    .add_system(draw_inspector)
+    .add_system(update_inspector_labels)
+    .add_system(update_inspector_panel)
+    .add_system_to_stage(CoreStage::PreUpdate, console_input.after(InputSystem))
+    .add_system(run_console_commands)
+    .add_system(apply_god_mode)
+    .add_system(update_console_view)
+    .add_system(refresh_localized_text)
+    .add_system(bevy::window::close_on_esc);
+
+    if let Some(config) = NetConfig::from_args(&args) {
+        app.insert_resource(config);
+    }
+    if let Some(playback) = playback {
+        app.insert_resource(playback);
+    }
+    app.run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{run_ticks, simulation_world};
+
+    fn ships(world: &mut World) -> Vec<(usize, Vec3)> {
+        let mut ships: Vec<(usize, Vec3)> = world.query::<(&Ship, &Transform)>().iter(world).map(|(ship, transform)| (ship.player, transform.translation)).collect();
+        ships.sort_by_key(|(player, _)| *player);
+        ships
+    }
+
+    fn player_world(players: usize, mode: GameMode) -> World {
+        let mut world = simulation_world(8, players);
+        world.resource_mut::<Game>().mode = mode;
+        world
+    }
+
+    #[test]
+    fn only_a_shared_screen_can_be_versus() {
+        let mut game = Game::new();
+        game.mode = GameMode::Versus;
+        assert!(!game.is_versus());
+        game.players = 2;
+        assert!(game.is_versus());
+        game.mode = GameMode::Coop;
+        assert!(!game.is_versus());
+    }
+
+    #[test]
+    fn points_go_to_the_player_and_the_team_total() {
+        let mut game = Game::new();
+        game.players = 2;
+        game.score_multiplier = 3;
+        game.add_score(1, 10);
+        game.add_score(0, 5);
+        assert_eq!(game.score, 45);
+        assert_eq!(game.player_scores[..2], [15, 30]);
+    }
+
+    #[test]
+    fn players_start_spread_evenly_around_the_star() {
+        for players in 1..=MAX_PLAYERS {
+            let starts = ships(&mut player_world(players, GameMode::Coop));
+            assert_eq!(starts.len(), players);
+            for (index, (player, position)) in starts.iter().enumerate() {
+                assert_eq!(*player, index);
+                assert!((position.length() - PLAYER_START_DISTANCE).abs() < 1e-3);
+                if players > 1 {
+                    let next = starts[(index + 1) % players].1;
+                    assert!((position.angle_between(next) - 2.0 * std::f32::consts::PI / players as f32).abs() < 1e-3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_versus_run_ends_with_one_ship_left_and_a_coop_run_with_none() {
+        for (mode, ends) in [(GameMode::Versus, true), (GameMode::Coop, false)] {
+            let mut world = player_world(2, mode);
+            let first = world.query::<(Entity, &Ship)>().iter(&world).find(|(_, ship)| ship.player == 0).unwrap().0;
+            world.despawn(first);
+            run_ticks(&mut world, 1);
+            assert_eq!(world.resource::<Game>().gameover_time > 0, ends, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn players_only_shoot_each_other_down_in_versus() {
+        for (mode, hit) in [(GameMode::Versus, true), (GameMode::Coop, false)] {
+            let mut world = player_world(2, mode);
+            let target = ships(&mut world)[0].1;
+            world.spawn((Bullet { owner: BulletOwner::Player(1) }, Lifetime(Duration::from_millis(BULLET_LIFETIME_MS)), Radius(BULLET_RADIUS), Mass(BULLET_MASS), Transform::from_translation(target), Velocity(Vec2::ZERO)));
+            run_ticks(&mut world, 1);
+            assert_eq!(ships(&mut world).len(), if hit { 1 } else { 2 }, "{:?}", mode);
+            assert_eq!(world.resource::<Game>().player_scores[1], if hit { SCORE_PLAYER_KILL } else { 0 }, "{:?}", mode);
+        }
+    }
 }
@@ -0,0 +1,409 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::audio::{play_queued_audio_system, AudioOutput, Decodable, Source};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::Duration;
+
+use crate::sfx::{sweep, AudioBackend, AudioSettings, Noise, Oscillator, SAMPLE_RATE};
+use crate::{Mass, Planet, PLANET_MASS_COLLAPSE_TRIGGER, PLANET_START_MASS};
+
+const MUSIC_GAIN: f32 = 0.3;
+const MUSIC_BASE_BPM: f32 = 96.0;
+const MUSIC_MAX_BPM: f32 = 150.0;
+const MUSIC_MAX_TRANSPOSE: f32 = 5.0;        // semitones the whole track climbs by at full tension
+const MUSIC_ROOT_HZ: f32 = 110.0;
+const MUSIC_STEPS_PER_BEAT: f32 = 4.0;
+const MUSIC_TENSION_SMOOTHING: f32 = 0.5;    // seconds for tension changes to settle so the tempo never jumps
+const MUSIC_HAT_TENSION: f32 = 0.3;          // hi-hats join in above this tension
+const MUSIC_BLOCK_SIZE: usize = 512;
+const MUSIC_CUE_SECONDS: f32 = 4.0;
+
+// semitones above the root for each sixteenth, None is a rest
+const MUSIC_BASS_PATTERN: [Option<i32>; 16] = [
+    Some(0), None, Some(0), None, Some(7), None, Some(0), None,
+    Some(3), None, Some(3), None, Some(5), None, Some(7), None,
+];
+const MUSIC_ARP_NOTES: [i32; 4] = [12, 15, 19, 24];
+const MUSIC_CUE_CHORD: [i32; 3] = [0, 3, 7];
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum Instrument {
+    Bass,
+    Arp,
+    Kick,
+    Hat,
+    Cue,
+}
+
+struct Voice {
+    instrument: Instrument,
+    frequency: f32,
+    gain: f32,
+    age: u32,
+    length: u32,
+    oscillator: Oscillator
+}
+
+impl Voice {
+    fn new(instrument: Instrument, frequency: f32, gain: f32, seconds: f32) -> Self {
+        Self {
+            instrument,
+            frequency,
+            gain,
+            age: 0,
+            length: (seconds * SAMPLE_RATE as f32) as u32,
+            oscillator: Oscillator::default()
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.age >= self.length
+    }
+
+    fn sample(&mut self, noise: &mut Noise) -> f32 {
+        let t = self.age as f32 / SAMPLE_RATE as f32;
+        let duration = self.length as f32 / SAMPLE_RATE as f32;
+        let attack = (t / 0.005).min(1.0);
+        let value = match self.instrument {
+            Instrument::Bass => {
+                let phase = self.oscillator.step(self.frequency);
+                let tone = (phase * 2.0 - 1.0) * 0.5 + (phase * 2.0 * std::f32::consts::PI).sin() * 0.5;
+                tone * attack * (-t * 4.0 / duration).exp()
+            }
+            Instrument::Arp => self.oscillator.square(self.frequency) * attack * (-t * 5.0 / duration).exp(),
+            Instrument::Kick => self.oscillator.sine(sweep(120.0, 45.0, t, 0.1)) * (-t * 5.0 / duration).exp(),
+            Instrument::Hat => noise.white() * (-t * 6.0 / duration).exp(),
+            Instrument::Cue => {
+                let swell = (t / 0.3).min(1.0) * (1.0 - t / duration);
+                self.oscillator.saw(sweep(self.frequency, self.frequency / 4.0, t, duration)) * swell
+            }
+        };
+        self.age += 1;
+        value * self.gain
+    }
+}
+
+// a step sequencer whose tempo and key follow a tension value between calm (0) and collapse (1)
+pub struct MusicSequencer {
+    position: u64,
+    next_step: f64,
+    step: usize,
+    tension: f32,
+    target_tension: f32,
+    collapsed: bool,
+    volume: f32,
+    voices: Vec<Voice>,
+    noise: Noise
+}
+
+impl Default for MusicSequencer {
+    fn default() -> Self {
+        Self {
+            position: 0,
+            next_step: 0.0,
+            step: 0,
+            tension: 0.0,
+            target_tension: 0.0,
+            collapsed: false,
+            volume: 1.0,
+            voices: Vec::new(),
+            noise: Noise::new(0)
+        }
+    }
+}
+
+impl MusicSequencer {
+    pub fn reset(&mut self) {
+        let volume = self.volume;
+        *self = Self { volume, ..Self::default() };
+    }
+
+    pub fn set_tension(&mut self, tension: f32) {
+        self.target_tension = tension.clamp(0.0, 1.0);
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    pub fn tempo(&self) -> f32 {
+        MUSIC_BASE_BPM + (MUSIC_MAX_BPM - MUSIC_BASE_BPM) * self.tension
+    }
+
+    pub fn transpose(&self) -> f32 {
+        2f32.powf(MUSIC_MAX_TRANSPOSE * self.tension / 12.0)
+    }
+
+    // the loop stops and a falling chord plays out, once per game
+    pub fn collapse(&mut self) {
+        if self.collapsed {
+            return;
+        }
+        self.collapsed = true;
+        let root = MUSIC_ROOT_HZ * 2.0 * self.transpose();
+        for semitones in MUSIC_CUE_CHORD {
+            self.voices.push(Voice::new(Instrument::Cue, note(root, semitones), 0.4, MUSIC_CUE_SECONDS));
+        }
+        self.voices.push(Voice::new(Instrument::Kick, 0.0, 1.0, 1.5));
+    }
+
+    pub fn render(&mut self, out: &mut [f32]) {
+        let smoothing = 1.0 / (MUSIC_TENSION_SMOOTHING * SAMPLE_RATE as f32);
+        for sample in out.iter_mut() {
+            self.tension += (self.target_tension - self.tension) * smoothing;
+            if !self.collapsed && self.position as f64 >= self.next_step {
+                self.trigger_step();
+                let samples_per_step = SAMPLE_RATE as f64 * 60.0 / (self.tempo() * MUSIC_STEPS_PER_BEAT) as f64;
+                self.next_step += samples_per_step;
+            }
+
+            let mut value = 0.0;
+            for voice in &mut self.voices {
+                value += voice.sample(&mut self.noise);
+            }
+            self.voices.retain(|voice| !voice.is_finished());
+
+            *sample = (value * MUSIC_GAIN * self.volume).clamp(-1.0, 1.0);
+            self.position += 1;
+        }
+    }
+
+    fn trigger_step(&mut self) {
+        let step_seconds = 60.0 / (self.tempo() * MUSIC_STEPS_PER_BEAT);
+        let root = MUSIC_ROOT_HZ * self.transpose();
+        let step = self.step % MUSIC_BASS_PATTERN.len();
+
+        if let Some(semitones) = MUSIC_BASS_PATTERN[step] {
+            self.voices.push(Voice::new(Instrument::Bass, note(root, semitones), 0.5, step_seconds * 2.0));
+        }
+        if step.is_multiple_of(4) {
+            self.voices.push(Voice::new(Instrument::Kick, 0.0, 0.8, 0.25));
+        }
+        if step % 2 == 1 && self.tension > MUSIC_HAT_TENSION {
+            self.voices.push(Voice::new(Instrument::Hat, 0.0, 0.1 * self.tension, 0.05));
+        }
+        // the arpeggio fades in as the star gets heavier
+        let arp = MUSIC_ARP_NOTES[self.step % MUSIC_ARP_NOTES.len()];
+        self.voices.push(Voice::new(Instrument::Arp, note(root, arp), 0.2 * self.tension, step_seconds));
+
+        self.step += 1;
+    }
+}
+
+fn note(root: f32, semitones: i32) -> f32 {
+    root * 2f32.powf(semitones as f32 / 12.0)
+}
+
+pub fn planet_tension(mass: f32) -> f32 {
+    ((mass - PLANET_START_MASS) / (PLANET_MASS_COLLAPSE_TRIGGER - PLANET_START_MASS)).clamp(0.0, 1.0)
+}
+
+// renders the track offline, ramping tension up to collapse at the given fraction of the way through
+pub fn render_music(seconds: f32, collapse_at: f32) -> Vec<f32> {
+    let mut sequencer = MusicSequencer::default();
+    let total = (seconds * SAMPLE_RATE as f32) as usize;
+    let collapse_sample = (total as f32 * collapse_at) as usize;
+    let mut samples = vec![0.0; total];
+    for (block_index, block) in samples.chunks_mut(MUSIC_BLOCK_SIZE).enumerate() {
+        let start = block_index * MUSIC_BLOCK_SIZE;
+        sequencer.set_tension(start as f32 / collapse_sample.max(1) as f32);
+        if start >= collapse_sample {
+            sequencer.collapse();
+        }
+        sequencer.render(block);
+    }
+    samples
+}
+
+pub fn write_wav(path: &str, samples: &[f32]) -> std::io::Result<()> {
+    let data_size = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());                  // pcm
+    bytes.extend_from_slice(&1u16.to_le_bytes());                  // mono
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());     // byte rate
+    bytes.extend_from_slice(&2u16.to_le_bytes());                  // block align
+    bytes.extend_from_slice(&16u16.to_le_bytes());                 // bits per sample
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    std::fs::write(path, bytes)
+}
+
+// rusteroids --render-music <path> [seconds]
+pub fn render_music_main(args: &[String]) {
+    let path = args.first().map(String::as_str).unwrap_or("music.wav");
+    let seconds = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(60.0);
+    let samples = render_music(seconds, 0.8);
+    match write_wav(path, &samples) {
+        Ok(()) => println!("wrote {} samples to {}", samples.len(), path),
+        Err(err) => {
+            println!("could not write {}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// the audio thread pulls blocks from the shared sequencer while the game adjusts it
+#[derive(TypeUuid)]
+#[uuid = "1b9e4c3a-7d52-4f0e-8c61-5a2f9d0b3e74"]
+pub struct MusicTrack {
+    sequencer: Arc<Mutex<MusicSequencer>>
+}
+
+pub struct MusicStream {
+    sequencer: Arc<Mutex<MusicSequencer>>,
+    buffer: Vec<f32>,
+    index: usize
+}
+
+impl Iterator for MusicStream {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.index >= self.buffer.len() {
+            self.buffer.resize(MUSIC_BLOCK_SIZE, 0.0);
+            if let Ok(mut sequencer) = self.sequencer.lock() {
+                sequencer.render(&mut self.buffer);
+            }
+            self.index = 0;
+        }
+        let sample = self.buffer[self.index];
+        self.index += 1;
+        Some(sample)
+    }
+}
+
+impl Source for MusicStream {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for MusicTrack {
+    type Decoder = MusicStream;
+    type DecoderItem = f32;
+
+    fn decoder(&self) -> MusicStream {
+        MusicStream { sequencer: self.sequencer.clone(), buffer: Vec::new(), index: 0 }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Music {
+    pub sequencer: Arc<Mutex<MusicSequencer>>
+}
+
+pub struct MusicPlugin {
+    pub backend: AudioBackend
+}
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Music>()
+            .add_system(music_volume);
+
+        if self.backend == AudioBackend::Device {
+            app.init_non_send_resource::<AudioOutput<MusicTrack>>()
+                .add_asset::<MusicTrack>()
+                .init_resource::<Audio<MusicTrack>>()
+                .add_startup_system(start_music)
+                .add_system_to_stage(CoreStage::PostUpdate, play_queued_audio_system::<MusicTrack>);
+        }
+    }
+}
+
+fn start_music(music: Res<Music>, mut tracks: ResMut<Assets<MusicTrack>>, audio: Res<Audio<MusicTrack>>) {
+    let track = tracks.add(MusicTrack { sequencer: music.sequencer.clone() });
+    audio.play(track);
+}
+
+pub fn reset_music(music: Res<Music>) {
+    if let Ok(mut sequencer) = music.sequencer.lock() {
+        sequencer.reset();
+    }
+}
+
+pub fn update_music(planet_query: Query<(&Mass, &Planet)>, music: Res<Music>) {
+    if let (Ok((Mass(mass), planet)), Ok(mut sequencer)) = (planet_query.get_single(), music.sequencer.lock()) {
+        if planet.collapsing {
+            sequencer.collapse();
+        } else {
+            sequencer.set_tension(planet_tension(*mass));
+        }
+    }
+}
+
+fn music_volume(settings: Res<AudioSettings>, music: Res<Music>) {
+    if settings.is_changed() {
+        if let Ok(mut sequencer) = music.sequencer.lock() {
+            sequencer.set_volume(settings.volume);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_audible(samples: &[f32]) -> usize {
+        samples.iter().rposition(|sample| *sample != 0.0).unwrap()
+    }
+
+    #[test]
+    fn a_render_is_as_long_as_asked_and_stays_in_range() {
+        let samples = render_music(2.5, 0.5);
+        assert_eq!(samples.len(), (2.5 * SAMPLE_RATE as f32) as usize);
+        assert!(samples.iter().all(|sample| sample.is_finite() && sample.abs() <= 1.0));
+        assert_eq!(samples, render_music(2.5, 0.5));
+    }
+
+    #[test]
+    fn the_tempo_rises_as_the_star_nears_collapse() {
+        let mut block = [0.0; MUSIC_BLOCK_SIZE];
+        let mut tempos = Vec::new();
+        for mass in [PLANET_START_MASS, 1000.0, 1800.0, PLANET_MASS_COLLAPSE_TRIGGER - 1.0] {
+            let mut sequencer = MusicSequencer::default();
+            sequencer.set_tension(planet_tension(mass));
+            for _block in 0..(4 * SAMPLE_RATE as usize / MUSIC_BLOCK_SIZE) {
+                sequencer.render(&mut block);
+            }
+            tempos.push(sequencer.tempo());
+        }
+        assert!((tempos[0] - MUSIC_BASE_BPM).abs() < 0.01);
+        assert!(tempos.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", tempos);
+        assert!(tempos[3] > MUSIC_MAX_BPM - 1.0);
+    }
+
+    // once the cue starts the loop stops, so the track falls silent exactly one cue after the collapse
+    #[test]
+    fn the_collapse_cue_plays_at_collapse_at() {
+        for (seconds, collapse_at) in [(8.0, 0.25), (12.0, 0.5)] {
+            let samples = render_music(seconds, collapse_at);
+            let collapse = seconds * collapse_at * SAMPLE_RATE as f32;
+            let expected = collapse + MUSIC_CUE_SECONDS * SAMPLE_RATE as f32;
+            let last = last_audible(&samples) as f32;
+            assert!((last - expected).abs() <= MUSIC_BLOCK_SIZE as f32, "silent from {} instead of {}", last, expected);
+            assert!(samples[..collapse as usize].iter().any(|sample| *sample != 0.0));
+        }
+    }
+}
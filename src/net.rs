@@ -0,0 +1,712 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::rc::Rc;
+
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+use crate::save::{capture_world, restore_world, RunState, SavedRun};
+use crate::sim::*;
+use crate::*;
+
+const NET_PLAYERS: usize = 2;
+const NET_INPUT_DELAY: u32 = 2;         // local inputs are scheduled this many ticks ahead to hide some latency
+const NET_MAX_PREDICTION: u32 = 12;     // stall rather than predict further ahead of the remote than this
+const NET_CHECKSUM_INTERVAL: u32 = 30;  // exchange a state checksum every N confirmed ticks
+const NET_CHECKSUM_HISTORY: usize = 64;
+const NET_MAX_PACKET: usize = 512;
+const NET_PACKET_INPUTS: u8 = 1;
+const NET_NO_CHECKSUM: u32 = u32::MAX;
+const NET_DEFAULT_SEED: u64 = 0x5c4a_12d5;
+
+const NET_STATUS_SIZE: f32 = 20.0;
+
+pub trait Transport {
+    fn send(&mut self, data: &[u8]);
+    fn receive(&mut self) -> Option<Vec<u8>>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket
+}
+
+impl UdpTransport {
+    pub fn bind(local: SocketAddr, peer: SocketAddr) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, data: &[u8]) {
+        // losing a packet is fine, every packet resends everything the peer hasn't acknowledged
+        let _ = self.socket.send(data);
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        let mut buffer = [0u8; NET_MAX_PACKET];
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(size) => return Some(buffer[..size].to_vec()),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return None,
+                // a connected socket reports the peer not listening yet as an error, skip it and keep reading
+                Err(_) => continue,
+            }
+        }
+    }
+}
+
+// an in-process network between two transports with configurable latency, jitter and packet loss
+pub struct LoopbackNetwork {
+    queues: [Vec<(f32, Vec<u8>)>; 2],
+    time_ms: f32,
+    latency_ms: f32,
+    jitter_ms: f32,
+    loss: f32,
+    rng: Pcg32
+}
+
+impl LoopbackNetwork {
+    pub fn pair(latency_ms: f32, jitter_ms: f32, loss: f32, seed: u64) -> (LoopbackTransport, LoopbackTransport, Rc<RefCell<LoopbackNetwork>>) {
+        let network = Rc::new(RefCell::new(Self {
+            queues: [Vec::new(), Vec::new()],
+            time_ms: 0.0,
+            latency_ms,
+            jitter_ms,
+            loss,
+            rng: Pcg32::seed_from_u64(seed)
+        }));
+        let a = LoopbackTransport { network: network.clone(), side: 0 };
+        let b = LoopbackTransport { network: network.clone(), side: 1 };
+        (a, b, network)
+    }
+
+    pub fn advance(&mut self, ms: f32) {
+        self.time_ms += ms;
+    }
+}
+
+pub struct LoopbackTransport {
+    network: Rc<RefCell<LoopbackNetwork>>,
+    side: usize
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, data: &[u8]) {
+        let mut network = self.network.borrow_mut();
+        let network = &mut *network;
+        if network.rng.gen::<f32>() < network.loss {
+            return;
+        }
+        let jitter = if network.jitter_ms > 0.0 { network.rng.gen_range(0.0..network.jitter_ms) } else { 0.0 };
+        let deliver_at = network.time_ms + network.latency_ms + jitter;
+        network.queues[1 - self.side].push((deliver_at, data.to_vec()));
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        let mut network = self.network.borrow_mut();
+        let time_ms = network.time_ms;
+        let queue = &mut network.queues[self.side];
+        let index = queue.iter().position(|(deliver_at, _)| *deliver_at <= time_ms)?;
+        Some(queue.remove(index).1)
+    }
+}
+
+struct Packet {
+    ack: u32,
+    start_tick: u32,
+    inputs: Vec<u8>,
+    checksum: Option<(u32, u64)>
+}
+
+impl Packet {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(22 + self.inputs.len());
+        data.push(NET_PACKET_INPUTS);
+        data.extend_from_slice(&self.ack.to_le_bytes());
+        data.extend_from_slice(&self.start_tick.to_le_bytes());
+        data.push(self.inputs.len() as u8);
+        data.extend_from_slice(&self.inputs);
+        let (checksum_tick, checksum) = self.checksum.unwrap_or((NET_NO_CHECKSUM, 0));
+        data.extend_from_slice(&checksum_tick.to_le_bytes());
+        data.extend_from_slice(&checksum.to_le_bytes());
+        data
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 10 || data[0] != NET_PACKET_INPUTS {
+            return None;
+        }
+        let ack = u32::from_le_bytes(data[1..5].try_into().ok()?);
+        let start_tick = u32::from_le_bytes(data[5..9].try_into().ok()?);
+        let count = data[9] as usize;
+        let inputs = data.get(10..10 + count)?.to_vec();
+        let rest = data.get(10 + count..10 + count + 12)?;
+        let checksum_tick = u32::from_le_bytes(rest[0..4].try_into().ok()?);
+        let checksum = u64::from_le_bytes(rest[4..12].try_into().ok()?);
+        Some(Self {
+            ack,
+            start_tick,
+            inputs,
+            checksum: if checksum_tick == NET_NO_CHECKSUM { None } else { Some((checksum_tick, checksum)) }
+        })
+    }
+}
+
+// runs the game ahead of the remote player by predicting their input, and rewinds the world to the
+// run saved before a tick and resimulates from there whenever a prediction turns out to be wrong
+pub struct RollbackSession<T: Transport> {
+    transport: T,
+    local_player: usize,
+    tick: u32,                   // ticks the world has been stepped through
+    history: VecDeque<SavedRun>, // history[i] is the run before tick history_start + i
+    history_start: u32,
+    local_inputs: Vec<u8>,
+    remote_inputs: Vec<u8>,      // confirmed remote inputs, contiguous from tick 0
+    used_remote_inputs: Vec<u8>, // remote input each tick was last simulated with
+    remote_ack: u32,             // how many of our inputs the remote has confirmed
+    checksums_until: u32,
+    local_checksums: BTreeMap<u32, u64>,
+    remote_checksums: BTreeMap<u32, u64>,
+    pub checksums_matched: u32,
+    pub desync_tick: Option<u32>,
+    pub rollbacks: u32,
+    pub max_rollback: u32,
+    pub stalled: bool
+}
+
+impl<T: Transport> RollbackSession<T> {
+    pub fn new(transport: T, local_player: usize) -> Self {
+        Self {
+            transport,
+            local_player,
+            tick: 0,
+            history: VecDeque::new(),
+            history_start: 0,
+            local_inputs: vec![0; NET_INPUT_DELAY as usize],
+            remote_inputs: Vec::new(),
+            used_remote_inputs: Vec::new(),
+            remote_ack: 0,
+            checksums_until: 0,
+            local_checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            checksums_matched: 0,
+            desync_tick: None,
+            rollbacks: 0,
+            max_rollback: 0,
+            stalled: false
+        }
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    fn confirmed_tick(&self) -> u32 {
+        u32::min(self.remote_inputs.len() as u32, self.tick)
+    }
+
+    // returns false if the session had to wait for the remote instead of simulating a tick
+    pub fn advance(&mut self, world: &mut World, local_input: u8) -> bool {
+        self.receive(world);
+        self.stalled = self.tick >= self.remote_inputs.len() as u32 + NET_MAX_PREDICTION;
+        if self.stalled {
+            self.send();
+            return false;
+        }
+
+        self.local_inputs.push(local_input);
+        self.send();
+        self.simulate_tick(world);
+        self.update_checksums(world);
+        true
+    }
+
+    fn send(&mut self) {
+        let start = self.remote_ack.min(self.local_inputs.len() as u32);
+        let end = self.local_inputs.len().min(start as usize + u8::MAX as usize);
+        let packet = Packet {
+            ack: self.remote_inputs.len() as u32,
+            start_tick: start,
+            inputs: self.local_inputs[start as usize..end].to_vec(),
+            checksum: self.local_checksums.iter().next_back().map(|(tick, checksum)| (*tick, *checksum))
+        };
+        self.transport.send(&packet.encode());
+    }
+
+    fn receive(&mut self, world: &mut World) {
+        let mut rollback_from: Option<u32> = None;
+        while let Some(data) = self.transport.receive() {
+            let packet = match Packet::decode(&data) {
+                Some(packet) => packet,
+                None => continue,
+            };
+            self.remote_ack = self.remote_ack.max(packet.ack);
+
+            for (i, bits) in packet.inputs.iter().enumerate() {
+                let tick = packet.start_tick + i as u32;
+                // anything we already have is a resend, anything past a gap will be resent later
+                if tick != self.remote_inputs.len() as u32 {
+                    continue;
+                }
+                self.remote_inputs.push(*bits);
+                if tick < self.tick && self.used_remote_inputs[tick as usize] != *bits {
+                    rollback_from = Some(rollback_from.map_or(tick, |from| from.min(tick)));
+                }
+            }
+
+            if let Some((tick, checksum)) = packet.checksum {
+                if self.remote_checksums.insert(tick, checksum).is_none() {
+                    self.compare_checksum(tick);
+                }
+            }
+        }
+
+        if let Some(tick) = rollback_from {
+            self.rollback(world, tick);
+        }
+    }
+
+    fn rollback(&mut self, world: &mut World, tick: u32) {
+        let current_tick = self.tick;
+        let index = (tick - self.history_start) as usize;
+        restore_world(world, &self.history[index]);
+        self.history.truncate(index);
+        self.tick = tick;
+        without_effects(world, |world| {
+            while self.tick < current_tick {
+                self.simulate_tick(world);
+            }
+        });
+        self.rollbacks += 1;
+        self.max_rollback = self.max_rollback.max(current_tick - tick);
+    }
+
+    fn simulate_tick(&mut self, world: &mut World) {
+        let tick = self.tick as usize;
+        // predict that the remote keeps doing what they were last seen doing, except for one-shot actions
+        let remote_input = match self.remote_inputs.get(tick) {
+            Some(bits) => *bits,
+            None => self.remote_inputs.last().copied().unwrap_or(0) & !INPUT_TELEPORT,
+        };
+        if tick < self.used_remote_inputs.len() {
+            self.used_remote_inputs[tick] = remote_input;
+        } else {
+            self.used_remote_inputs.push(remote_input);
+        }
+
+        let mut inputs = [0u8; NET_PLAYERS];
+        inputs[self.local_player] = self.local_inputs[tick];
+        inputs[1 - self.local_player] = remote_input;
+
+        self.history.push_back(capture_world(world));
+        set_ship_inputs(world, &inputs);
+        run_ticks(world, 1);
+        self.tick += 1;
+    }
+
+    fn update_checksums(&mut self, world: &mut World) {
+        let confirmed = self.confirmed_tick();
+        while self.checksums_until < confirmed {
+            let tick = self.checksums_until + 1; // checksum of the run once this many ticks have run
+            if tick.is_multiple_of(NET_CHECKSUM_INTERVAL) {
+                let checksum = if tick == self.tick {
+                    capture_world(world).checksum()
+                } else {
+                    self.history[(tick - self.history_start) as usize].checksum()
+                };
+                self.local_checksums.insert(tick, checksum);
+                self.compare_checksum(tick);
+            }
+            self.checksums_until += 1;
+        }
+
+        while self.local_checksums.len() > NET_CHECKSUM_HISTORY {
+            self.local_checksums.pop_first();
+        }
+        while self.remote_checksums.len() > NET_CHECKSUM_HISTORY {
+            self.remote_checksums.pop_first();
+        }
+
+        // confirmed ticks can never be rolled back to, so their runs are no longer needed
+        while self.history_start < confirmed && !self.history.is_empty() {
+            self.history.pop_front();
+            self.history_start += 1;
+        }
+    }
+
+    fn compare_checksum(&mut self, tick: u32) {
+        if let (Some(local), Some(remote)) = (self.local_checksums.get(&tick), self.remote_checksums.get(&tick)) {
+            if local == remote {
+                self.checksums_matched += 1;
+            } else if self.desync_tick.is_none() {
+                self.desync_tick = Some(tick);
+            }
+        }
+    }
+}
+
+// a peer's world for an online game, the same new run on both sides
+fn online_world(seed: u64) -> World {
+    let mut world = simulation_world(seed, NET_PLAYERS);
+    world.resource_mut::<Game>().mode = GameMode::Versus;
+    world
+}
+
+pub struct LoopbackReport {
+    pub ticks: [u32; 2],
+    pub rollbacks: [u32; 2],
+    pub max_rollback: [u32; 2],
+    pub checksums_matched: u32,
+    pub desync_tick: Option<u32>,
+    pub final_checksums: [u64; 2]
+}
+
+// plays two sessions against each other over a simulated network with scripted random inputs
+pub fn run_loopback(ticks: u32, latency_ms: f32, jitter_ms: f32, loss: f32, seed: u64) -> LoopbackReport {
+    play_loopback(ticks, latency_ms, jitter_ms, loss, seed, |_, _, _| {})
+}
+
+// tamper gets each peer's world after every frame, to knock one out of step on purpose
+fn play_loopback(ticks: u32, latency_ms: f32, jitter_ms: f32, loss: f32, seed: u64, mut tamper: impl FnMut(usize, u32, &mut World)) -> LoopbackReport {
+    let (transport_a, transport_b, network) = LoopbackNetwork::pair(latency_ms, jitter_ms, loss, seed);
+    let mut sessions = [RollbackSession::new(transport_a, 0), RollbackSession::new(transport_b, 1)];
+    let mut worlds = [online_world(seed), online_world(seed)];
+    let mut input_rng = Pcg32::seed_from_u64(seed ^ 0xa5a5);
+    let mut inputs = [0u8; 2];
+
+    let mut frame = 0;
+    while sessions.iter().any(|session| session.tick() < ticks) && frame < ticks * 4 {
+        for (player, (session, world)) in sessions.iter_mut().zip(&mut worlds).enumerate() {
+            if input_rng.gen_range(0..10) == 0 {
+                inputs[player] = input_rng.gen::<u8>() & (INPUT_LEFT | INPUT_RIGHT | INPUT_THRUST | INPUT_FIRE | INPUT_TELEPORT);
+            }
+            if session.tick() < ticks {
+                session.advance(world, inputs[player]);
+            }
+            tamper(player, session.tick(), world);
+            drop_effects(world);
+        }
+        network.borrow_mut().advance(1000.0 / SIM_TICK_RATE as f32);
+        frame += 1;
+    }
+
+    // let the final inputs and checksums arrive
+    for _i in 0..(latency_ms + jitter_ms) as u32 / 10 + 10 {
+        for (session, world) in sessions.iter_mut().zip(&mut worlds) {
+            session.receive(world);
+            session.send();
+            session.update_checksums(world);
+        }
+        network.borrow_mut().advance(10.0);
+    }
+
+    LoopbackReport {
+        ticks: [sessions[0].tick(), sessions[1].tick()],
+        rollbacks: [sessions[0].rollbacks, sessions[1].rollbacks],
+        max_rollback: [sessions[0].max_rollback, sessions[1].max_rollback],
+        checksums_matched: sessions[0].checksums_matched + sessions[1].checksums_matched,
+        desync_tick: sessions[0].desync_tick.or(sessions[1].desync_tick),
+        final_checksums: [capture_world(&mut worlds[0]).checksum(), capture_world(&mut worlds[1]).checksum()]
+    }
+}
+
+// rusteroids --net-loopback [ticks] [latency ms] [loss 0-1]
+pub fn loopback_main(args: &[String]) {
+    let ticks = args.first().and_then(|arg| arg.parse().ok()).unwrap_or(3600);
+    let latency_ms = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(80.0);
+    let loss = args.get(2).and_then(|arg| arg.parse().ok()).unwrap_or(0.1);
+    let report = run_loopback(ticks, latency_ms, latency_ms * 0.25, loss, NET_DEFAULT_SEED);
+    println!("ticks simulated:   {} / {}", report.ticks[0], report.ticks[1]);
+    println!("rollbacks:         {} / {}", report.rollbacks[0], report.rollbacks[1]);
+    println!("deepest rollback:  {} / {} ticks", report.max_rollback[0], report.max_rollback[1]);
+    println!("checksums matched: {}", report.checksums_matched);
+    println!("final states:      {}", if report.final_checksums[0] == report.final_checksums[1] { "match" } else { "differ" });
+    match report.desync_tick {
+        Some(tick) => {
+            println!("DESYNC at tick {}", tick);
+            std::process::exit(1);
+        }
+        None => println!("no desync"),
+    }
+}
+
+// rusteroids --net <local addr> <peer addr> <player 1|2> [seed]
+#[derive(Resource)]
+pub struct NetConfig {
+    pub local: SocketAddr,
+    pub peer: SocketAddr,
+    pub player: usize,
+    pub seed: u64,
+    pub fixed: bool               // whether the seed was given, both peers are started with the same arguments
+}
+
+impl NetConfig {
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let index = args.iter().position(|arg| arg == "--net")?;
+        let local = args.get(index + 1)?.parse().ok()?;
+        let peer = args.get(index + 2)?.parse().ok()?;
+        let player: usize = args.get(index + 3)?.parse().ok()?;
+        let given_seed: Option<u64> = args.get(index + 4).and_then(|arg| arg.parse().ok());
+        let (seed, fixed) = (given_seed.unwrap_or(NET_DEFAULT_SEED), given_seed.is_some());
+        if !(1..=NET_PLAYERS).contains(&player) {
+            return None;
+        }
+        Some(Self { local, peer, player: player - 1, seed, fixed })
+    }
+}
+
+#[derive(Resource)]
+pub struct NetPlay {
+    session: RollbackSession<UdpTransport>,
+    pending_teleport: bool
+}
+
+#[derive(Component)]
+pub struct NetStatusText;
+
+pub fn setup_online_hint(mut commands: Commands, asset_server: Res<AssetServer>, config: Option<Res<NetConfig>>, strings: Res<Strings>, theme: Res<Theme>) {
+    let config = match config {
+        Some(config) => config,
+        None => return,
+    };
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: TITLE_HINT_SIZE,
+        color: theme.hint,
+    };
+    commands.spawn(
+        Text2dBundle {
+            text: Text::from_section(strings.format("online.hint", &[&(config.player + 1), &config.peer]), text_style)
+                .with_alignment(TextAlignment::BOTTOM_LEFT),
+            transform: Transform::from_xyz(-SIM_ARENA_HALF_SIZE.x + 20.0, -SIM_ARENA_HALF_SIZE.y + 35.0, 0.0),
+            ..default()
+        }
+    );
+}
+
+pub fn start_online(mut commands: Commands, mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>, config: Option<Res<NetConfig>>) {
+    let config = match config {
+        Some(config) => config,
+        None => return,
+    };
+    if !keyboard_input.just_pressed(KeyCode::N) {
+        return;
+    }
+    match UdpTransport::bind(config.local, config.peer) {
+        Ok(transport) => {
+            commands.insert_resource(NetPlay {
+                session: RollbackSession::new(transport, config.player),
+                pending_teleport: false
+            });
+            state.set(GameState::Online).unwrap();
+        }
+        Err(err) => error!("could not open {}: {}", config.local, err),
+    }
+}
+
+// both peers start the same run from the same seed with the rules at their defaults
+pub fn setup_online(mut commands: Commands, mut run: RunState, config: Res<NetConfig>, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    run.game.players = NET_PLAYERS;
+    run.game.mode = GameMode::Versus;
+    run.game.black_hole_phase = false;
+    run.start(&mut commands, Some(GameRng::new(config.seed, config.fixed)), &theme);
+
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: SCORE_SIZE,
+        color: theme.title,
+    };
+    let sections = (0..NET_PLAYERS).map(|player| {
+        let mut text_style = text_style.clone();
+        text_style.color = theme.players[player];
+        TextSection::new("", text_style)
+    });
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_sections(sections)
+                .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(0.0, 330.0, 0.0),
+            ..default()
+        },
+        ScoreText)
+    );
+
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: NET_STATUS_SIZE,
+        color: theme.hint,
+    };
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section("", text_style)
+                .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(0.0, -330.0, 0.0),
+            ..default()
+        },
+        NetStatusText)
+    );
+}
+
+// the local player's keys go to the session once per tick the frame was worth
+pub fn update_online(world: &mut World) {
+    let keyboard_input = world.resource::<Input<KeyCode>>();
+    let controls = &PLAYER_CONTROLS[0];
+    let teleport = keyboard_input.just_pressed(controls.teleport);
+    let mut input = ShipInput {
+        left: keyboard_input.pressed(controls.left),
+        right: keyboard_input.pressed(controls.right),
+        thrust: keyboard_input.pressed(controls.thrust),
+        fire: keyboard_input.pressed(controls.fire),
+        teleport: false
+    };
+
+    let delta = world.resource::<Time>().delta();
+    let ticks = world.resource_mut::<Simulation>().due(delta);
+    world.resource_scope(|world, mut net: Mut<NetPlay>| {
+        net.pending_teleport |= teleport;
+        for _tick in 0..ticks {
+            input.teleport = net.pending_teleport;
+            if !net.session.advance(world, input.to_bits()) {
+                break;
+            }
+            net.pending_teleport = false;
+        }
+    });
+}
+
+pub fn update_net_status(net: Res<NetPlay>, mut query: Query<&mut Text, With<NetStatusText>>, strings: Res<Strings>, theme: Res<Theme>) {
+    let session = &net.session;
+    for mut text in &mut query {
+        let section = &mut text.sections[0];
+        if let Some(tick) = session.desync_tick {
+            section.value = strings.format("online.desync", &[&tick]);
+            section.style.color = theme.warning;
+        } else if session.stalled {
+            section.value = strings.get("online.waiting").to_string();
+        } else {
+            section.value = String::new();
+        }
+    }
+}
+
+// the session is kept through the results screen so it knows the match was online
+pub fn teardown_online(mut commands: Commands) {
+    commands.remove_resource::<NetPlay>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particles::Particle;
+
+    const SCRIPT: [u8; 4] = [INPUT_THRUST | INPUT_FIRE, INPUT_LEFT | INPUT_FIRE, INPUT_FIRE | INPUT_TELEPORT, INPUT_RIGHT | INPUT_THRUST];
+
+    fn scripted_run(seed: u64, script: &[u8]) -> World {
+        let mut world = online_world(seed);
+        for tick in 0..300 {
+            let bits = script[tick / 30 % script.len()];
+            set_ship_inputs(&mut world, &[bits, bits & !INPUT_FIRE]);
+            run_ticks(&mut world, 1);
+            drop_effects(&mut world);
+        }
+        world
+    }
+
+    fn checksum(world: &mut World) -> u64 {
+        capture_world(world).checksum()
+    }
+
+    #[test]
+    fn same_seed_and_inputs_give_the_same_checksum() {
+        let expected = checksum(&mut scripted_run(5, &SCRIPT));
+        assert_eq!(checksum(&mut scripted_run(5, &SCRIPT)), expected);
+        assert_ne!(checksum(&mut scripted_run(6, &SCRIPT)), expected);
+        assert_ne!(checksum(&mut scripted_run(5, &SCRIPT[1..])), expected);
+    }
+
+    #[test]
+    fn checksum_survives_a_restore_and_ignores_the_trajectory_preview() {
+        let run = capture_world(&mut scripted_run(5, &SCRIPT));
+        let mut world = online_world(99);
+        world.resource_mut::<Game>().draw_trajectory = true;
+        restore_world(&mut world, &run);
+        assert_eq!(checksum(&mut world), run.checksum());
+    }
+
+    fn config(args: &str) -> Option<NetConfig> {
+        NetConfig::from_args(&args.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn the_seed_and_whether_it_was_given_come_from_the_arguments() {
+        let given = config("rusteroids --net 127.0.0.1:7000 127.0.0.1:7001 2 42").unwrap();
+        assert_eq!((given.player, given.seed, given.fixed), (1, 42, true));
+        let default = config("rusteroids --net 127.0.0.1:7000 127.0.0.1:7001 1").unwrap();
+        assert_eq!((default.player, default.seed, default.fixed), (0, NET_DEFAULT_SEED, false));
+        assert!(config("rusteroids --net 127.0.0.1:7000 127.0.0.1:7001 3").is_none());
+    }
+
+    fn effects(world: &mut World) -> (usize, usize) {
+        (world.resource::<Events<SoundEffect>>().len(), world.query::<&Particle>().iter(world).count())
+    }
+
+    #[test]
+    fn resimulated_ticks_are_not_seen_or_heard_again() {
+        // a shot already touching the first ship blows it up on the first tick
+        let mut world = online_world(5);
+        let target = world.query::<(&Ship, &Transform)>().iter(&world).find(|(ship, _)| ship.player == 0).unwrap().1.translation;
+        world.spawn((Bullet { owner: BulletOwner::Player(1) }, Lifetime(Duration::from_millis(BULLET_LIFETIME_MS)), Radius(BULLET_RADIUS), Mass(BULLET_MASS), Transform::from_translation(target), Velocity(Vec2::ZERO)));
+        let start = capture_world(&mut world);
+        let play = |world: &mut World| {
+            for _tick in 0..10 {
+                set_ship_inputs(world, &[0, INPUT_FIRE]);
+                run_ticks(world, 1);
+            }
+        };
+        play(&mut world);
+        let (sounds, particles) = effects(&mut world);
+        assert!(sounds > 0 && particles > 0, "{} sounds and {} particles", sounds, particles);
+
+        world.resource_mut::<Events<SoundEffect>>().clear();
+        drop_effects(&mut world);
+        restore_world(&mut world, &start);
+        without_effects(&mut world, play);
+        assert_eq!(effects(&mut world), (0, 0));
+    }
+
+    #[test]
+    fn rollback_resimulates_to_the_same_state() {
+        let report = run_loopback(600, 100.0, 30.0, 0.0, 9);
+        assert_eq!(report.ticks, [600, 600]);
+        assert!(report.rollbacks[0] + report.rollbacks[1] > 0);
+        assert_eq!(report.final_checksums[0], report.final_checksums[1]);
+        assert!(report.checksums_matched > 0);
+        assert_eq!(report.desync_tick, None);
+    }
+
+    #[test]
+    fn peers_stay_in_step_under_loss_and_latency() {
+        let report = run_loopback(600, 80.0, 20.0, 0.2, 4);
+        assert_eq!(report.ticks, [600, 600]);
+        assert_eq!(report.final_checksums[0], report.final_checksums[1]);
+        assert_eq!(report.desync_tick, None);
+    }
+
+    #[test]
+    fn desync_is_detected_under_loss_and_latency() {
+        let report = play_loopback(600, 80.0, 20.0, 0.2, 4, |player, tick, world| {
+            if player == 1 && tick >= 200 {
+                world.resource_mut::<Tuning>().gravity = GRAVITY * 1.05;
+            }
+        });
+        let tick = report.desync_tick.expect("the tampered peer should be caught");
+        assert!(tick >= 180, "desync reported at tick {} before the tampering", tick);
+        assert_ne!(report.final_checksums[0], report.final_checksums[1]);
+    }
+}
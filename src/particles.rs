@@ -0,0 +1,356 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::tween::{lerp_color, Ease};
+use crate::theme::Theme;
+use crate::vector::VectorLines;
+use crate::{gravity_velocity, Mass, Planet, Tuning};
+
+const TRAIL_MAX_LIFE: f32 = 3.0;
+const TRAIL_START_ALPHA: f32 = 0.2;
+const TRAIL_MAX_STEP: f32 = 10.0; // dont join points across large jumps in distance (teleports, wrapping)
+
+const EXPLOSION_MAX_LIFE: f32 = 0.5;
+const EXPLOSION_MAX_RADIUS: f32 = 40.0;
+
+const TELEPORT_FX_TIME: f32 = 0.6;
+const TELEPORT_FX_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.1);
+const TELEPORT_FX_SIZE: f32 = 150.0;
+
+const PARTICLE_MASS: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EmitterShape {
+    Point,
+    Circle { radius: f32 },
+    // joins the emitter's previous position to its current one, for trails
+    Path,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParticleShape {
+    Circle { segments: u32 },
+    // a fixed segment from the particle to its offset
+    Line,
+    // a segment trailing behind the particle along its velocity
+    Streak,
+}
+
+// a value over the particle's life, t runs from 0 at birth to 1 at death
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ParticleCurve {
+    pub start: f32,
+    pub end: f32,
+    pub ease: Ease
+}
+
+impl ParticleCurve {
+    pub const fn constant(value: f32) -> Self {
+        Self { start: value, end: value, ease: Ease::Linear }
+    }
+
+    pub fn at(&self, t: f32) -> f32 {
+        self.ease.lerp(self.start, self.end, t)
+    }
+}
+
+pub struct ParticleEffect {
+    pub count: u32,
+    pub emitter: EmitterShape,
+    pub speed: (f32, f32),
+    pub lifetime: (f32, f32),
+    pub inherit_velocity: f32,
+    pub gravity: bool,
+    pub shape: ParticleShape,
+    pub size: ParticleCurve,
+    pub alpha: ParticleCurve,
+    pub color_start: Color,
+    pub color_end: Color
+}
+
+pub const EXPLOSION: ParticleEffect = ParticleEffect {
+    count: 1,
+    emitter: EmitterShape::Point,
+    speed: (0.0, 0.0),
+    lifetime: (EXPLOSION_MAX_LIFE, EXPLOSION_MAX_LIFE),
+    inherit_velocity: 1.0,
+    gravity: false,
+    shape: ParticleShape::Circle { segments: 20 },
+    size: ParticleCurve { start: 0.0, end: EXPLOSION_MAX_RADIUS, ease: Ease::QuadOut },
+    alpha: ParticleCurve { start: 0.5, end: 0.0, ease: Ease::Linear },
+    color_start: Color::WHITE,
+    color_end: Color::WHITE
+};
+
+pub const DEBRIS_SPARKS: ParticleEffect = ParticleEffect {
+    count: 8,
+    emitter: EmitterShape::Circle { radius: 2.0 },
+    speed: (40.0, 120.0),
+    lifetime: (0.3, 0.8),
+    inherit_velocity: 0.5,
+    gravity: true,
+    shape: ParticleShape::Streak,
+    size: ParticleCurve { start: 5.0, end: 1.0, ease: Ease::Linear },
+    alpha: ParticleCurve { start: 0.8, end: 0.0, ease: Ease::QuadIn },
+    color_start: Color::rgb(1.0, 0.9, 0.5),
+    color_end: Color::rgb(1.0, 0.3, 0.1)
+};
+
+pub const PLASMA_TRAIL: ParticleEffect = ParticleEffect {
+    count: 1,
+    emitter: EmitterShape::Circle { radius: 3.0 },
+    speed: (5.0, 20.0),
+    lifetime: (0.2, 0.5),
+    inherit_velocity: 0.0,
+    gravity: false,
+    shape: ParticleShape::Circle { segments: 4 },
+    size: ParticleCurve { start: 1.5, end: 0.0, ease: Ease::QuadIn },
+    alpha: ParticleCurve { start: 0.6, end: 0.0, ease: Ease::Linear },
+    color_start: Color::rgb(1.0, 0.8, 0.3),
+    color_end: Color::rgb(0.8, 0.2, 0.1)
+};
+
+pub const TELEPORT_ENTER: ParticleEffect = ParticleEffect {
+    count: 1,
+    emitter: EmitterShape::Point,
+    speed: (0.0, 0.0),
+    lifetime: (TELEPORT_FX_TIME, TELEPORT_FX_TIME),
+    inherit_velocity: 0.0,
+    gravity: false,
+    shape: ParticleShape::Circle { segments: 20 },
+    size: ParticleCurve { start: 0.0, end: TELEPORT_FX_SIZE, ease: Ease::QuadOut },
+    alpha: ParticleCurve { start: 1.0, end: 0.0, ease: Ease::QuadOut },
+    color_start: TELEPORT_FX_COLOR,
+    color_end: TELEPORT_FX_COLOR
+};
+
+pub const TELEPORT_EXIT: ParticleEffect = ParticleEffect {
+    size: ParticleCurve { start: TELEPORT_FX_SIZE, end: 0.0, ease: Ease::QuadOut },
+    alpha: ParticleCurve { start: 0.0, end: 1.0, ease: Ease::QuadOut },
+    ..TELEPORT_ENTER
+};
+
+pub const SHIP_TRAIL: ParticleEffect = ParticleEffect {
+    count: 1,
+    emitter: EmitterShape::Path,
+    speed: (0.0, 0.0),
+    lifetime: (TRAIL_MAX_LIFE, TRAIL_MAX_LIFE),
+    inherit_velocity: 0.0,
+    gravity: false,
+    shape: ParticleShape::Line,
+    size: ParticleCurve::constant(1.0),
+    alpha: ParticleCurve { start: TRAIL_START_ALPHA, end: 0.0, ease: Ease::Linear },
+    color_start: Color::WHITE,
+    color_end: Color::WHITE
+};
+
+#[derive(Component)]
+pub struct Particle {
+    shape: ParticleShape,
+    offset: Vec3,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    gravity: bool,
+    size: ParticleCurve,
+    alpha: ParticleCurve,
+    color_start: Color,
+    color_end: Color
+}
+
+// emits an effect every frame from wherever the entity is, with an optional colour override
+#[derive(Component)]
+pub struct ParticleEmitter {
+    pub effect: &'static ParticleEffect,
+    pub color: Option<Color>,
+    last_position: Option<Vec3>
+}
+
+impl ParticleEmitter {
+    pub fn new(effect: &'static ParticleEffect, color: Option<Color>) -> Self {
+        Self { effect, color, last_position: None }
+    }
+}
+
+fn random_between(rng: &mut impl Rng, (min, max): (f32, f32)) -> f32 {
+    if max > min { rng.gen_range(min..max) } else { min }
+}
+
+fn spawn_particle(commands: &mut Commands, effect: &ParticleEffect, position: Vec3, offset: Vec3, velocity: Vec2, color: Option<Color>, rng: &mut impl Rng) {
+    commands.spawn((Particle {
+                        shape: effect.shape,
+                        offset,
+                        velocity,
+                        age: 0.0,
+                        lifetime: random_between(rng, effect.lifetime),
+                        gravity: effect.gravity,
+                        size: effect.size,
+                        alpha: effect.alpha,
+                        color_start: color.unwrap_or(effect.color_start),
+                        color_end: color.unwrap_or(effect.color_end)
+                    },
+                    Transform::from_translation(position)));
+}
+
+// a one off burst of the effect, moving with the given source velocity
+pub fn spawn_effect(commands: &mut Commands, effect: &ParticleEffect, position: Vec3, source_velocity: Vec2) {
+    let mut rng = rand::thread_rng();
+    for _i in 0..effect.count {
+        let angle: f32 = rng.gen_range(0.0..2.0 * std::f32::consts::PI);
+        let direction = Vec2::new(angle.cos(), angle.sin());
+        let offset = match effect.emitter {
+            EmitterShape::Circle { radius } => direction * radius,
+            EmitterShape::Point | EmitterShape::Path => Vec2::ZERO,
+        };
+        let velocity = direction * random_between(&mut rng, effect.speed) + source_velocity * effect.inherit_velocity;
+        spawn_particle(commands, effect, position + offset.extend(0.0), Vec3::ZERO, velocity, None, &mut rng);
+    }
+}
+
+pub fn emit_particles(mut commands: Commands, mut query: Query<(&Transform, &mut ParticleEmitter)>) {
+    let mut rng = rand::thread_rng();
+    for (transform, mut emitter) in &mut query {
+        let position = transform.translation;
+        if let Some(last_position) = emitter.last_position {
+            let effect = emitter.effect;
+            match effect.emitter {
+                EmitterShape::Path => {
+                    if Vec3::distance(last_position, position) < TRAIL_MAX_STEP {
+                        spawn_particle(&mut commands, effect, position, last_position - position, Vec2::ZERO, emitter.color, &mut rng);
+                    }
+                }
+                EmitterShape::Point | EmitterShape::Circle { .. } => spawn_effect(&mut commands, effect, position, Vec2::ZERO),
+            }
+        }
+        emitter.last_position = Some(position);
+    }
+}
+
+type ParticlePlanetQuery<'w, 's> = Query<'w, 's, (&'static Transform, &'static Mass), (With<Planet>, Without<Particle>)>;
+
+pub fn update_particles(mut commands: Commands, mut query: Query<(Entity, &mut Particle, &mut Transform)>, planet_query: ParticlePlanetQuery, tuning: Res<Tuning>, time: Res<Time>) {
+    let planet = planet_query.get_single().ok();
+    for (entity, mut particle, mut transform) in &mut query {
+        particle.age += time.delta_seconds();
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        if particle.gravity {
+            if let Some((planet_transform, Mass(planet_mass))) = planet {
+                let added_velocity = gravity_velocity(tuning.gravity, planet_transform.translation, *planet_mass, transform.translation, PARTICLE_MASS);
+                particle.velocity += added_velocity * time.delta_seconds();
+            }
+        }
+        transform.translation.x += particle.velocity.x * time.delta_seconds();
+        transform.translation.y += particle.velocity.y * time.delta_seconds();
+    }
+}
+
+pub fn draw_particles(query: Query<(&Particle, &Transform)>, mut lines: ResMut<VectorLines>, theme: Res<Theme>) {
+    for (particle, transform) in &query {
+        let t = particle.age / particle.lifetime;
+        let size = particle.size.at(t);
+        let mut color = theme.tint(lerp_color(particle.color_start, particle.color_end, t));
+        color.set_a(color.a() * particle.alpha.at(t));
+        let position = transform.translation;
+        match particle.shape {
+            ParticleShape::Circle { segments } => lines.circle(position, size, color, segments),
+            ParticleShape::Line => lines.line(position, position + particle.offset, color),
+            ParticleShape::Streak => {
+                let tail = particle.velocity.normalize_or_zero().extend(0.0) * size;
+                lines.line(position, position - tail, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::{Duration, Instant};
+
+    use super::*;
+
+    fn particles(world: &mut World) -> usize {
+        world.query::<&Particle>().iter(world).count()
+    }
+
+    fn emit(world: &mut World) {
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(emit_particles);
+        stage.run(world);
+    }
+
+    #[test]
+    fn curves_run_from_start_to_end() {
+        let curve = ParticleCurve { start: 4.0, end: 0.0, ease: Ease::Linear };
+        assert_eq!(curve.at(0.0), 4.0);
+        assert_eq!(curve.at(0.5), 2.0);
+        assert_eq!(curve.at(1.0), 0.0);
+        assert_eq!(ParticleCurve::constant(3.0).at(0.7), 3.0);
+    }
+
+    #[test]
+    fn a_burst_spawns_the_effects_count() {
+        let mut world = World::new();
+        let mut queue = bevy::ecs::system::CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        spawn_effect(&mut commands, &DEBRIS_SPARKS, Vec3::ZERO, Vec2::ZERO);
+        queue.apply(&mut world);
+        assert_eq!(particles(&mut world), DEBRIS_SPARKS.count as usize);
+        for particle in world.query::<&Particle>().iter(&world) {
+            assert!(particle.lifetime >= DEBRIS_SPARKS.lifetime.0 && particle.lifetime <= DEBRIS_SPARKS.lifetime.1);
+        }
+    }
+
+    #[test]
+    fn emitters_start_on_their_second_frame() {
+        let mut world = World::new();
+        world.spawn((Transform::default(), ParticleEmitter::new(&DEBRIS_SPARKS, None)));
+        emit(&mut world);
+        assert_eq!(particles(&mut world), 0);
+        emit(&mut world);
+        assert_eq!(particles(&mut world), DEBRIS_SPARKS.count as usize);
+        emit(&mut world);
+        assert_eq!(particles(&mut world), 2 * DEBRIS_SPARKS.count as usize);
+    }
+
+    #[test]
+    fn trails_join_small_steps_but_not_jumps() {
+        let mut world = World::new();
+        let ship = world.spawn((Transform::default(), ParticleEmitter::new(&SHIP_TRAIL, None))).id();
+        emit(&mut world);
+        world.get_mut::<Transform>(ship).unwrap().translation.x += TRAIL_MAX_STEP / 2.0;
+        emit(&mut world);
+        assert_eq!(particles(&mut world), 1);
+        world.get_mut::<Transform>(ship).unwrap().translation.x += TRAIL_MAX_STEP * 2.0;
+        emit(&mut world);
+        assert_eq!(particles(&mut world), 1);
+    }
+
+    #[test]
+    fn particles_go_away_at_the_end_of_their_life() {
+        let mut world = World::new();
+        let start = Instant::now();
+        let mut time = Time::new(start);
+        time.update_with_instant(start);
+        world.insert_resource(time);
+        world.insert_resource(Tuning::default());
+        let mut queue = bevy::ecs::system::CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        spawn_effect(&mut commands, &EXPLOSION, Vec3::ZERO, Vec2::new(10.0, 0.0));
+        queue.apply(&mut world);
+
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(update_particles);
+        let step = Duration::from_secs_f32(EXPLOSION_MAX_LIFE * 0.6);
+        world.resource_mut::<Time>().update_with_instant(start + step);
+        stage.run(&mut world);
+        let x = world.query_filtered::<&Transform, With<Particle>>().single(&world).translation.x;
+        assert!((x - 10.0 * step.as_secs_f32()).abs() < 1e-3);
+        world.resource_mut::<Time>().update_with_instant(start + step * 2);
+        stage.run(&mut world);
+        assert_eq!(particles(&mut world), 0);
+    }
+}
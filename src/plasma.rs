@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use rand::Rng;
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+use crate::console::VulnerableShipQuery;
+use crate::particles::{spawn_effect, ParticleEmitter, DEBRIS_SPARKS, EXPLOSION, PLASMA_TRAIL};
+use crate::sfx::SoundEffect;
+use crate::theme::Theme;
+use crate::vector::{irregular_circle_points, VectorPath, VectorShape, LAYER_SHAPES};
+use crate::sim::SIM_TICK_RATE;
+use crate::{in_position_order, Bullet, Game, Lifetime, Mass, Planet, Radius, Velocity};
+
+pub const PLANET_EJECT_HITS: u32 = 6;         // bullets into the star before it throws off a blob
+pub const PLANET_EJECT_MASS: f32 = 40.0;
+pub const PLANET_EJECT_RADIUS: f32 = 2.0;
+const PLANET_COOL_TICKS: u64 = SIM_TICK_RATE as u64 / 2; // the star forgets a hit this often, so only sustained fire makes it erupt
+const PLASMA_RADIUS: f32 = 4.0;
+const PLASMA_MASS: f32 = 8.0;                 // falling back in returns about what was thrown off
+const PLASMA_LIFETIME_MS: u64 = 8000;
+const PLASMA_SPREAD: f32 = 0.4;               // radians either side of the impact direction
+const PLASMA_SPEED_MIN: f32 = 0.7;            // launch speed as a fraction of escape speed, some blobs fall back and some get away
+const PLASMA_SPEED_MAX: f32 = 1.2;
+const PLASMA_POINT_COUNT: u32 = 8;
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Plasma { seed: u64 }
+
+pub fn escape_speed(gravity: f32, planet_mass: f32, mass: f32, distance: f32) -> f32 {
+    (2.0 * gravity * planet_mass * mass / distance.max(1.0)).sqrt()
+}
+
+// throws a blob off the star's surface in roughly the direction the shots came from
+pub fn spawn_plasma(commands: &mut Commands, rng: &mut Pcg32, gravity: f32, planet_position: Vec3, planet_radius: f32, planet_mass: f32, direction: Vec2) {
+    let angle = direction.y.atan2(direction.x) + rng.gen_range(-PLASMA_SPREAD..PLASMA_SPREAD);
+    let direction = Vec2::new(angle.cos(), angle.sin());
+    let distance = planet_radius + PLASMA_RADIUS + 1.0;
+    let speed = escape_speed(gravity, planet_mass, PLASMA_MASS, distance) * rng.gen_range(PLASMA_SPEED_MIN..PLASMA_SPEED_MAX);
+    commands.spawn((Plasma { seed: rng.gen() },
+                    Radius(PLASMA_RADIUS),
+                    Mass(PLASMA_MASS),
+                    Transform::from_translation(planet_position + (direction * distance).extend(0.0)),
+                    Velocity(direction * speed),
+                    Lifetime(Duration::from_millis(PLASMA_LIFETIME_MS)),
+                    ParticleEmitter::new(&PLASMA_TRAIL, None)));
+}
+
+pub fn plasma_shape(mut commands: Commands, query: Query<(Entity, &Radius, &Plasma), Changed<Radius>>, theme: Res<Theme>) {
+    for (entity, Radius(radius), plasma) in &query {
+        let points = irregular_circle_points(plasma.seed, radius * 0.7, radius * 1.3, PLASMA_POINT_COUNT);
+        commands.entity(entity).insert(VectorShape::new(VectorPath::polygon(points, theme.plasma), LAYER_SHAPES));
+    }
+}
+
+pub fn cool_planet(mut planets: Query<&mut Planet>, game: Res<Game>) {
+    if !game.tick.is_multiple_of(PLANET_COOL_TICKS) {
+        return;
+    }
+    for mut planet in &mut planets {
+        if planet.heat > 0 {
+            planet.heat -= 1;
+        }
+    }
+}
+
+// blobs burn through ships and can be shot apart
+pub fn plasma_collision(mut commands: Commands, plasma_query: Query<(Entity, &Radius, &Transform, &Velocity), With<Plasma>>, ship_query: VulnerableShipQuery, bullet_query: Query<(Entity, &Radius, &Transform), With<Bullet>>, mut sounds: EventWriter<SoundEffect>) {
+    let ships = in_position_order(ship_query.iter(), |item| item.2);
+    let bullets = in_position_order(bullet_query.iter(), |item| item.2);
+    for (plasma_entity, Radius(plasma_radius), plasma_transform, plasma_velocity) in in_position_order(plasma_query.iter(), |item| item.2) {
+        let mut destroyed = false;
+        for &(ship_entity, Radius(ship_radius), ship_transform, ship_velocity) in &ships {
+            if Vec3::distance(plasma_transform.translation, ship_transform.translation) < plasma_radius + ship_radius {
+                commands.entity(ship_entity).despawn();
+                spawn_effect(&mut commands, &EXPLOSION, ship_transform.translation, **ship_velocity);
+                sounds.send(SoundEffect::Explosion { radius: *ship_radius });
+                destroyed = true;
+                break;
+            }
+        }
+
+        if !destroyed {
+            for &(bullet_entity, Radius(bullet_radius), bullet_transform) in &bullets {
+                if Vec3::distance(plasma_transform.translation, bullet_transform.translation) < plasma_radius + bullet_radius {
+                    commands.entity(bullet_entity).despawn();
+                    destroyed = true;
+                    break;
+                }
+            }
+        }
+
+        if destroyed {
+            commands.entity(plasma_entity).despawn();
+            spawn_effect(&mut commands, &DEBRIS_SPARKS, plasma_transform.translation, **plasma_velocity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{run_ticks, simulation_world};
+
+    fn heat(world: &mut World) -> u32 {
+        world.query::<&Planet>().single(world).heat
+    }
+
+    #[test]
+    fn the_star_cools_off_between_bursts() {
+        let mut world = simulation_world(1, 1);
+        world.query::<&mut Planet>().single_mut(&mut world).heat = PLANET_EJECT_HITS - 1;
+        run_ticks(&mut world, PLANET_COOL_TICKS as u32);
+        assert_eq!(heat(&mut world), PLANET_EJECT_HITS - 2);
+        run_ticks(&mut world, PLANET_COOL_TICKS as u32 * PLANET_EJECT_HITS);
+        assert_eq!(heat(&mut world), 0);
+    }
+}
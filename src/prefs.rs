@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::sim::SIM_ARENA_HALF_SIZE;
+use crate::store;
+use crate::theme::Theme;
+use crate::FONT_PATH;
+
+pub const PREFERENCES_PATH: &str = "preferences.json";
+const PREFERENCES_VERSION: u32 = 1;
+
+const OPTION_TEXT_MS: u64 = 1500;
+const OPTION_TEXT_SIZE: f32 = 20.0;
+
+// settings the player picks once and expects to find again next time
+#[derive(Resource, Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Preferences {
+    pub language: Option<String>,
+    pub theme: Option<String>,
+    pub ghost: bool
+}
+
+impl Preferences {
+    pub fn load(path: &str) -> Self {
+        store::load(path, PREFERENCES_VERSION)
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        store::save(path, PREFERENCES_VERSION, self)
+    }
+}
+
+// sent with a short message whenever a view or sound option is toggled
+pub struct OptionChanged(pub String);
+
+// counts down on the wall clock, so the hint still goes away while the game is over or a replay is paused
+#[derive(Component)]
+pub struct OptionText(Timer);
+
+// briefly shows the last option that changed in the top right corner
+pub fn show_option_text(mut commands: Commands, mut events: EventReader<OptionChanged>, asset_server: Res<AssetServer>, text_query: Query<Entity, With<OptionText>>, theme: Res<Theme>) {
+    let Some(OptionChanged(message)) = events.iter().last() else {
+        return;
+    };
+
+    for entity in &text_query {
+        commands.entity(entity).despawn();
+    }
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: OPTION_TEXT_SIZE,
+        color: theme.hint,
+    };
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section(message.clone(), text_style)
+                .with_alignment(TextAlignment::TOP_RIGHT),
+            transform: Transform::from_xyz(SIM_ARENA_HALF_SIZE.x - 20.0, SIM_ARENA_HALF_SIZE.y - 10.0, 1.0),
+            ..default()
+        },
+        OptionText(Timer::new(Duration::from_millis(OPTION_TEXT_MS), TimerMode::Once)))
+    );
+}
+
+pub fn expire_option_text(mut commands: Commands, mut text_query: Query<(Entity, &mut OptionText)>, time: Res<Time>) {
+    for (entity, mut option_text) in &mut text_query {
+        if option_text.0.tick(time.raw_delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::Instant;
+
+    use super::*;
+
+    #[test]
+    fn preferences_come_back_as_they_were_saved() {
+        let path = std::env::temp_dir().join(format!("rusteroids-prefs-{}.json", std::process::id())).to_string_lossy().into_owned();
+        let _ = std::fs::remove_file(&path);
+        assert!(Preferences::load(&path).theme.is_none());
+
+        let preferences = Preferences { language: Some("de".to_string()), theme: Some("monochrome".to_string()), ghost: true };
+        preferences.save(&path).unwrap();
+        let loaded = Preferences::load(&path);
+        assert_eq!(loaded.language.as_deref(), Some("de"));
+        assert_eq!(Theme::from_name(loaded.theme.as_deref().unwrap()).unwrap().name, "monochrome");
+        assert!(loaded.ghost);
+
+        // settings added since the file was written start at their defaults
+        std::fs::write(&path, "{\"version\":1,\"data\":{\"language\":\"en\"}}").unwrap();
+        let loaded = Preferences::load(&path);
+        assert_eq!(loaded.language.as_deref(), Some("en"));
+        assert!(loaded.theme.is_none() && !loaded.ghost);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn the_option_hint_goes_away_while_the_game_clock_is_stopped() {
+        let mut world = World::new();
+        let start = Instant::now();
+        let mut time = Time::new(start);
+        time.update_with_instant(start);
+        time.set_relative_speed(0.0);
+        world.insert_resource(time);
+        let hint = world.spawn(OptionText(Timer::new(Duration::from_millis(OPTION_TEXT_MS), TimerMode::Once))).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(expire_option_text);
+
+        world.resource_mut::<Time>().update_with_instant(start + Duration::from_millis(OPTION_TEXT_MS / 2));
+        stage.run(&mut world);
+        assert!(world.get_entity(hint).is_some());
+        world.resource_mut::<Time>().update_with_instant(start + Duration::from_millis(OPTION_TEXT_MS + 100));
+        stage.run(&mut world);
+        assert!(world.get_entity(hint).is_none());
+    }
+}
@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+
+use crate::camera::CameraRig;
+use crate::enemy::Enemy;
+use crate::locale::Strings;
+use crate::plasma::Plasma;
+use crate::prefs::OptionChanged;
+use crate::sim::SIM_ARENA_HALF_SIZE;
+use crate::theme::Theme;
+use crate::tween::lerp_color;
+use crate::vector::{VectorPath, VectorLines, LAYER_EFFECTS, LAYER_SHAPES};
+use crate::{Asteroid, Planet, Radius, Ship, Velocity};
+
+const INDICATOR_MARGIN: f32 = 16.0;           // distance from the window edge to the arrow tips
+const INDICATOR_SIZE: f32 = 6.0;
+const INDICATOR_RADIUS_SCALE: f32 = 0.4;      // bigger bodies get bigger arrows
+const INDICATOR_THREAT_SPEED: f32 = 120.0;    // closing speed that counts as a full threat
+const INDICATOR_FADE_DISTANCE: f32 = 800.0;   // arrows fade out for bodies this far beyond the edge
+
+const RADAR_SIZE: f32 = 70.0;                 // radius on screen
+const RADAR_RANGE: f32 = 1200.0;              // radius in the world, wide enough to catch asteroids as they spawn
+const RADAR_MARGIN: f32 = 20.0;
+const RADAR_BLIP_SIZE: f32 = 1.5;
+
+#[derive(Resource, Default)]
+pub struct Radar {
+    pub visible: bool
+}
+
+type IncomingQuery<'w, 's> = Query<'w, 's, (&'static Transform, &'static Radius, &'static Velocity, Option<&'static Enemy>), Or<(With<Asteroid>, With<Enemy>, With<Plasma>)>>;
+
+// 0 for bodies drifting away from whatever they are heading for, 1 for enemies and anything closing fast
+fn threat(position: Vec2, velocity: Vec2, targets: &[Vec2], enemy: bool) -> f32 {
+    if enemy {
+        return 1.0;
+    }
+    let Some(target) = targets.iter().min_by(|a, b| a.distance_squared(position).total_cmp(&b.distance_squared(position))) else {
+        return 0.0;
+    };
+    let closing = velocity.dot((*target - position).normalize_or_zero());
+    (closing / INDICATOR_THREAT_SPEED).clamp(0.0, 1.0)
+}
+
+// where a body outside the view points from, on the edge of the view
+fn indicator_tip(screen: Vec2, edge: Vec2) -> Vec2 {
+    screen * f32::min(edge.x / screen.x.abs().max(f32::EPSILON), edge.y / screen.y.abs().max(f32::EPSILON))
+}
+
+// where a body shows up on the radar, if it is in range
+fn radar_point(position: Vec2, world_centre: Vec2, radar_centre: Vec2) -> Option<Vec2> {
+    let offset = (position - world_centre) * RADAR_SIZE / RADAR_RANGE;
+    (offset.length() <= RADAR_SIZE).then_some(radar_centre + offset)
+}
+
+// bodies threaten the ships, or the planet once there are none left
+fn threat_targets(ship_query: &Query<(&Transform, &Ship)>, planet_query: &Query<(&Transform, &Radius), With<Planet>>) -> Vec<Vec2> {
+    let ships: Vec<Vec2> = ship_query.iter().map(|(transform, _)| transform.translation.truncate()).collect();
+    if ships.is_empty() {
+        planet_query.iter().map(|(transform, _)| transform.translation.truncate()).collect()
+    } else {
+        ships
+    }
+}
+
+// an arrow at the edge of the window for every body outside the view, pointing at where it is
+pub fn draw_indicators(query: IncomingQuery, ship_query: Query<(&Transform, &Ship)>, planet_query: Query<(&Transform, &Radius), With<Planet>>, rig_query: Query<&CameraRig>, mut lines: ResMut<VectorLines>, theme: Res<Theme>) {
+    let (camera_position, camera_zoom) = rig_query.get_single().map_or((Vec2::ZERO, 1.0), |rig| (rig.position, rig.zoom));
+    let half_size = SIM_ARENA_HALF_SIZE;
+    let edge = half_size - Vec2::splat(INDICATOR_MARGIN);
+    let targets = threat_targets(&ship_query, &planet_query);
+
+    for (transform, Radius(radius), velocity, enemy) in &query {
+        let position = transform.translation.truncate();
+        let screen = (position - camera_position) / camera_zoom;
+        if screen.x.abs() < half_size.x + radius / camera_zoom && screen.y.abs() < half_size.y + radius / camera_zoom {
+            continue;
+        }
+
+        let tip = indicator_tip(screen, edge);
+        let direction = screen.normalize_or_zero();
+        let size = INDICATOR_SIZE + radius * INDICATOR_RADIUS_SCALE;
+        let back = tip - direction * size;
+        let side = direction.perp() * size / 2.0;
+
+        let mut color = lerp_color(theme.safe, theme.danger, threat(position, **velocity, &targets, enemy.is_some()));
+        let beyond = (screen - tip).length() * camera_zoom;
+        color.set_a(color.a() * (1.0 - beyond / INDICATOR_FADE_DISTANCE).clamp(0.2, 1.0));
+        lines.overlay(VectorPath::polygon([tip, back + side, back - side], color), LAYER_SHAPES);
+    }
+}
+
+pub fn toggle_radar(keyboard_input: Res<Input<KeyCode>>, mut radar: ResMut<Radar>, mut options: EventWriter<OptionChanged>, strings: Res<Strings>) {
+    if keyboard_input.just_pressed(KeyCode::R) {
+        radar.visible = !radar.visible;
+        options.send(OptionChanged(strings.format("option.radar", &[&strings.on_off(radar.visible)])));
+    }
+}
+
+// a round radar in the bottom right corner centred on the ships, showing everything within range
+pub fn draw_radar(radar: Res<Radar>, query: IncomingQuery, ship_query: Query<(&Transform, &Ship)>, planet_query: Query<(&Transform, &Radius), With<Planet>>, rig_query: Query<&CameraRig>, mut lines: ResMut<VectorLines>, theme: Res<Theme>) {
+    if !radar.visible {
+        return;
+    }
+    let half_size = SIM_ARENA_HALF_SIZE;
+    let radar_centre = Vec2::new(half_size.x - RADAR_SIZE - RADAR_MARGIN, -half_size.y + RADAR_SIZE + RADAR_MARGIN);
+    let scale = RADAR_SIZE / RADAR_RANGE;
+    let targets = threat_targets(&ship_query, &planet_query);
+    let world_centre = if ship_query.is_empty() { Vec2::ZERO } else { targets.iter().sum::<Vec2>() / targets.len() as f32 };
+    let to_radar = |position: Vec2| radar_point(position, world_centre, radar_centre);
+
+    lines.overlay(VectorPath::circle(radar_centre, RADAR_SIZE, 48, theme.frame), LAYER_EFFECTS);
+    if let Ok(rig) = rig_query.get_single() {
+        let view = half_size * rig.zoom * scale;
+        let view_centre = radar_centre + (rig.position - world_centre) * scale;
+        let corners = [Vec2::new(-1.0, -1.0), Vec2::new(1.0, -1.0), Vec2::new(1.0, 1.0), Vec2::new(-1.0, 1.0)];
+        lines.overlay(VectorPath::polygon(corners.iter().map(|corner| view_centre + *corner * view), theme.frame_faint), LAYER_EFFECTS);
+    }
+
+    for (transform, Radius(radius)) in &planet_query {
+        if let Some(point) = to_radar(transform.translation.truncate()) {
+            lines.overlay(VectorPath::circle(point, (radius * scale).max(RADAR_BLIP_SIZE * 1.5), 12, theme.mass), LAYER_SHAPES);
+        }
+    }
+    for (transform, Radius(radius), velocity, enemy) in &query {
+        let position = transform.translation.truncate();
+        if let Some(point) = to_radar(position) {
+            let color = lerp_color(theme.safe, theme.danger, threat(position, **velocity, &targets, enemy.is_some()));
+            lines.overlay(VectorPath::circle(point, (radius * scale).max(RADAR_BLIP_SIZE), 6, color), LAYER_SHAPES);
+        }
+    }
+    for (transform, ship) in &ship_query {
+        if let Some(point) = to_radar(transform.translation.truncate()) {
+            let heading = transform.rotation * Vec3::Y;
+            let forward = heading.truncate() * RADAR_BLIP_SIZE * 2.5;
+            let side = forward.perp() * 0.5;
+            lines.overlay(VectorPath::polygon([point + forward, point - forward + side, point - forward - side], theme.players[ship.player]), LAYER_SHAPES);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enemies_and_closing_bodies_are_threats() {
+        let targets = [Vec2::new(-500.0, 0.0), Vec2::new(100.0, 0.0)];
+        assert_eq!(threat(Vec2::ZERO, Vec2::new(INDICATOR_THREAT_SPEED * 2.0, 0.0), &targets, false), 1.0);
+        assert_eq!(threat(Vec2::ZERO, Vec2::new(-INDICATOR_THREAT_SPEED, 0.0), &targets, false), 0.0);
+        assert_eq!(threat(Vec2::ZERO, Vec2::new(INDICATOR_THREAT_SPEED / 2.0, 0.0), &targets, false), 0.5);
+        assert_eq!(threat(Vec2::ZERO, Vec2::ZERO, &targets, true), 1.0);
+        assert_eq!(threat(Vec2::ZERO, Vec2::X, &[], false), 0.0);
+    }
+
+    #[test]
+    fn indicators_sit_on_the_edge_towards_the_body() {
+        let edge = Vec2::new(400.0, 300.0);
+        assert_eq!(indicator_tip(Vec2::new(800.0, 0.0), edge), Vec2::new(400.0, 0.0));
+        assert_eq!(indicator_tip(Vec2::new(0.0, -900.0), edge), Vec2::new(0.0, -300.0));
+        assert_eq!(indicator_tip(Vec2::new(800.0, 1200.0), edge), Vec2::new(200.0, 300.0));
+    }
+
+    #[test]
+    fn the_radar_scales_the_world_around_its_centre_and_drops_what_is_out_of_range() {
+        let radar_centre = Vec2::new(300.0, -200.0);
+        let world_centre = Vec2::new(50.0, 50.0);
+        assert_eq!(radar_point(world_centre, world_centre, radar_centre), Some(radar_centre));
+        assert_eq!(radar_point(world_centre + Vec2::new(RADAR_RANGE, 0.0), world_centre, radar_centre), Some(radar_centre + Vec2::new(RADAR_SIZE, 0.0)));
+        assert_eq!(radar_point(world_centre + Vec2::new(0.0, -RADAR_RANGE / 2.0), world_centre, radar_centre), Some(radar_centre + Vec2::new(0.0, -RADAR_SIZE / 2.0)));
+        assert_eq!(radar_point(world_centre + Vec2::new(RADAR_RANGE, RADAR_RANGE) * 0.8, world_centre, radar_centre), None);
+    }
+}
@@ -0,0 +1,435 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::CameraRig;
+use crate::daily::{Challenge, Daily};
+use crate::locale::Strings;
+use crate::save::{capture_world, restore_world, RunState, SavedRun};
+use crate::sim::{set_ship_inputs, Simulation, SIM_ARENA_HALF_SIZE, SIM_MAX_FRAME_TICKS, SIM_TICK_RATE};
+use crate::stats::format_time;
+use crate::theme::Theme;
+use crate::{tick_ms, GameState, Ship, ShipInput, FONT_PATH, MAX_PLAYERS};
+
+pub const REPLAY_PATH: &str = "replay.json";
+const REPLAY_VERSION: u32 = 3;                // bump whenever anything recorded here changes shape
+const REPLAY_KEYFRAME_TICKS: u32 = 2 * SIM_TICK_RATE; // seeking lands on these
+const REPLAY_SEEK_GRACE_TICKS: u32 = SIM_TICK_RATE / 2; // seeking back this soon after a keyframe goes to the one before
+const REPLAY_SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const REPLAY_NORMAL_SPEED: usize = 2;
+const REPLAY_PANEL_SIZE: f32 = 14.0;
+const REPLAY_PANEL_TOP: f32 = 40.0;           // clear of the option text
+
+const FREE_CAMERA_SPEED: f32 = 600.0;         // world units per second at zoom 1
+const FREE_CAMERA_ZOOM_RATE: f32 = 1.5;
+const FREE_CAMERA_MIN_ZOOM: f32 = 0.25;
+const FREE_CAMERA_MAX_ZOOM: f32 = 4.0;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct InputChange {
+    tick: u32,
+    player: usize,
+    bits: u8
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Keyframe {
+    tick: u32,
+    run: SavedRun
+}
+
+// a run kept as every change to the players' inputs tick by tick plus a snapshot of the whole game every couple of seconds
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Replay {
+    version: u32,
+    ticks: u32,
+    inputs: Vec<InputChange>,
+    keyframes: Vec<Keyframe>,
+    #[serde(default)]
+    challenge: Option<Challenge>      // a daily run plays back under that day's rules
+}
+
+impl Replay {
+    fn new(challenge: Option<Challenge>) -> Self {
+        Self { version: REPLAY_VERSION, ticks: 0, inputs: Vec::new(), keyframes: Vec::new(), challenge }
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let replay: Replay = serde_json::from_str(&fs::read_to_string(path)?)?;
+        replay.check()?;
+        Ok(replay)
+    }
+
+    pub fn check(&self) -> io::Result<()> {
+        if self.version != REPLAY_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("replay version {} is not {}", self.version, REPLAY_VERSION)));
+        }
+        if self.keyframes.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "replay has no keyframes"));
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    pub fn start(&self) -> &SavedRun {
+        &self.keyframes[0].run
+    }
+
+    // what each player held on a tick, the last change at or before it
+    fn inputs_at(&self, tick: u32) -> [u8; MAX_PLAYERS] {
+        let end = self.inputs.partition_point(|change| change.tick <= tick);
+        let players = self.start().game.players;
+        let mut inputs = [0; MAX_PLAYERS];
+        let mut found = [false; MAX_PLAYERS];
+        for change in self.inputs[..end].iter().rev() {
+            if found[..players].iter().all(|found| *found) {
+                break;
+            }
+            if !found[change.player] {
+                found[change.player] = true;
+                inputs[change.player] = change.bits;
+            }
+        }
+        inputs
+    }
+}
+
+pub fn replay_exists() -> bool {
+    Path::new(REPLAY_PATH).exists()
+}
+
+// every run is recorded, the latest one is kept to watch back from the title screen
+#[derive(Resource)]
+pub struct Recording {
+    replay: Replay,
+    inputs: [u8; MAX_PLAYERS]
+}
+
+pub fn start_recording(mut commands: Commands, daily: Res<Daily>) {
+    commands.insert_resource(Recording { replay: Replay::new(daily.challenge.clone()), inputs: [0; MAX_PLAYERS] });
+}
+
+// runs before every tick of a recorded run, so a keyframe is the state that tick's inputs were applied to
+pub fn record_tick(world: &mut World) {
+    let Some(mut recording) = world.remove_resource::<Recording>() else {
+        return;
+    };
+    let tick = recording.replay.ticks;
+    if tick.is_multiple_of(REPLAY_KEYFRAME_TICKS) {
+        recording.replay.keyframes.push(Keyframe { tick, run: capture_world(world) });
+    }
+    let mut query = world.query::<(&Ship, &ShipInput)>();
+    for (ship, input) in query.iter(world) {
+        let bits = input.to_bits();
+        if bits != recording.inputs[ship.player] {
+            recording.inputs[ship.player] = bits;
+            recording.replay.inputs.push(InputChange { tick, player: ship.player, bits });
+        }
+    }
+    recording.replay.ticks += 1;
+    world.insert_resource(recording);
+}
+
+pub fn finish_recording(mut commands: Commands, recording: Option<Res<Recording>>) {
+    let Some(recording) = recording else {
+        return;
+    };
+    if let Err(err) = recording.replay.save(REPLAY_PATH) {
+        warn!("could not save the replay: {}", err);
+    }
+    commands.remove_resource::<Recording>();
+}
+
+#[derive(Resource)]
+pub struct Playback {
+    replay: Replay,
+    tick: u32,                    // the next tick to play
+    seek: Option<usize>,          // keyframe to jump to at the start of the next frame
+    speed: usize,                 // index into REPLAY_SPEEDS
+    paused: bool,
+    step: bool,                   // play a single tick, then pause again
+    outer_speed: f32              // the clock's relative speed before playback took it over, put back on the way out
+}
+
+impl Playback {
+    fn new(replay: Replay) -> Self {
+        Self { replay, tick: 0, seek: None, speed: REPLAY_NORMAL_SPEED, paused: false, step: false, outer_speed: 1.0 }
+    }
+
+    // any replay file can be watched, the latest run's or one kept under its own name like an imported daily
+    pub fn load(path: &str) -> io::Result<Self> {
+        Replay::load(path).map(Self::new)
+    }
+
+    fn finished(&self) -> bool {
+        self.tick >= self.replay.ticks
+    }
+
+    // fast playback needs more ticks a frame than the game would ever catch up by, so the cap grows with the speed
+    fn frame_ticks(&self) -> u32 {
+        SIM_MAX_FRAME_TICKS * REPLAY_SPEEDS[self.speed].max(1.0) as u32
+    }
+}
+
+pub fn watch_replay(mut commands: Commands, mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>) {
+    if !keyboard_input.just_pressed(KeyCode::V) || !replay_exists() {
+        return;
+    }
+    match Playback::load(REPLAY_PATH) {
+        Ok(playback) => {
+            if state.set(GameState::Replay).is_ok() {
+                commands.insert_resource(playback);
+            }
+        }
+        Err(err) => warn!("could not load {}: {}", REPLAY_PATH, err),
+    }
+}
+
+// the first keyframe brings the run's rules with it, a daily one included
+pub fn start_replay(mut commands: Commands, mut playback: ResMut<Playback>, mut run: RunState, time: Res<Time>, theme: Res<Theme>) {
+    playback.outer_speed = time.relative_speed();
+    let keyframe = &playback.replay.keyframes[0];
+    run.restore(&mut commands, &keyframe.run, &theme);
+    let tick = keyframe.tick;
+    playback.tick = tick;
+}
+
+// puts the game back to a keyframe when the viewer seeks, then plays the recorded inputs tick by tick
+pub fn run_replay(world: &mut World) {
+    let delta = world.resource::<Time>().delta();
+    world.resource_scope(|world, mut playback: Mut<Playback>| {
+        if let Some(index) = playback.seek.take() {
+            let keyframe = &playback.replay.keyframes[index];
+            restore_world(world, &keyframe.run);
+            let tick = keyframe.tick;
+            playback.tick = tick;
+        }
+
+        world.resource_scope(|world, mut simulation: Mut<Simulation>| {
+            let ticks = if playback.step { 1 } else { simulation.due_within(delta, playback.frame_ticks()) };
+            for _tick in 0..ticks {
+                if playback.finished() {
+                    break;
+                }
+                set_ship_inputs(world, &playback.replay.inputs_at(playback.tick));
+                simulation.tick(world);
+                playback.tick += 1;
+            }
+        });
+
+        playback.step = false;
+        if playback.finished() {
+            playback.paused = true;
+        }
+    });
+}
+
+pub fn replay_controls(mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>, mut playback: ResMut<Playback>, mut time: ResMut<Time>) {
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        if let Err(err) = state.set(GameState::Title) {
+            warn!("could not leave the replay: {:?}", err);
+        }
+        return;
+    }
+
+    let keyframes = &playback.replay.keyframes;
+    let current = keyframes.partition_point(|keyframe| keyframe.tick <= playback.tick);
+    let seek = if keyboard_input.just_pressed(KeyCode::Home) || (keyboard_input.just_pressed(KeyCode::Space) && playback.finished()) {
+        Some(0)
+    } else if keyboard_input.just_pressed(KeyCode::Left) {
+        Some(keyframes.partition_point(|keyframe| keyframe.tick + REPLAY_SEEK_GRACE_TICKS < playback.tick).saturating_sub(1))
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        (current < keyframes.len()).then_some(current)
+    } else {
+        None
+    };
+
+    if let Some(index) = seek {
+        playback.seek = Some(index);
+        playback.paused = playback.paused && index > 0;
+    } else if keyboard_input.just_pressed(KeyCode::Space) {
+        playback.paused = !playback.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::Period) && !playback.finished() {
+        playback.paused = true;
+        playback.step = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        playback.speed = (playback.speed + 1).min(REPLAY_SPEEDS.len() - 1);
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        playback.speed = playback.speed.saturating_sub(1);
+    }
+
+    let running = !playback.paused || playback.step;
+    time.set_relative_speed(if running { REPLAY_SPEEDS[playback.speed] } else { 0.0 });
+}
+
+// WASD pans and Q/E zooms the camera, which keeps running while playback is paused; C hands it back to the follow logic
+pub fn free_camera(keyboard_input: Res<Input<KeyCode>>, mut rig_query: Query<&mut CameraRig>, time: Res<Time>) {
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+    let directions = [(KeyCode::W, Vec2::Y), (KeyCode::S, Vec2::NEG_Y), (KeyCode::A, Vec2::NEG_X), (KeyCode::D, Vec2::X)];
+    let pan: Vec2 = directions.iter()
+        .filter(|(key, _)| keyboard_input.pressed(*key))
+        .map(|(_, direction)| *direction)
+        .sum();
+    let zoom = keyboard_input.pressed(KeyCode::E) as i32 - keyboard_input.pressed(KeyCode::Q) as i32;
+
+    if pan != Vec2::ZERO || zoom != 0 {
+        rig.free = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::C) {
+        rig.free = false;
+    }
+    let delta_seconds = time.raw_delta_seconds();
+    let step = pan * FREE_CAMERA_SPEED * rig.zoom * delta_seconds;
+    rig.position += step;
+    rig.zoom = (rig.zoom * (FREE_CAMERA_ZOOM_RATE * zoom as f32 * delta_seconds).exp()).clamp(FREE_CAMERA_MIN_ZOOM, FREE_CAMERA_MAX_ZOOM);
+}
+
+pub fn stop_replay(mut commands: Commands, playback: Res<Playback>, mut rig_query: Query<&mut CameraRig>, mut time: ResMut<Time>) {
+    commands.remove_resource::<Playback>();
+    time.set_relative_speed(playback.outer_speed);
+    for mut rig in &mut rig_query {
+        rig.free = false;
+    }
+}
+
+#[derive(Component)]
+pub struct ReplayPanel;
+
+fn describe_input(bits: u8, strings: &Strings) -> String {
+    let input = ShipInput::from_bits(bits);
+    let held = [(input.left, "replay.left"), (input.right, "replay.right"), (input.thrust, "replay.thrust"), (input.fire, "replay.fire"), (input.teleport, "replay.teleport")];
+    held.iter()
+        .filter(|(pressed, _)| *pressed)
+        .map(|(_, key)| strings.get(key))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// playback position and speed, and what every player was pressing at this point of the run
+pub fn update_replay_panel(mut commands: Commands, playback: Res<Playback>, mut panel_query: Query<&mut Text, With<ReplayPanel>>, asset_server: Res<AssetServer>, strings: Res<Strings>, theme: Res<Theme>) {
+    let mut status = strings.format("replay.status", &[&format_time(tick_ms(playback.tick as u64)), &format_time(tick_ms(playback.replay.ticks as u64)), &REPLAY_SPEEDS[playback.speed]]);
+    if playback.paused {
+        status += "   ";
+        status += strings.get("replay.paused");
+    }
+    let mut lines = vec![status];
+    let inputs = playback.replay.inputs_at(playback.tick);
+    for (player, bits) in inputs.iter().enumerate().take(playback.replay.start().game.players) {
+        lines.push(strings.format("replay.input", &[&(player + 1), &describe_input(*bits, &strings)]));
+    }
+    lines.push(strings.get("replay.controls").to_string());
+    let report = lines.join("\n");
+
+    if let Ok(mut text) = panel_query.get_single_mut() {
+        text.sections[0].value = report;
+        return;
+    }
+    let half_size = SIM_ARENA_HALF_SIZE;
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: REPLAY_PANEL_SIZE,
+        color: theme.hint,
+    };
+    commands.spawn(
+        (Text2dBundle {
+            text: Text::from_section(report, text_style)
+                .with_alignment(TextAlignment::TOP_RIGHT),
+            transform: Transform::from_xyz(half_size.x - 20.0, half_size.y - REPLAY_PANEL_TOP, 1.0),
+            ..default()
+        },
+        ReplayPanel)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg32;
+
+    use bevy::utils::Duration;
+
+    use super::*;
+    use crate::sim::{run_ticks, simulation_world, INPUT_FIRE, INPUT_LEFT, INPUT_RIGHT, INPUT_TELEPORT, INPUT_THRUST};
+
+    fn record_run(seed: u64, ticks: u32) -> (Replay, SavedRun) {
+        let mut world = simulation_world(seed, 2);
+        world.insert_resource(Recording { replay: Replay::new(None), inputs: [0; MAX_PLAYERS] });
+        let mut rng = Pcg32::seed_from_u64(seed);
+        let mut inputs = [0; MAX_PLAYERS];
+        for tick in 0..ticks {
+            if tick % 20 == 0 {
+                for bits in &mut inputs[..2] {
+                    *bits = rng.gen::<u8>() & (INPUT_LEFT | INPUT_RIGHT | INPUT_THRUST | INPUT_FIRE | INPUT_TELEPORT);
+                }
+            }
+            set_ship_inputs(&mut world, &inputs);
+            record_tick(&mut world);
+            run_ticks(&mut world, 1);
+        }
+        let end = capture_world(&mut world);
+        (world.remove_resource::<Recording>().unwrap().replay, end)
+    }
+
+    fn play_from(replay: &Replay, keyframe: usize) -> SavedRun {
+        let mut world = simulation_world(0, 2);
+        restore_world(&mut world, &replay.keyframes[keyframe].run);
+        for tick in replay.keyframes[keyframe].tick..replay.ticks {
+            set_ship_inputs(&mut world, &replay.inputs_at(tick));
+            run_ticks(&mut world, 1);
+        }
+        capture_world(&mut world)
+    }
+
+    fn same_run(a: &SavedRun, b: &SavedRun) -> bool {
+        serde_json::to_string(a).unwrap() == serde_json::to_string(b).unwrap()
+    }
+
+    #[test]
+    fn playback_ends_where_the_recorded_run_did() {
+        let (replay, end) = record_run(7, 1800);
+        assert_eq!(replay.ticks, 1800);
+        assert!(same_run(&play_from(&replay, 0), &end));
+    }
+
+    #[test]
+    fn playback_keeps_up_at_every_speed_on_slow_frames() {
+        let (replay, _) = record_run(3, 60);
+        let mut playback = Playback::new(replay);
+        let frame = Duration::from_secs_f32(1.0 / 20.0);
+        for (speed, factor) in REPLAY_SPEEDS.iter().enumerate() {
+            playback.speed = speed;
+            let mut simulation = Simulation::default();
+            let ticks: u32 = (0..20).map(|_| simulation.due_within(frame.mul_f32(*factor), playback.frame_ticks())).sum();
+            assert!(ticks as f32 >= (SIM_TICK_RATE as f32 * factor).floor() - 1.0, "{} ticks in a second at {}x", ticks, factor);
+        }
+    }
+
+    #[test]
+    fn a_replay_kept_under_its_own_name_can_be_watched() {
+        let (replay, end) = record_run(11, 300);
+        let path = std::env::temp_dir().join(format!("rusteroids-daily-{}.replay.json", std::process::id())).to_string_lossy().into_owned();
+        replay.save(&path).unwrap();
+        let playback = Playback::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(playback.replay.ticks, 300);
+        assert!(same_run(&play_from(&playback.replay, 0), &end));
+    }
+
+    #[test]
+    fn playback_from_a_later_keyframe_ends_the_same() {
+        let (replay, end) = record_run(21, 1200);
+        assert_eq!(replay.keyframes.len(), 10);
+        assert!(same_run(&play_from(&replay, 4), &end));
+    }
+}
@@ -0,0 +1,367 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy::ecs::system::{SystemParam, SystemState};
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use bevy::window::WindowCloseRequested;
+use serde::{Deserialize, Serialize};
+
+use crate::blackhole::BlackHole;
+use crate::daily::{Challenge, Daily};
+use crate::enemy::{Enemy, EnemyTimer};
+use crate::particles::{ParticleEmitter, PLASMA_TRAIL, SHIP_TRAIL};
+use crate::plasma::Plasma;
+use crate::stats::Stats;
+use crate::theme::Theme;
+use crate::tween::Tween;
+use crate::*;
+
+pub const SAVE_PATH: &str = "savegame.json";
+const SAVE_VERSION: u32 = 2;                  // bump whenever anything saved here changes shape
+const CHECKSUM_OFFSET: u64 = 0xcbf29ce484222325; // 64 bit FNV-1a
+const CHECKSUM_PRIME: u64 = 0x100000001b3;
+
+// everything needed to carry on a run exactly where it was left, particles aside
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedRun {
+    pub game: Game,
+    stats: Stats,
+    asteroid_timer: AsteroidTimer,
+    enemy_timer: EnemyTimer,
+    rng: GameRng,
+    tuning: Tuning,
+    challenge: Option<Challenge>,       // a daily run carries on under that day's rules and still goes on the board
+    bodies: Vec<SavedBody>
+}
+
+impl SavedRun {
+    // a hash of the run for peers to compare, the trajectory preview is a view setting and is left out
+    pub fn checksum(&self) -> u64 {
+        let mut run = self.clone();
+        run.game.draw_trajectory = false;
+        let bytes = serde_json::to_vec(&run).unwrap_or_default();
+        bytes.iter().fold(CHECKSUM_OFFSET, |hash, byte| (hash ^ *byte as u64).wrapping_mul(CHECKSUM_PRIME))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveFile {
+    version: u32,
+    run: SavedRun
+}
+
+impl SaveFile {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let save: SaveFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if save.version != SAVE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("save version {} is not {}", save.version, SAVE_VERSION)));
+        }
+        Ok(save)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+enum SavedKind {
+    Ship(Ship),
+    Planet(Planet, Option<BlackHole>),
+    Asteroid(Asteroid),
+    Bullet(Bullet),
+    Enemy(Enemy),
+    Plasma(Plasma),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SavedBody {
+    kind: SavedKind,
+    translation: Vec3,
+    rotation: Quat,
+    radius: f32,
+    mass: Option<f32>,
+    velocity: Option<Vec2>,
+    angular_velocity: Option<f32>,
+    lifetime: Option<Duration>,
+    tween: Option<Tween>
+}
+
+// the run to pick up on entering the playing state, instead of starting a new one
+#[derive(Resource)]
+pub struct Resume(SaveFile);
+
+type BodyItem<'a> = (&'a Transform, &'a Radius, Option<&'a Mass>, Option<&'a Velocity>, Option<&'a AngularVelocity>, Option<&'a Lifetime>, Option<&'a Tween>);
+type KindItem<'a> = (Option<&'a Ship>, Option<&'a Planet>, Option<&'a BlackHole>, Option<&'a Asteroid>, Option<&'a Bullet>, Option<&'a Enemy>, Option<&'a Plasma>);
+type SaveQuery<'w, 's> = Query<'w, 's, (Entity, BodyItem<'static>, KindItem<'static>)>;
+
+fn saved_kind((ship, planet, black_hole, asteroid, bullet, enemy, plasma): KindItem) -> Option<SavedKind> {
+    ship.map(|ship| SavedKind::Ship(ship.clone()))
+        .or_else(|| planet.map(|planet| SavedKind::Planet(planet.clone(), black_hole.cloned())))
+        .or_else(|| asteroid.map(|asteroid| SavedKind::Asteroid(asteroid.clone())))
+        .or_else(|| bullet.map(|bullet| SavedKind::Bullet(bullet.clone())))
+        .or_else(|| enemy.map(|enemy| SavedKind::Enemy(enemy.clone())))
+        .or_else(|| plasma.map(|plasma| SavedKind::Plasma(plasma.clone())))
+}
+
+fn saved_body((transform, Radius(radius), mass, velocity, angular_velocity, lifetime, tween): BodyItem, kind: SavedKind) -> SavedBody {
+    SavedBody {
+        kind,
+        translation: transform.translation,
+        rotation: transform.rotation,
+        radius: *radius,
+        mass: mass.map(|Mass(mass)| *mass),
+        velocity: velocity.map(|Velocity(velocity)| *velocity),
+        angular_velocity: angular_velocity.map(|AngularVelocity(angular_velocity)| *angular_velocity),
+        lifetime: lifetime.map(|Lifetime(lifetime)| *lifetime),
+        tween: tween.cloned()
+    }
+}
+
+// trails come from the current theme, shapes are rebuilt by the usual systems once the bodies exist
+fn spawn_body(commands: &mut Commands, body: &SavedBody, theme: &Theme) {
+    let mut entity = commands.spawn((Radius(body.radius), Transform::from_translation(body.translation).with_rotation(body.rotation)));
+    if let Some(mass) = body.mass {
+        entity.insert(Mass(mass));
+    }
+    if let Some(velocity) = body.velocity {
+        entity.insert(Velocity(velocity));
+    }
+    if let Some(angular_velocity) = body.angular_velocity {
+        entity.insert(AngularVelocity(angular_velocity));
+    }
+    if let Some(lifetime) = body.lifetime {
+        entity.insert(Lifetime(lifetime));
+    }
+    if let Some(tween) = &body.tween {
+        entity.insert(tween.clone());
+    }
+
+    match &body.kind {
+        SavedKind::Ship(ship) => {
+            entity.insert((ship.clone(), ShipInput::default(), ParticleEmitter::new(&SHIP_TRAIL, Some(ship_trail_color(ship.player, theme)))));
+        }
+        SavedKind::Planet(planet, black_hole) => {
+            entity.insert((planet.clone(), GravityVis { radius: 0.0 }));
+            if let Some(black_hole) = black_hole {
+                entity.insert(black_hole.clone());
+            }
+        }
+        SavedKind::Asteroid(asteroid) => {
+            entity.insert(asteroid.clone());
+        }
+        SavedKind::Bullet(bullet) => {
+            entity.insert(bullet.clone());
+        }
+        SavedKind::Enemy(enemy) => {
+            entity.insert((enemy.clone(), ShipInput::default(), ParticleEmitter::new(&SHIP_TRAIL, Some(enemy.trail_color(theme)))));
+        }
+        SavedKind::Plasma(plasma) => {
+            entity.insert((plasma.clone(), ParticleEmitter::new(&PLASMA_TRAIL, None)));
+        }
+    }
+}
+
+pub fn save_exists() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+// the resources and bodies a run is made of, for saving it whole or putting it back
+#[derive(SystemParam)]
+pub(crate) struct RunState<'w, 's> {
+    bodies: SaveQuery<'w, 's>,
+    pub game: ResMut<'w, Game>,
+    stats: ResMut<'w, Stats>,
+    asteroid_timer: ResMut<'w, AsteroidTimer>,
+    enemy_timer: ResMut<'w, EnemyTimer>,
+    rng: ResMut<'w, GameRng>,
+    tuning: ResMut<'w, Tuning>,
+    daily: ResMut<'w, Daily>
+}
+
+impl<'w, 's> RunState<'w, 's> {
+    pub fn capture(&self) -> SavedRun {
+        SavedRun {
+            game: self.game.clone(),
+            stats: self.stats.clone(),
+            asteroid_timer: self.asteroid_timer.clone(),
+            enemy_timer: self.enemy_timer.clone(),
+            rng: self.rng.clone(),
+            tuning: self.tuning.clone(),
+            challenge: self.daily.challenge.clone(),
+            bodies: in_position_order(self.bodies.iter(), |(_, (transform, ..), _)| transform)
+                .into_iter()
+                .filter_map(|(_, body, kind)| saved_kind(kind).map(|kind| saved_body(body, kind)))
+                .collect()
+        }
+    }
+
+    // the bodies in play are gone at the end of the stage
+    fn clear(&self, commands: &mut Commands) {
+        for (entity, _, kind) in &self.bodies {
+            if saved_kind(kind).is_some() {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+
+    pub fn restore(&mut self, commands: &mut Commands, run: &SavedRun, theme: &Theme) {
+        self.clear(commands);
+        let draw_trajectory = self.game.draw_trajectory;
+        *self.game = run.game.clone();
+        self.game.draw_trajectory = draw_trajectory;
+        *self.stats = run.stats.clone();
+        *self.asteroid_timer = run.asteroid_timer.clone();
+        *self.enemy_timer = run.enemy_timer.clone();
+        *self.rng = run.rng.clone();
+        self.daily.resume(run.challenge.clone(), &mut self.tuning, &run.tuning);
+        for body in &run.bodies {
+            spawn_body(commands, body, theme);
+        }
+    }
+
+    // a new run from the start: the rules, spawn timers and clock begin the same way every time, so a seed is the whole run
+    pub fn start(&mut self, commands: &mut Commands, rng: Option<GameRng>, theme: &Theme) {
+        match rng {
+            Some(rng) => *self.rng = rng,
+            None => self.rng.restart(),
+        }
+        self.game.gameover_time = 0;
+        self.game.score = 0;
+        self.game.player_scores = [0; MAX_PLAYERS];
+        self.game.winner = None;
+        self.game.tick = 0;
+        self.game.time = 0;
+        self.game.score_multiplier = 1;
+        *self.stats = Stats::new();
+        *self.asteroid_timer = AsteroidTimer::default();
+        *self.enemy_timer = EnemyTimer::default();
+        *self.tuning = self.daily.challenge.as_ref().map_or_else(Tuning::default, Challenge::tuning);
+        spawn_run(commands, self.game.players, theme);
+    }
+
+    pub fn ship_count(&self) -> usize {
+        self.bodies.iter().filter(|(_, _, (ship, ..))| ship.is_some()).count()
+    }
+}
+
+// the same as RunState for code holding the whole world, such as the fixed step drivers
+pub fn capture_world(world: &mut World) -> SavedRun {
+    let mut state: SystemState<RunState> = SystemState::new(world);
+    state.get_mut(world).capture()
+}
+
+pub fn start_world(world: &mut World, rng: Option<GameRng>) {
+    let mut state: SystemState<(Commands, RunState, Res<Theme>)> = SystemState::new(world);
+    let (mut commands, mut run_state, theme) = state.get_mut(world);
+    run_state.clear(&mut commands);
+    run_state.start(&mut commands, rng, &theme);
+    state.apply(world);
+}
+
+pub fn restore_world(world: &mut World, run: &SavedRun) {
+    let mut state: SystemState<(Commands, RunState, Res<Theme>)> = SystemState::new(world);
+    let (mut commands, mut run_state, theme) = state.get_mut(world);
+    run_state.restore(&mut commands, run, &theme);
+    state.apply(world);
+}
+
+// closing the window or pressing escape mid-run keeps the run for later
+pub fn save_on_quit(keyboard_input: Res<Input<KeyCode>>, mut close_requests: EventReader<WindowCloseRequested>, run: RunState) {
+    let quitting = close_requests.iter().count() > 0 || keyboard_input.just_pressed(KeyCode::Escape);
+    if !quitting || run.ship_count() == 0 {
+        return;
+    }
+
+    let save = SaveFile { version: SAVE_VERSION, run: run.capture() };
+    match save.save(SAVE_PATH) {
+        Ok(()) => info!("saved the game to {}", SAVE_PATH),
+        Err(err) => warn!("could not save the game: {}", err),
+    }
+}
+
+// a save is only good for one continue, a broken or outdated one is thrown away
+pub fn continue_game(mut commands: Commands, mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>) {
+    if !keyboard_input.just_pressed(KeyCode::C) || !save_exists() {
+        return;
+    }
+    match SaveFile::load(SAVE_PATH) {
+        Ok(save) => {
+            if state.set(GameState::Playing).is_ok() {
+                commands.insert_resource(Resume(save));
+            }
+        }
+        Err(err) => warn!("could not load {}: {}", SAVE_PATH, err),
+    }
+    if let Err(err) = fs::remove_file(SAVE_PATH) {
+        warn!("could not remove {}: {}", SAVE_PATH, err);
+    }
+}
+
+pub fn restore_game(mut commands: Commands, resume: Option<Res<Resume>>, mut run: RunState, theme: Res<Theme>) {
+    let Some(resume) = resume else {
+        return;
+    };
+    let Resume(save) = resume.as_ref();
+    run.restore(&mut commands, &save.run, &theme);
+    commands.remove_resource::<Resume>();
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg32;
+
+    use super::*;
+    use crate::sim::{run_ticks, set_ship_inputs, simulation_world, INPUT_FIRE, INPUT_LEFT, INPUT_RIGHT, INPUT_THRUST};
+
+    fn play(world: &mut World, seed: u64, ticks: u32) {
+        let mut rng = Pcg32::seed_from_u64(seed);
+        let mut inputs = [0; MAX_PLAYERS];
+        for tick in 0..ticks {
+            if tick % 15 == 0 {
+                for bits in &mut inputs[..2] {
+                    *bits = rng.gen::<u8>() & (INPUT_LEFT | INPUT_RIGHT | INPUT_THRUST | INPUT_FIRE);
+                }
+            }
+            set_ship_inputs(world, &inputs);
+            run_ticks(world, 1);
+        }
+    }
+
+    #[test]
+    fn a_loaded_save_plays_on_like_the_run_it_came_from() {
+        let mut world = simulation_world(3, 2);
+        world.resource_mut::<Tuning>().gravity = GRAVITY * 1.5;
+        play(&mut world, 3, 300);
+
+        let json = serde_json::to_string(&SaveFile { version: SAVE_VERSION, run: capture_world(&mut world) }).unwrap();
+        let save: SaveFile = serde_json::from_str(&json).unwrap();
+        let mut loaded = simulation_world(11, 1);
+        restore_world(&mut loaded, &save.run);
+        assert_eq!(loaded.resource::<Tuning>().gravity, GRAVITY * 1.5);
+
+        play(&mut world, 4, 600);
+        play(&mut loaded, 4, 600);
+        let (original, loaded) = (capture_world(&mut world), capture_world(&mut loaded));
+        assert!(original.game.tick > 300);
+        assert_eq!(serde_json::to_string(&original).unwrap(), serde_json::to_string(&loaded).unwrap());
+    }
+
+    #[test]
+    fn a_continued_daily_run_is_still_the_daily_run() {
+        let challenge = Challenge::for_day(20_000);
+        let mut world = simulation_world(challenge.seed, 1);
+        world.resource_scope(|world, mut daily: Mut<Daily>| daily.begin(challenge.clone(), &mut world.resource_mut::<Tuning>()));
+        play(&mut world, 5, 120);
+        let run = capture_world(&mut world);
+
+        let mut loaded = simulation_world(11, 1);
+        loaded.resource_mut::<Tuning>().gravity = GRAVITY * 2.0;
+        restore_world(&mut loaded, &run);
+        assert_eq!(loaded.resource::<Daily>().challenge, Some(challenge.clone()));
+        assert_eq!(loaded.resource::<Tuning>().gravity, challenge.tuning().gravity);
+    }
+}
@@ -0,0 +1,401 @@
+use std::sync::Arc;
+
+use bevy::audio::{play_queued_audio_system, AudioOutput, AudioSink, Decodable, Source};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{Duration, HashMap};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+use crate::locale::Strings;
+use crate::prefs::OptionChanged;
+use crate::{GameEvent, Ship, ShipInput, PLANET_COLLAPSE_TIME_MS};
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+const SFX_DEFAULT_VOLUME: f32 = 0.7;
+const SFX_VOLUME_STEP: f32 = 0.1;
+
+const SFX_LASER_GAIN: f32 = 0.25;
+const SFX_EXPLOSION_GAIN: f32 = 0.6;
+const SFX_TELEPORT_GAIN: f32 = 0.4;
+const SFX_THUD_GAIN: f32 = 0.7;
+const SFX_COLLAPSE_GAIN: f32 = 0.8;
+const SFX_EJECT_GAIN: f32 = 0.5;
+const SFX_THRUST_GAIN: f32 = 0.35;
+
+// a mono buffer of samples synthesized at runtime, playable like any other bevy audio asset
+#[derive(TypeUuid, Clone)]
+#[uuid = "6f0c0a4e-4d1e-4b8a-9a55-1d8b2c3e7f10"]
+pub struct SynthSound {
+    pub samples: Arc<[f32]>
+}
+
+impl SynthSound {
+    pub fn new(samples: Vec<f32>) -> Self {
+        Self { samples: samples.into() }
+    }
+}
+
+pub struct SynthDecoder {
+    samples: Arc<[f32]>,
+    position: usize
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.position).copied();
+        self.position += 1;
+        sample
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len().saturating_sub(self.position))
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(self.samples.len() as f32 / SAMPLE_RATE as f32))
+    }
+}
+
+impl Decodable for SynthSound {
+    type Decoder = SynthDecoder;
+    type DecoderItem = f32;
+
+    fn decoder(&self) -> SynthDecoder {
+        SynthDecoder { samples: self.samples.clone(), position: 0 }
+    }
+}
+
+// renders `seconds` of audio by calling the generator with the time of each sample
+pub fn render(seconds: f32, mut generator: impl FnMut(f32) -> f32) -> Vec<f32> {
+    let count = (seconds * SAMPLE_RATE as f32) as usize;
+    (0..count).map(|i| generator(i as f32 / SAMPLE_RATE as f32).clamp(-1.0, 1.0)).collect()
+}
+
+// integrates frequency into phase so sweeps stay continuous
+#[derive(Default)]
+pub struct Oscillator { phase: f32 }
+
+impl Oscillator {
+    pub fn step(&mut self, frequency: f32) -> f32 {
+        self.phase = (self.phase + frequency / SAMPLE_RATE as f32).fract();
+        self.phase
+    }
+
+    pub fn sine(&mut self, frequency: f32) -> f32 {
+        (self.step(frequency) * 2.0 * std::f32::consts::PI).sin()
+    }
+
+    pub fn square(&mut self, frequency: f32) -> f32 {
+        if self.step(frequency) < 0.5 { 1.0 } else { -1.0 }
+    }
+
+    pub fn saw(&mut self, frequency: f32) -> f32 {
+        self.step(frequency) * 2.0 - 1.0
+    }
+}
+
+// seeded so every render of a sound is identical
+pub struct Noise {
+    rng: Pcg32,
+    filtered: f32
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: Pcg32::seed_from_u64(seed), filtered: 0.0 }
+    }
+
+    pub fn white(&mut self) -> f32 {
+        self.rng.gen_range(-1.0..1.0)
+    }
+
+    // one pole low pass, smaller cutoffs give a duller rumble
+    pub fn low(&mut self, cutoff: f32) -> f32 {
+        let white = self.white();
+        self.filtered += (white - self.filtered) * cutoff.clamp(0.0, 1.0);
+        self.filtered
+    }
+}
+
+// exponential sweep from start to end frequency over the duration
+pub fn sweep(start: f32, end: f32, t: f32, duration: f32) -> f32 {
+    start * (end / start).powf((t / duration).clamp(0.0, 1.0))
+}
+
+pub fn synth_laser() -> Vec<f32> {
+    let duration = 0.15;
+    let mut oscillator = Oscillator::default();
+    render(duration, |t| {
+        let decay = (1.0 - t / duration).powi(2);
+        oscillator.square(sweep(1400.0, 220.0, t, duration)) * decay
+    })
+}
+
+pub fn synth_explosion(radius: f32) -> Vec<f32> {
+    let duration = 0.25 + radius * 0.04;
+    let cutoff = (0.35 - radius * 0.012).max(0.05);
+    let mut noise = Noise::new(radius.to_bits() as u64);
+    render(duration, |t| {
+        let decay = (-t * 6.0 / duration).exp();
+        noise.low(cutoff) * decay * 2.0
+    })
+}
+
+pub fn synth_teleport() -> Vec<f32> {
+    let duration = 0.5;
+    let mut oscillator = Oscillator::default();
+    let mut noise = Noise::new(7);
+    render(duration, |t| {
+        let swell = (std::f32::consts::PI * t / duration).sin();
+        let tone = oscillator.sine(sweep(300.0, 1800.0, t, duration));
+        let air = noise.low(0.05 + 0.4 * t / duration);
+        (tone * 0.5 + air * 1.5) * swell
+    })
+}
+
+pub fn synth_thud() -> Vec<f32> {
+    let duration = 0.35;
+    let mut oscillator = Oscillator::default();
+    let mut noise = Noise::new(13);
+    render(duration, |t| {
+        let click = if t < 0.005 { noise.white() * 0.3 } else { 0.0 };
+        oscillator.sine(sweep(90.0, 45.0, t, duration)) * (-t * 12.0).exp() + click
+    })
+}
+
+pub fn synth_collapse() -> Vec<f32> {
+    let duration = PLANET_COLLAPSE_TIME_MS / 1000.0;
+    let mut oscillator = Oscillator::default();
+    let mut noise = Noise::new(29);
+    render(duration + 0.8, |t| {
+        if t < duration {
+            let progress = t / duration;
+            let tone = oscillator.saw(sweep(900.0, 30.0, t, duration));
+            tone * (1.0 - progress) * 0.5 + noise.low(0.1) * progress
+        } else {
+            // the star finally gives way with a long low boom
+            let tail = t - duration;
+            (oscillator.sine(35.0) + noise.low(0.02) * 2.0) * (-tail * 4.0).exp()
+        }
+    })
+}
+
+pub fn synth_eject() -> Vec<f32> {
+    let duration = 0.4;
+    let mut oscillator = Oscillator::default();
+    let mut noise = Noise::new(41);
+    render(duration, |t| {
+        let decay = (1.0 - t / duration).powi(2);
+        let sizzle = noise.low(0.1 + 0.3 * t / duration);
+        (oscillator.saw(sweep(120.0, 480.0, t, duration)) * 0.3 + sizzle * 1.5) * decay
+    })
+}
+
+pub fn synth_thrust() -> Vec<f32> {
+    let duration = 0.5;
+    let mut noise = Noise::new(3);
+    render(duration, |t| {
+        let wobble = 0.8 + 0.2 * (2.0 * std::f32::consts::PI * 30.0 * t).sin();
+        noise.low(0.04) * wobble * 3.0
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SoundEffect {
+    Laser,
+    Explosion { radius: f32 },
+    Teleport,
+    Thud,
+    Collapse,
+    Eject,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum AudioBackend {
+    Device,
+    Null,
+}
+
+#[derive(Resource)]
+pub struct AudioSettings {
+    pub volume: f32
+}
+
+#[derive(Resource)]
+pub struct SoundBank {
+    laser: Handle<SynthSound>,
+    teleport: Handle<SynthSound>,
+    thud: Handle<SynthSound>,
+    collapse: Handle<SynthSound>,
+    eject: Handle<SynthSound>,
+    thrust: Handle<SynthSound>,
+    explosions: HashMap<u32, Handle<SynthSound>>,
+    thrust_sink: Option<Handle<AudioSink>>
+}
+
+// the null backend synthesizes nothing and never opens an audio device
+pub struct SfxPlugin {
+    pub backend: AudioBackend
+}
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AudioSettings { volume: SFX_DEFAULT_VOLUME })
+            .add_event::<SoundEffect>()
+            .add_system(adjust_volume);
+
+        if self.backend == AudioBackend::Device {
+            app.init_non_send_resource::<AudioOutput<SynthSound>>()
+                .add_asset::<SynthSound>()
+                .add_asset::<AudioSink>()
+                .init_resource::<Audio<SynthSound>>()
+                .add_startup_system(setup_sound_bank)
+                .add_system_to_stage(CoreStage::PostUpdate, play_queued_audio_system::<SynthSound>);
+        }
+    }
+}
+
+fn setup_sound_bank(mut commands: Commands, mut sounds: ResMut<Assets<SynthSound>>) {
+    commands.insert_resource(SoundBank {
+        laser: sounds.add(SynthSound::new(synth_laser())),
+        teleport: sounds.add(SynthSound::new(synth_teleport())),
+        thud: sounds.add(SynthSound::new(synth_thud())),
+        collapse: sounds.add(SynthSound::new(synth_collapse())),
+        eject: sounds.add(SynthSound::new(synth_eject())),
+        thrust: sounds.add(SynthSound::new(synth_thrust())),
+        explosions: HashMap::default(),
+        thrust_sink: None
+    });
+}
+
+pub fn play_sounds(mut sound_events: EventReader<SoundEffect>, mut game_events: EventReader<GameEvent>, bank: Option<ResMut<SoundBank>>, audio: Option<Res<Audio<SynthSound>>>, mut sounds: Option<ResMut<Assets<SynthSound>>>, settings: Res<AudioSettings>) {
+    let (Some(mut bank), Some(audio), Some(sounds)) = (bank, audio, sounds.as_mut()) else {
+        sound_events.clear();
+        game_events.clear();
+        return;
+    };
+
+    let teleports = game_events.iter().filter(|event| matches!(event, GameEvent::Teleported { .. })).map(|_| SoundEffect::Teleport);
+    let effects: Vec<SoundEffect> = sound_events.iter().copied().chain(teleports).collect();
+    for effect in effects {
+        let (handle, gain) = match effect {
+            SoundEffect::Laser => (bank.laser.clone(), SFX_LASER_GAIN),
+            SoundEffect::Explosion { radius } => {
+                // explosions are cached per whole unit of radius
+                let size = radius.round().max(1.0) as u32;
+                let handle = bank.explosions.entry(size)
+                    .or_insert_with(|| sounds.add(SynthSound::new(synth_explosion(size as f32))))
+                    .clone();
+                (handle, SFX_EXPLOSION_GAIN)
+            }
+            SoundEffect::Teleport => (bank.teleport.clone(), SFX_TELEPORT_GAIN),
+            SoundEffect::Thud => (bank.thud.clone(), SFX_THUD_GAIN),
+            SoundEffect::Collapse => (bank.collapse.clone(), SFX_COLLAPSE_GAIN),
+            SoundEffect::Eject => (bank.eject.clone(), SFX_EJECT_GAIN),
+        };
+        audio.play_with_settings(handle, PlaybackSettings::ONCE.with_volume(settings.volume * gain));
+    }
+}
+
+pub fn thrust_sound(query: Query<&ShipInput, With<Ship>>, bank: Option<ResMut<SoundBank>>, audio: Option<Res<Audio<SynthSound>>>, sinks: Option<Res<Assets<AudioSink>>>, settings: Res<AudioSettings>) {
+    let (Some(mut bank), Some(audio), Some(sinks)) = (bank, audio, sinks) else {
+        return;
+    };
+
+    let thrusting = query.iter().any(|input| input.thrust);
+    match &bank.thrust_sink {
+        Some(sink_handle) => {
+            if let Some(sink) = sinks.get(sink_handle) {
+                sink.set_volume(if thrusting { settings.volume * SFX_THRUST_GAIN } else { 0.0 });
+            }
+        }
+        None => {
+            // the rumble loops forever and is faded in and out rather than restarted
+            let sink = audio.play_with_settings(bank.thrust.clone(), PlaybackSettings::LOOP.with_volume(0.0));
+            bank.thrust_sink = Some(sinks.get_handle(sink));
+        }
+    }
+}
+
+pub fn silence_thrust(bank: Option<Res<SoundBank>>, sinks: Option<Res<Assets<AudioSink>>>) {
+    if let (Some(bank), Some(sinks)) = (bank, sinks) {
+        if let Some(sink) = bank.thrust_sink.as_ref().and_then(|handle| sinks.get(handle)) {
+            sink.set_volume(0.0);
+        }
+    }
+}
+
+pub fn adjust_volume(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<AudioSettings>, mut options: EventWriter<OptionChanged>, strings: Res<Strings>) {
+    let step = if keyboard_input.just_pressed(KeyCode::Equals) {
+        SFX_VOLUME_STEP
+    } else if keyboard_input.just_pressed(KeyCode::Minus) {
+        -SFX_VOLUME_STEP
+    } else {
+        return;
+    };
+    settings.volume = ((settings.volume + step) * 10.0).round() / 10.0;
+    settings.volume = settings.volume.clamp(0.0, 1.0);
+    options.send(OptionChanged(strings.format("option.volume", &[&format!("{:.0}", settings.volume * 100.0)])));
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    use super::*;
+
+    fn all_sounds() -> Vec<(&'static str, Vec<f32>)> {
+        vec![("laser", synth_laser()), ("explosion", synth_explosion(1.0)), ("big explosion", synth_explosion(40.0)),
+             ("teleport", synth_teleport()), ("thud", synth_thud()), ("collapse", synth_collapse()),
+             ("eject", synth_eject()), ("thrust", synth_thrust())]
+    }
+
+    #[test]
+    fn every_sound_has_samples_and_stays_in_range() {
+        for (name, samples) in all_sounds() {
+            assert!(!samples.is_empty(), "{} is empty", name);
+            assert!(samples.len() < 3 * SAMPLE_RATE as usize, "{} is {} samples", name, samples.len());
+            assert!(samples.iter().all(|sample| sample.is_finite() && sample.abs() <= 1.0), "{} leaves -1..1", name);
+            assert!(samples.iter().any(|sample| sample.abs() > 0.01), "{} is silent", name);
+        }
+    }
+
+    #[test]
+    fn sounds_render_the_same_every_time() {
+        for ((name, first), (_, second)) in all_sounds().into_iter().zip(all_sounds()) {
+            assert_eq!(first, second, "{} differs between renders", name);
+        }
+        assert!(synth_explosion(40.0).len() > synth_explosion(1.0).len());
+    }
+
+    #[test]
+    fn the_null_backend_drops_sounds_without_a_device() {
+        let mut app = App::new();
+        app.add_event::<GameEvent>()
+            .add_plugin(SfxPlugin { backend: AudioBackend::Null });
+        assert!(!app.world.contains_resource::<Audio<SynthSound>>());
+
+        app.world.send_event(SoundEffect::Explosion { radius: 12.0 });
+        app.world.send_event(SoundEffect::Laser);
+        app.world.send_event(GameEvent::Teleported { player: 0 });
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(play_sounds);
+        stage.run(&mut app.world);
+        assert!(!app.world.contains_resource::<SoundBank>());
+        assert!(!app.world.contains_resource::<Assets<SynthSound>>());
+    }
+}
@@ -0,0 +1,161 @@
+use bevy::ecs::schedule::{Stage, SystemStage};
+use bevy::math::Vec2;
+use bevy::utils::{Duration, HashSet, Instant};
+
+use crate::*;
+
+// the rules step at a fixed rate so every peer, replay and rollback computes identical states
+pub const SIM_TICK_RATE: u32 = 60;
+pub const SIM_ARENA_HALF_SIZE: Vec2 = Vec2 { x: 640.0, y: 360.0 }; // window size can differ between peers so the arena is fixed
+pub const SIM_STEP: Duration = Duration::from_nanos(1_000_000_000 / SIM_TICK_RATE as u64);
+pub const SIM_MAX_FRAME_TICKS: u32 = 8;   // a long frame catches up this many ticks at most, the rest is dropped
+
+pub const INPUT_LEFT: u8 = 1 << 0;
+pub const INPUT_RIGHT: u8 = 1 << 1;
+pub const INPUT_THRUST: u8 = 1 << 2;
+pub const INPUT_FIRE: u8 = 1 << 3;
+pub const INPUT_TELEPORT: u8 = 1 << 4;
+
+impl ShipInput {
+    pub fn to_bits(&self) -> u8 {
+        let mut bits = 0;
+        if self.left { bits |= INPUT_LEFT; }
+        if self.right { bits |= INPUT_RIGHT; }
+        if self.thrust { bits |= INPUT_THRUST; }
+        if self.fire { bits |= INPUT_FIRE; }
+        if self.teleport { bits |= INPUT_TELEPORT; }
+        bits
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            left: bits & INPUT_LEFT != 0,
+            right: bits & INPUT_RIGHT != 0,
+            thrust: bits & INPUT_THRUST != 0,
+            fire: bits & INPUT_FIRE != 0,
+            teleport: bits & INPUT_TELEPORT != 0
+        }
+    }
+}
+
+// the game's rules as a stage of their own, stepped a whole tick at a time so the same inputs always play out the same
+#[derive(Resource)]
+pub struct Simulation {
+    stage: SystemStage,
+    time: Time,                   // the clock the rules see, it only ever moves by SIM_STEP
+    accumulator: Duration
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        let start = Instant::now();
+        let mut time = Time::new(start);
+        time.update_with_instant(start);
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system_set(rule_systems());
+        Self { stage, time, accumulator: Duration::ZERO }
+    }
+}
+
+impl Simulation {
+    // how many ticks a frame of this length is worth, any remainder carries over to the next frame
+    pub fn due(&mut self, delta: Duration) -> u32 {
+        self.due_within(delta, SIM_MAX_FRAME_TICKS)
+    }
+
+    // the same with a cap of its own, for a clock that is meant to run faster than real time
+    pub fn due_within(&mut self, delta: Duration, max_ticks: u32) -> u32 {
+        self.accumulator += delta;
+        let ticks = (self.accumulator.as_nanos() / SIM_STEP.as_nanos()) as u32;
+        if ticks > max_ticks {
+            self.accumulator = Duration::ZERO;
+            return max_ticks;
+        }
+        self.accumulator -= SIM_STEP * ticks;
+        ticks
+    }
+
+    // runs the rules once with the frame's clock swapped out for the fixed one
+    pub fn tick(&mut self, world: &mut World) {
+        let last_update = self.time.last_update().unwrap_or_else(Instant::now);
+        self.time.update_with_instant(last_update + SIM_STEP);
+        let frame_time = world.remove_resource::<Time>();
+        world.insert_resource(self.time.clone());
+        self.stage.run(world);
+        match frame_time {
+            Some(frame_time) => world.insert_resource(frame_time),
+            None => {
+                world.remove_resource::<Time>();
+            }
+        }
+    }
+}
+
+// steps a world that holds its own Simulation, for drivers with nothing to do between ticks
+pub fn run_ticks(world: &mut World, ticks: u32) {
+    world.resource_scope(|world, mut simulation: Mut<Simulation>| {
+        for _tick in 0..ticks {
+            simulation.tick(world);
+        }
+    });
+}
+
+// hands every ship its player's input for the next tick
+pub fn set_ship_inputs(world: &mut World, inputs: &[u8]) {
+    let mut query = world.query::<(&Ship, &mut ShipInput)>();
+    for (ship, mut input) in query.iter_mut(world) {
+        if let Some(bits) = inputs.get(ship.player) {
+            *input = ShipInput::from_bits(*bits);
+        }
+    }
+}
+
+// a world holding nothing but a new run and the rules, for stepping a game without a window
+pub fn simulation_world(seed: u64, players: usize) -> World {
+    let mut world = World::new();
+    let mut game = Game::new();
+    game.players = players;
+    world.insert_resource(game);
+    world.insert_resource(Stats::new());
+    world.insert_resource(GameRng::new(seed, true));
+    world.init_resource::<Tuning>();
+    world.init_resource::<Daily>();
+    world.init_resource::<AsteroidTimer>();
+    world.init_resource::<EnemyTimer>();
+    world.insert_resource(CLASSIC);
+    world.init_resource::<Events<GameEvent>>();
+    world.init_resource::<Events<SoundEffect>>();
+    world.init_resource::<Events<TweenCompleted>>();
+    world.init_resource::<Simulation>();
+    start_world(&mut world, Some(GameRng::new(seed, true)));
+    world
+}
+
+// with nothing on screen or speaker the events and particles the rules throw off are dropped as they come
+pub fn drop_effects(world: &mut World) {
+    world.resource_mut::<Events<GameEvent>>().update();
+    world.resource_mut::<Events<SoundEffect>>().update();
+    world.resource_mut::<Events<TweenCompleted>>().update();
+    let particles: Vec<Entity> = world.query_filtered::<Entity, With<Particle>>().iter(world).collect();
+    for particle in particles {
+        world.despawn(particle);
+    }
+}
+
+// runs ticks that were already shown once, as a rollback does, keeping the sounds, shakes and particles they throw off a second time from reaching the player
+pub fn without_effects(world: &mut World, resimulate: impl FnOnce(&mut World)) {
+    let game_events = world.remove_resource::<Events<GameEvent>>().unwrap_or_default();
+    let sounds = world.remove_resource::<Events<SoundEffect>>().unwrap_or_default();
+    world.init_resource::<Events<GameEvent>>();
+    world.init_resource::<Events<SoundEffect>>();
+    let shown: HashSet<Entity> = world.query_filtered::<Entity, With<Particle>>().iter(world).collect();
+
+    resimulate(world);
+
+    world.insert_resource(game_events);
+    world.insert_resource(sounds);
+    let repeated: Vec<Entity> = world.query_filtered::<Entity, With<Particle>>().iter(world).filter(|particle| !shown.contains(particle)).collect();
+    for particle in repeated {
+        world.despawn(particle);
+    }
+}
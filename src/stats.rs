@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::locale::Strings;
+
+use crate::{Game, Mass, Planet, Radius, Ship, Velocity, ASTEROID_FRACTURE_MIN_RADIUS, ASTEROID_RADIUS_MIN};
+
+pub const STATS_EXPORT_PATH: &str = "stats.json";
+pub const STATS_SIZE: f32 = 20.0;
+pub const STATS_EXPORT_SIZE: f32 = 16.0;
+
+// asteroids are bucketed by the radius they had when shot
+const STATS_TIER_NAMES: [&str; 3] = ["small", "medium", "large"];
+const STATS_TIER_MEDIUM_RADIUS: f32 = ASTEROID_FRACTURE_MIN_RADIUS; // anything that could still fracture
+const STATS_TIER_LARGE_RADIUS: f32 = ASTEROID_RADIUS_MIN;           // anything that could have been freshly spawned
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct Stats {
+    pub shots_fired: u32,
+    pub hits: u32,
+    pub asteroids_destroyed: [u32; 3],
+    pub enemies_destroyed: u32,
+    pub teleports: u32,
+    pub distance_flown: f32,
+    pub closest_approach: Option<f32>,     // none until a ship has flown near a star
+    pub peak_planet_mass: f32,
+    pub time_survived: u64
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            shots_fired: 0,
+            hits: 0,
+            asteroids_destroyed: [0; 3],
+            enemies_destroyed: 0,
+            teleports: 0,
+            distance_flown: 0.0,
+            closest_approach: None,
+            peak_planet_mass: 0.0,
+            time_survived: 0
+        }
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            return 0.0;
+        }
+        self.hits as f32 / self.shots_fired as f32
+    }
+
+    pub fn record_asteroid(&mut self, radius: f32) {
+        let tier = if radius >= STATS_TIER_LARGE_RADIUS {
+            2
+        } else if radius > STATS_TIER_MEDIUM_RADIUS {
+            1
+        } else {
+            0
+        };
+        self.asteroids_destroyed[tier] += 1;
+    }
+
+    pub fn report(&self, strings: &Strings) -> String {
+        let closest_approach = match self.closest_approach {
+            Some(closest_approach) => strings.number(closest_approach.round() as u32),
+            None => "-".to_string(),
+        };
+        let lines = [
+            strings.format("stats.time_survived", &[&format_time(self.time_survived)]),
+            strings.format("stats.shots_fired", &[&strings.number(self.shots_fired)]),
+            strings.format("stats.hits", &[&strings.number(self.hits)]),
+            strings.format("stats.accuracy", &[&format!("{:.0}", self.accuracy() * 100.0)]),
+            strings.format("stats.asteroids", &[&strings.number(self.asteroids_destroyed[0]), &strings.number(self.asteroids_destroyed[1]), &strings.number(self.asteroids_destroyed[2])]),
+            strings.format("stats.enemies", &[&strings.number(self.enemies_destroyed)]),
+            strings.format("stats.teleports", &[&strings.number(self.teleports)]),
+            strings.format("stats.distance", &[&strings.number(self.distance_flown.round() as u64)]),
+            strings.format("stats.closest_approach", &[&closest_approach]),
+            strings.format("stats.peak_mass", &[&strings.number(self.peak_planet_mass.round() as u32)]),
+        ];
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut asteroids = serde_json::Map::new();
+        for (name, count) in STATS_TIER_NAMES.iter().zip(self.asteroids_destroyed.iter()) {
+            asteroids.insert(name.to_string(), json!(count));
+        }
+        json!({
+            "time_survived_ms": self.time_survived,
+            "shots_fired": self.shots_fired,
+            "hits": self.hits,
+            "accuracy": self.accuracy(),
+            "asteroids_destroyed": asteroids,
+            "enemies_destroyed": self.enemies_destroyed,
+            "teleports": self.teleports,
+            "distance_flown": self.distance_flown,
+            "closest_approach": self.closest_approach,
+            "peak_planet_mass": self.peak_planet_mass
+        })
+    }
+
+    pub fn export(&self, path: &str) -> std::io::Result<()> {
+        let contents = serde_json::to_string_pretty(&self.to_json())?;
+        std::fs::write(path, contents)
+    }
+}
+
+#[derive(Component)]
+pub struct StatsExportText;
+
+pub fn format_time(ms: u64) -> String {
+    let seconds = ms / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+pub fn track_stats(ship_query: Query<(&Transform, &Velocity), With<Ship>>, planet_query: Query<(&Transform, &Radius, &Mass, &Planet)>, mut stats: ResMut<Stats>, game: Res<Game>, time: Res<Time>) {
+    for (planet_transform, Radius(planet_radius), Mass(planet_mass), planet) in &planet_query {
+        if !planet.collapsing {
+            stats.peak_planet_mass = stats.peak_planet_mass.max(*planet_mass);
+        }
+        for (transform, _velocity) in &ship_query {
+            let altitude = Vec3::distance(planet_transform.translation, transform.translation) - planet_radius;
+            stats.closest_approach = Some(stats.closest_approach.map_or(altitude, |closest| closest.min(altitude)).max(0.0));
+        }
+    }
+
+    for (_transform, Velocity(velocity)) in &ship_query {
+        stats.distance_flown += velocity.length() * time.delta_seconds();
+    }
+
+    stats.time_survived = game.time;
+}
+
+pub fn export_stats(keyboard_input: Res<Input<KeyCode>>, stats: Res<Stats>, mut query: Query<&mut Text, With<StatsExportText>>, strings: Res<Strings>) {
+    if keyboard_input.just_pressed(KeyCode::E) {
+        let message = match stats.export(STATS_EXPORT_PATH) {
+            Ok(()) => strings.format("stats.saved", &[&STATS_EXPORT_PATH]),
+            Err(err) => strings.format("stats.save_failed", &[&err]),
+        };
+        for mut text in &mut query {
+            text.sections[0].value = message.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_reads_in_minutes_and_seconds() {
+        assert_eq!(format_time(0), "0:00");
+        assert_eq!(format_time(59_999), "0:59");
+        assert_eq!(format_time(61_000), "1:01");
+        assert_eq!(format_time(3_725_000), "62:05");
+    }
+
+    #[test]
+    fn accuracy_is_zero_before_the_first_shot() {
+        let mut stats = Stats::new();
+        assert_eq!(stats.accuracy(), 0.0);
+        stats.shots_fired = 8;
+        stats.hits = 2;
+        assert_eq!(stats.accuracy(), 0.25);
+    }
+
+    #[test]
+    fn asteroids_are_counted_by_the_size_they_were_shot_at() {
+        let mut stats = Stats::new();
+        for radius in [STATS_TIER_MEDIUM_RADIUS, STATS_TIER_MEDIUM_RADIUS + 0.1, STATS_TIER_LARGE_RADIUS - 0.1, STATS_TIER_LARGE_RADIUS, STATS_TIER_LARGE_RADIUS * 2.0] {
+            stats.record_asteroid(radius);
+        }
+        assert_eq!(stats.asteroids_destroyed, [1, 2, 2]);
+
+        let json = stats.to_json();
+        assert_eq!(json["asteroids_destroyed"]["small"], 1);
+        assert_eq!(json["asteroids_destroyed"]["large"], 2);
+        assert!(json["closest_approach"].is_null());
+    }
+
+    #[test]
+    fn flying_close_and_far_is_tracked_from_the_star_surface() {
+        let mut world = World::new();
+        let mut game = Game::new();
+        game.time = 4_000;
+        world.insert_resource(game);
+        world.insert_resource(Stats::new());
+        world.insert_resource(Time::default());
+        world.spawn((Planet::new(), Transform::default(), Radius(50.0), Mass(100.0)));
+        let ship = world.spawn((Ship::new(0), Transform::from_xyz(80.0, 0.0, 0.0), Velocity(Vec2::ZERO))).id();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(track_stats);
+
+        stage.run(&mut world);
+        world.get_mut::<Transform>(ship).unwrap().translation = Vec3::new(0.0, 40.0, 0.0);
+        stage.run(&mut world);
+        world.get_mut::<Transform>(ship).unwrap().translation = Vec3::new(0.0, 200.0, 0.0);
+        stage.run(&mut world);
+
+        let stats = world.resource::<Stats>();
+        assert_eq!(stats.closest_approach, Some(0.0));
+        assert_eq!(stats.peak_planet_mass, 100.0);
+        assert_eq!(stats.time_survived, 4_000);
+    }
+}
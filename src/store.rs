@@ -0,0 +1,97 @@
+use std::fs;
+use std::io;
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+// the files kept between sessions; a missing one starts out empty, one that cannot be read is left for the player to recover
+#[derive(Serialize, Deserialize)]
+struct Stored<T> {
+    version: u32,
+    data: T
+}
+
+// what is in a file: nothing if there is none, an error if it is damaged or from another version
+fn read<T: DeserializeOwned>(path: &str, version: u32) -> io::Result<Option<T>> {
+    let contents = match fs::read_to_string(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        contents => contents?,
+    };
+    let mut value: serde_json::Value = serde_json::from_str(&contents)?;
+    match value.get("version").map(serde_json::Value::as_u64) {
+        Some(Some(found)) if found == version as u64 => Ok(Some(serde_json::from_value(value["data"].take())?)),
+        Some(found) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has version {:?}, not {}", path, found, version))),
+        // written before the files carried a version
+        None => Ok(Some(serde_json::from_value(value)?)),
+    }
+}
+
+pub fn load<T: DeserializeOwned + Default>(path: &str, version: u32) -> T {
+    match read(path, version) {
+        Ok(data) => data.unwrap_or_default(),
+        Err(err) => {
+            warn!("could not load {}, it will not be written over: {}", path, err);
+            T::default()
+        }
+    }
+}
+
+pub fn save<T: Serialize + DeserializeOwned>(path: &str, version: u32, data: &T) -> io::Result<()> {
+    if let Err(err) = read::<T>(path, version) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} could not be read and was left as it is: {}", path, err)));
+    }
+    fs::write(path, serde_json::to_string(&Stored { version, data })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION: u32 = 3;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rusteroids-store-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn a_missing_file_starts_empty_and_is_written_with_its_version() {
+        let path = temp_path("missing");
+        assert_eq!(load::<Vec<u32>>(&path, VERSION), Vec::<u32>::new());
+        save(&path, VERSION, &vec![1, 2, 3]).unwrap();
+        assert_eq!(load::<Vec<u32>>(&path, VERSION), vec![1, 2, 3]);
+        assert!(fs::read_to_string(&path).unwrap().contains("\"version\":3"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_damaged_file_is_not_written_over() {
+        let path = temp_path("damaged");
+        fs::write(&path, "{\"version\":3,\"da").unwrap();
+        assert_eq!(load::<Vec<u32>>(&path, VERSION), Vec::<u32>::new());
+        assert!(save(&path, VERSION, &vec![1]).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"version\":3,\"da");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_file_from_another_version_is_not_written_over() {
+        let path = temp_path("newer");
+        fs::write(&path, "{\"version\":4,\"data\":[7]}").unwrap();
+        assert!(read::<Vec<u32>>(&path, VERSION).is_err());
+        assert!(save(&path, VERSION, &vec![1]).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_file_from_before_versions_still_loads() {
+        let path = temp_path("unversioned");
+        fs::write(&path, "[4,5]").unwrap();
+        assert_eq!(load::<Vec<u32>>(&path, VERSION), vec![4, 5]);
+        save(&path, VERSION, &vec![6]).unwrap();
+        assert_eq!(load::<Vec<u32>>(&path, VERSION), vec![6]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,292 @@
+use bevy::prelude::*;
+
+use crate::prefs::{Preferences, PREFERENCES_PATH};
+use crate::{GameState, MAX_PLAYERS};
+
+// every colour the game draws with, so a palette can be swapped as a whole
+#[derive(Resource, Clone, Debug)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Color,
+    pub title: Color,
+    pub text: Color,
+    pub hint: Color,
+    pub disabled: Color,
+    pub toast: Color,
+    pub warning: Color,
+    pub players: [Color; MAX_PLAYERS],
+    pub ship_help: Color,
+    pub teleport_ready: Color,
+    pub bullet: Color,
+    pub asteroid: Color,
+    pub star: Color,
+    pub background_star: Color,
+    pub gravity_ring: Color,
+    pub plasma: Color,
+    pub black_hole_horizon: Color,
+    pub photon_ring: Color,
+    pub accretion_hot: Color,
+    pub accretion_cool: Color,
+    pub enemy_pursuit: Color,
+    pub enemy_orbit_snipe: Color,
+    pub enemy_kamikaze: Color,
+    pub safe: Color,
+    pub danger: Color,
+    pub frame: Color,
+    pub frame_faint: Color,
+    pub hud_text: Color,
+    pub hud_label: Color,
+    pub hud_bar: Color,
+    pub power_up: Color,
+    pub mass: Color,
+    pub particle_tint: Option<Color>             // particles keep their own colours unless the palette overrides them
+}
+
+pub const CLASSIC: Theme = Theme {
+    name: "classic",
+    background: Color::rgb(0.0, 0.0, 0.0),
+    title: Color::hsl(351.0, 0.68, 0.53),
+    text: Color::hsl(171.0, 0.68, 0.53),
+    hint: Color::rgba(1.0, 1.0, 1.0, 0.4),
+    disabled: Color::rgba(1.0, 1.0, 1.0, 0.3),
+    toast: Color::hsl(67.00, 0.76, 0.79),
+    warning: Color::hsl(351.0, 0.68, 0.53),
+    players: [
+        Color::hsl(171.0, 0.68, 0.53),
+        Color::hsl(30.0, 0.78, 0.55),
+        Color::hsl(280.0, 0.60, 0.65),
+        Color::hsl(210.0, 0.70, 0.60),
+    ],
+    ship_help: Color::hsla(12.0, 0.78, 0.55, 0.1),
+    teleport_ready: Color::hsl(351.0, 0.68, 0.53),
+    bullet: Color::rgb(1.0, 1.0, 1.0),
+    asteroid: Color::hsl(128.0, 0.39, 0.40),
+    star: Color::hsl(67.00, 0.76, 0.79),
+    background_star: Color::rgba(1.0, 1.0, 1.0, 0.15),
+    gravity_ring: Color::rgba(1.0, 1.0, 1.0, 0.05),
+    plasma: Color::rgb(1.0, 0.6, 0.2),
+    black_hole_horizon: Color::rgba(0.6, 0.4, 1.0, 0.6),
+    photon_ring: Color::rgba(1.0, 1.0, 1.0, 0.35),
+    accretion_hot: Color::rgba(1.0, 0.95, 0.7, 0.5),
+    accretion_cool: Color::rgba(0.9, 0.2, 0.1, 0.1),
+    enemy_pursuit: Color::hsl(30.0, 0.85, 0.55),
+    enemy_orbit_snipe: Color::hsl(280.0, 0.65, 0.65),
+    enemy_kamikaze: Color::hsl(0.0, 0.9, 0.6),
+    safe: Color::rgba(0.6, 0.8, 1.0, 0.5),
+    danger: Color::rgba(1.0, 0.2, 0.1, 0.9),
+    frame: Color::rgba(1.0, 1.0, 1.0, 0.25),
+    frame_faint: Color::rgba(1.0, 1.0, 1.0, 0.08),
+    hud_text: Color::rgba(1.0, 1.0, 1.0, 0.8),
+    hud_label: Color::rgba(1.0, 1.0, 1.0, 0.5),
+    hud_bar: Color::rgba(1.0, 1.0, 1.0, 0.1),
+    power_up: Color::rgb(1.0, 0.85, 0.3),
+    mass: Color::rgba(1.0, 0.9, 0.5, 0.7),
+    particle_tint: None
+};
+
+// saturated colours and no faint text, for low vision or bright rooms
+pub const HIGH_CONTRAST: Theme = Theme {
+    name: "high_contrast",
+    title: Color::rgb(1.0, 1.0, 0.0),
+    text: Color::rgb(0.0, 1.0, 1.0),
+    hint: Color::rgba(1.0, 1.0, 1.0, 0.85),
+    disabled: Color::rgba(1.0, 1.0, 1.0, 0.6),
+    toast: Color::rgb(1.0, 1.0, 0.0),
+    warning: Color::rgb(1.0, 0.0, 0.0),
+    players: [
+        Color::rgb(0.0, 1.0, 1.0),
+        Color::rgb(1.0, 0.5, 0.0),
+        Color::rgb(1.0, 0.0, 1.0),
+        Color::rgb(0.3, 1.0, 0.3),
+    ],
+    ship_help: Color::rgba(1.0, 1.0, 1.0, 0.35),
+    teleport_ready: Color::rgb(1.0, 1.0, 0.0),
+    asteroid: Color::rgb(0.3, 1.0, 0.3),
+    star: Color::rgb(1.0, 1.0, 0.4),
+    background_star: Color::rgba(1.0, 1.0, 1.0, 0.4),
+    gravity_ring: Color::rgba(1.0, 1.0, 1.0, 0.2),
+    black_hole_horizon: Color::rgb(0.8, 0.5, 1.0),
+    photon_ring: Color::rgba(1.0, 1.0, 1.0, 0.7),
+    accretion_hot: Color::rgba(1.0, 1.0, 0.6, 0.9),
+    accretion_cool: Color::rgba(1.0, 0.3, 0.0, 0.4),
+    enemy_pursuit: Color::rgb(1.0, 0.5, 0.0),
+    enemy_orbit_snipe: Color::rgb(1.0, 0.0, 1.0),
+    enemy_kamikaze: Color::rgb(1.0, 0.0, 0.0),
+    safe: Color::rgba(0.3, 0.8, 1.0, 0.9),
+    danger: Color::rgb(1.0, 0.0, 0.0),
+    frame: Color::rgba(1.0, 1.0, 1.0, 0.6),
+    frame_faint: Color::rgba(1.0, 1.0, 1.0, 0.3),
+    hud_text: Color::rgb(1.0, 1.0, 1.0),
+    hud_label: Color::rgba(1.0, 1.0, 1.0, 0.85),
+    hud_bar: Color::rgba(1.0, 1.0, 1.0, 0.3),
+    power_up: Color::rgb(1.0, 1.0, 0.0),
+    mass: Color::rgb(1.0, 1.0, 0.4),
+    ..CLASSIC
+};
+
+// built on the okabe-ito colours, so nothing depends on telling red from green
+pub const DEUTERANOPIA: Theme = Theme {
+    name: "deuteranopia",
+    title: Color::rgb(0.9, 0.6, 0.0),
+    text: Color::rgb(0.35, 0.7, 0.9),
+    toast: Color::rgb(0.95, 0.9, 0.25),
+    warning: Color::rgb(0.9, 0.6, 0.0),
+    players: [
+        Color::rgb(0.35, 0.7, 0.9),
+        Color::rgb(0.9, 0.6, 0.0),
+        Color::rgb(0.95, 0.9, 0.25),
+        Color::rgb(0.8, 0.6, 0.7),
+    ],
+    ship_help: Color::rgba(0.35, 0.7, 0.9, 0.15),
+    teleport_ready: Color::rgb(0.95, 0.9, 0.25),
+    asteroid: Color::rgb(0.6, 0.6, 0.65),
+    star: Color::rgb(0.95, 0.9, 0.25),
+    plasma: Color::rgb(0.9, 0.6, 0.0),
+    black_hole_horizon: Color::rgba(0.35, 0.7, 0.9, 0.6),
+    accretion_hot: Color::rgba(0.95, 0.9, 0.6, 0.5),
+    accretion_cool: Color::rgba(0.0, 0.45, 0.7, 0.15),
+    enemy_pursuit: Color::rgb(0.9, 0.6, 0.0),
+    enemy_orbit_snipe: Color::rgb(0.8, 0.6, 0.7),
+    enemy_kamikaze: Color::rgb(0.8, 0.4, 0.0),
+    safe: Color::rgba(0.0, 0.45, 0.7, 0.6),
+    danger: Color::rgba(0.9, 0.6, 0.0, 0.95),
+    power_up: Color::rgb(0.95, 0.9, 0.25),
+    mass: Color::rgba(0.95, 0.9, 0.25, 0.7),
+    ..CLASSIC
+};
+
+// like deuteranopia but with no dark reds, which protanopes see as near black
+pub const PROTANOPIA: Theme = Theme {
+    name: "protanopia",
+    enemy_kamikaze: Color::rgb(1.0, 0.75, 0.2),
+    accretion_cool: Color::rgba(0.35, 0.7, 0.9, 0.15),
+    danger: Color::rgba(1.0, 0.85, 0.3, 0.95),
+    ..DEUTERANOPIA
+};
+
+// one phosphor green like an old vector monitor, told apart by brightness alone
+pub const MONOCHROME: Theme = Theme {
+    name: "monochrome",
+    background: Color::rgb(0.0, 0.02, 0.0),
+    title: Color::rgb(0.4, 1.0, 0.5),
+    text: Color::rgb(0.3, 0.85, 0.4),
+    hint: Color::rgba(0.3, 1.0, 0.4, 0.4),
+    disabled: Color::rgba(0.3, 1.0, 0.4, 0.25),
+    toast: Color::rgb(0.6, 1.0, 0.7),
+    warning: Color::rgb(0.8, 1.0, 0.8),
+    players: [
+        Color::rgb(0.4, 1.0, 0.5),
+        Color::rgb(0.7, 1.0, 0.75),
+        Color::rgb(0.25, 0.75, 0.35),
+        Color::rgb(0.55, 0.9, 0.6),
+    ],
+    ship_help: Color::rgba(0.3, 1.0, 0.4, 0.12),
+    teleport_ready: Color::rgb(0.8, 1.0, 0.8),
+    bullet: Color::rgb(0.8, 1.0, 0.8),
+    asteroid: Color::rgb(0.2, 0.6, 0.3),
+    star: Color::rgb(0.6, 1.0, 0.7),
+    background_star: Color::rgba(0.3, 1.0, 0.4, 0.15),
+    gravity_ring: Color::rgba(0.3, 1.0, 0.4, 0.05),
+    plasma: Color::rgb(0.6, 1.0, 0.6),
+    black_hole_horizon: Color::rgba(0.3, 1.0, 0.4, 0.6),
+    photon_ring: Color::rgba(0.6, 1.0, 0.7, 0.35),
+    accretion_hot: Color::rgba(0.7, 1.0, 0.75, 0.5),
+    accretion_cool: Color::rgba(0.1, 0.5, 0.2, 0.1),
+    enemy_pursuit: Color::rgb(0.8, 1.0, 0.8),
+    enemy_orbit_snipe: Color::rgb(0.5, 0.9, 0.55),
+    enemy_kamikaze: Color::rgb(1.0, 1.0, 1.0),
+    safe: Color::rgba(0.2, 0.6, 0.3, 0.5),
+    danger: Color::rgba(0.8, 1.0, 0.8, 0.95),
+    frame: Color::rgba(0.3, 1.0, 0.4, 0.25),
+    frame_faint: Color::rgba(0.3, 1.0, 0.4, 0.08),
+    hud_text: Color::rgba(0.4, 1.0, 0.5, 0.8),
+    hud_label: Color::rgba(0.4, 1.0, 0.5, 0.5),
+    hud_bar: Color::rgba(0.3, 1.0, 0.4, 0.12),
+    power_up: Color::rgb(0.7, 1.0, 0.75),
+    mass: Color::rgba(0.4, 1.0, 0.5, 0.7),
+    particle_tint: Some(Color::rgb(0.4, 1.0, 0.5))
+};
+
+impl Theme {
+    pub const ALL: [&'static Theme; 5] = [&CLASSIC, &HIGH_CONTRAST, &DEUTERANOPIA, &PROTANOPIA, &MONOCHROME];
+
+    pub fn from_name(name: &str) -> Option<&'static Theme> {
+        Theme::ALL.iter().copied().find(|theme| theme.name == name)
+    }
+
+    pub fn next(&self) -> &'static Theme {
+        let index = Theme::ALL.iter().position(|theme| theme.name == self.name).unwrap_or(0);
+        Theme::ALL[(index + 1) % Theme::ALL.len()]
+    }
+
+    // the localization key for the palette's display name
+    pub fn key(&self) -> String {
+        format!("theme.{}", self.name)
+    }
+
+    pub fn tint(&self, color: Color) -> Color {
+        match self.particle_tint {
+            Some(tint) => {
+                let mut tinted = tint;
+                tinted.set_a(color.a());
+                tinted
+            }
+            None => color,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        CLASSIC
+    }
+}
+
+// restarting the title screen rebuilds every text and outline in the new colours
+pub fn switch_theme(keyboard_input: Res<Input<KeyCode>>, mut theme: ResMut<Theme>, mut preferences: ResMut<Preferences>, mut clear_color: ResMut<ClearColor>, mut state: ResMut<State<GameState>>) {
+    if !keyboard_input.just_pressed(KeyCode::T) {
+        return;
+    }
+    *theme = theme.next().clone();
+    clear_color.0 = theme.background;
+    preferences.theme = Some(theme.name.to_string());
+    if let Err(err) = preferences.save(PREFERENCES_PATH) {
+        warn!("could not save preferences: {}", err);
+    }
+    let _ = state.restart();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_palette_is_found_by_its_name() {
+        for theme in Theme::ALL {
+            assert_eq!(Theme::from_name(theme.name).unwrap().name, theme.name);
+        }
+        assert!(Theme::from_name("sepia").is_none());
+        assert_eq!(Theme::default().name, CLASSIC.name);
+    }
+
+    #[test]
+    fn cycling_visits_every_palette_and_comes_back() {
+        let mut theme: &Theme = &CLASSIC;
+        let mut names = Vec::new();
+        for _ in Theme::ALL {
+            names.push(theme.name);
+            theme = theme.next();
+        }
+        assert_eq!(theme.name, CLASSIC.name);
+        assert_eq!(names, Theme::ALL.map(|theme| theme.name));
+        assert_eq!(CLASSIC.key(), "theme.classic");
+    }
+
+    #[test]
+    fn a_tint_replaces_the_colour_but_keeps_its_alpha() {
+        let color = Color::rgba(1.0, 0.0, 0.0, 0.25);
+        assert_eq!(CLASSIC.tint(color), color);
+        let tint = MONOCHROME.particle_tint.unwrap();
+        assert_eq!(MONOCHROME.tint(color), Color::rgba(tint.r(), tint.g(), tint.b(), 0.25));
+    }
+}
@@ -0,0 +1,335 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::Radius;
+
+const BACK_OVERSHOOT: f32 = 1.70158;
+const BACK_OVERSHOOT_IN_OUT: f32 = BACK_OVERSHOOT * 1.525;
+const ELASTIC_PERIOD: f32 = 2.0 * PI / 3.0;
+const ELASTIC_PERIOD_IN_OUT: f32 = 2.0 * PI / 4.5;
+const BOUNCE_N1: f32 = 7.5625;
+const BOUNCE_D1: f32 = 2.75;
+
+// not every curve is in use, they're all kept to pick from when tuning effects
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Ease {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    QuartIn,
+    QuartOut,
+    QuartInOut,
+    QuintIn,
+    QuintOut,
+    QuintInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    CircIn,
+    CircOut,
+    CircInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+    // t^n and its mirror, for curves sharper than the named ones
+    PowIn(f32),
+    PowOut(f32),
+}
+
+fn pow_in(t: f32, n: f32) -> f32 {
+    t.powf(n)
+}
+
+fn pow_out(t: f32, n: f32) -> f32 {
+    1.0 - (1.0 - t).powf(n)
+}
+
+fn pow_in_out(t: f32, n: f32) -> f32 {
+    if t < 0.5 {
+        2f32.powf(n - 1.0) * t.powf(n)
+    } else {
+        1.0 - (2.0 - 2.0 * t).powf(n) / 2.0
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    if t < 1.0 / BOUNCE_D1 {
+        BOUNCE_N1 * t * t
+    } else if t < 2.0 / BOUNCE_D1 {
+        let t = t - 1.5 / BOUNCE_D1;
+        BOUNCE_N1 * t * t + 0.75
+    } else if t < 2.5 / BOUNCE_D1 {
+        let t = t - 2.25 / BOUNCE_D1;
+        BOUNCE_N1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / BOUNCE_D1;
+        BOUNCE_N1 * t * t + 0.984375
+    }
+}
+
+impl Ease {
+    // maps progress in [0, 1] to eased progress, every curve starts at 0 and ends at 1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Ease::Linear => t,
+            Ease::QuadIn => pow_in(t, 2.0),
+            Ease::QuadOut => pow_out(t, 2.0),
+            Ease::QuadInOut => pow_in_out(t, 2.0),
+            Ease::CubicIn => pow_in(t, 3.0),
+            Ease::CubicOut => pow_out(t, 3.0),
+            Ease::CubicInOut => pow_in_out(t, 3.0),
+            Ease::QuartIn => pow_in(t, 4.0),
+            Ease::QuartOut => pow_out(t, 4.0),
+            Ease::QuartInOut => pow_in_out(t, 4.0),
+            Ease::QuintIn => pow_in(t, 5.0),
+            Ease::QuintOut => pow_out(t, 5.0),
+            Ease::QuintInOut => pow_in_out(t, 5.0),
+            Ease::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Ease::SineOut => (t * PI / 2.0).sin(),
+            Ease::SineInOut => (1.0 - (t * PI).cos()) / 2.0,
+            Ease::ExpoIn => if t <= 0.0 { 0.0 } else { 2f32.powf(10.0 * t - 10.0) },
+            Ease::ExpoOut => if t >= 1.0 { 1.0 } else { 1.0 - 2f32.powf(-10.0 * t) },
+            Ease::ExpoInOut => {
+                if t <= 0.0 {
+                    0.0
+                } else if t >= 1.0 {
+                    1.0
+                } else if t < 0.5 {
+                    2f32.powf(20.0 * t - 10.0) / 2.0
+                } else {
+                    (2.0 - 2f32.powf(10.0 - 20.0 * t)) / 2.0
+                }
+            }
+            Ease::CircIn => 1.0 - (1.0 - t * t).sqrt(),
+            Ease::CircOut => (1.0 - (t - 1.0) * (t - 1.0)).sqrt(),
+            Ease::CircInOut => {
+                if t < 0.5 {
+                    (1.0 - (1.0 - 4.0 * t * t).sqrt()) / 2.0
+                } else {
+                    ((1.0 - (2.0 - 2.0 * t).powi(2)).sqrt() + 1.0) / 2.0
+                }
+            }
+            Ease::BackIn => (BACK_OVERSHOOT + 1.0) * t * t * t - BACK_OVERSHOOT * t * t,
+            Ease::BackOut => 1.0 + (BACK_OVERSHOOT + 1.0) * (t - 1.0).powi(3) + BACK_OVERSHOOT * (t - 1.0).powi(2),
+            Ease::BackInOut => {
+                if t < 0.5 {
+                    (2.0 * t).powi(2) * ((BACK_OVERSHOOT_IN_OUT + 1.0) * 2.0 * t - BACK_OVERSHOOT_IN_OUT) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((BACK_OVERSHOOT_IN_OUT + 1.0) * (2.0 * t - 2.0) + BACK_OVERSHOOT_IN_OUT) + 2.0) / 2.0
+                }
+            }
+            Ease::ElasticIn => {
+                if t <= 0.0 || t >= 1.0 {
+                    t
+                } else {
+                    -(2f32.powf(10.0 * t - 10.0)) * ((10.0 * t - 10.75) * ELASTIC_PERIOD).sin()
+                }
+            }
+            Ease::ElasticOut => {
+                if t <= 0.0 || t >= 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((10.0 * t - 0.75) * ELASTIC_PERIOD).sin() + 1.0
+                }
+            }
+            Ease::ElasticInOut => {
+                if t <= 0.0 || t >= 1.0 {
+                    t
+                } else if t < 0.5 {
+                    -(2f32.powf(20.0 * t - 10.0) * ((20.0 * t - 11.125) * ELASTIC_PERIOD_IN_OUT).sin()) / 2.0
+                } else {
+                    2f32.powf(10.0 - 20.0 * t) * ((20.0 * t - 11.125) * ELASTIC_PERIOD_IN_OUT).sin() / 2.0 + 1.0
+                }
+            }
+            Ease::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Ease::BounceOut => bounce_out(t),
+            Ease::BounceInOut => {
+                if t < 0.5 {
+                    (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+            Ease::PowIn(n) => pow_in(t, n),
+            Ease::PowOut(n) => pow_out(t, n),
+        }
+    }
+
+    pub fn lerp(self, from: f32, to: f32, t: f32) -> f32 {
+        from + (to - from) * self.apply(t)
+    }
+}
+
+pub fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    Color::rgba(
+        from.r() + (to.r() - from.r()) * t,
+        from.g() + (to.g() - from.g()) * t,
+        from.b() + (to.b() - from.b()) * t,
+        from.a() + (to.a() - from.a()) * t,
+    )
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum TweenTarget {
+    Radius { from: f32, to: f32 },
+    Translation { from: Vec3, to: Vec3 },
+    Rotation { from: Quat, to: Quat },
+    Scale { from: Vec3, to: Vec3 },
+    TextColor { from: Color, to: Color },
+    TextAlpha { from: f32, to: f32 },
+}
+
+// animates one property of its entity, then removes itself and sends a TweenCompleted
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Tween {
+    target: TweenTarget,
+    ease: Ease,
+    duration: f32,
+    delay: f32,
+    elapsed: f32
+}
+
+impl Tween {
+    pub fn new(target: TweenTarget, ease: Ease, duration: f32) -> Self {
+        Self { target, ease, duration, delay: 0.0, elapsed: 0.0 }
+    }
+
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            1.0
+        } else {
+            ((self.elapsed - self.delay) / self.duration).clamp(0.0, 1.0)
+        }
+    }
+}
+
+pub struct TweenCompleted(pub Entity);
+
+type TweenQuery<'w, 's> = Query<'w, 's, (Entity, &'static mut Tween, Option<&'static mut Radius>, Option<&'static mut Transform>, Option<&'static mut Text>)>;
+
+pub fn update_tweens(mut commands: Commands, mut query: TweenQuery, mut events: EventWriter<TweenCompleted>, time: Res<Time>) {
+    for (entity, mut tween, radius, transform, text) in &mut query {
+        tween.elapsed += time.delta_seconds();
+        if tween.elapsed < tween.delay {
+            continue;
+        }
+
+        let t = tween.progress();
+        let eased = tween.ease.apply(t);
+        match tween.target {
+            TweenTarget::Radius { from, to } => {
+                if let Some(mut radius) = radius {
+                    **radius = from + (to - from) * eased;
+                }
+            }
+            TweenTarget::Translation { from, to } => {
+                if let Some(mut transform) = transform {
+                    transform.translation = from.lerp(to, eased);
+                }
+            }
+            TweenTarget::Rotation { from, to } => {
+                if let Some(mut transform) = transform {
+                    transform.rotation = from.slerp(to, eased);
+                }
+            }
+            TweenTarget::Scale { from, to } => {
+                if let Some(mut transform) = transform {
+                    transform.scale = from.lerp(to, eased);
+                }
+            }
+            TweenTarget::TextColor { from, to } => {
+                if let Some(mut text) = text {
+                    for section in &mut text.sections {
+                        section.style.color = lerp_color(from, to, eased);
+                    }
+                }
+            }
+            TweenTarget::TextAlpha { from, to } => {
+                if let Some(mut text) = text {
+                    for section in &mut text.sections {
+                        section.style.color.set_a(from + (to - from) * eased);
+                    }
+                }
+            }
+        }
+
+        if t >= 1.0 {
+            commands.entity(entity).remove::<Tween>();
+            events.send(TweenCompleted(entity));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    const ALL: [Ease; 33] = [
+        Ease::Linear, Ease::QuadIn, Ease::QuadOut, Ease::QuadInOut, Ease::CubicIn, Ease::CubicOut, Ease::CubicInOut,
+        Ease::QuartIn, Ease::QuartOut, Ease::QuartInOut, Ease::QuintIn, Ease::QuintOut, Ease::QuintInOut,
+        Ease::SineIn, Ease::SineOut, Ease::SineInOut, Ease::ExpoIn, Ease::ExpoOut, Ease::ExpoInOut,
+        Ease::CircIn, Ease::CircOut, Ease::CircInOut, Ease::BackIn, Ease::BackOut, Ease::BackInOut,
+        Ease::ElasticIn, Ease::ElasticOut, Ease::ElasticInOut, Ease::BounceIn, Ease::BounceOut, Ease::BounceInOut,
+        Ease::PowIn(8.0), Ease::PowOut(8.0),
+    ];
+
+    fn assert_near(actual: f32, expected: f32, ease: Ease, t: f32) {
+        assert!((actual - expected).abs() < EPSILON, "{:?} at {} is {}, not {}", ease, t, actual, expected);
+    }
+
+    #[test]
+    fn every_curve_starts_at_0_and_ends_at_1() {
+        for ease in ALL {
+            assert_near(ease.apply(0.0), 0.0, ease, 0.0);
+            assert_near(ease.apply(1.0), 1.0, ease, 1.0);
+        }
+    }
+
+    #[test]
+    fn progress_outside_0_to_1_is_clamped() {
+        for ease in ALL {
+            assert_eq!(ease.apply(-0.5), ease.apply(0.0), "{:?} below 0", ease);
+            assert_eq!(ease.apply(1.5), ease.apply(1.0), "{:?} above 1", ease);
+        }
+    }
+
+    #[test]
+    fn bounce_out_lands_on_each_bounce() {
+        for (t, expected) in [(0.2, 0.3025), (0.5, 0.765625), (0.9, 0.988125)] {
+            assert_near(Ease::BounceOut.apply(t), expected, Ease::BounceOut, t);
+        }
+    }
+
+    #[test]
+    fn pow_curves_follow_their_exponent() {
+        for (t, expected) in [(0.5, 0.00390625), (0.9, 0.43046721)] {
+            assert_near(Ease::PowIn(8.0).apply(t), expected, Ease::PowIn(8.0), t);
+            assert_near(Ease::PowOut(8.0).apply(1.0 - t), 1.0 - expected, Ease::PowOut(8.0), 1.0 - t);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::view::{NoFrustumCulling, RenderLayers};
+use bevy::sprite::MaterialMesh2dBundle;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+pub const VECTOR_LINE_WIDTH: f32 = 1.0;
+
+// shapes are drawn back to front by layer
+pub const LAYER_BACKGROUND: f32 = 0.0;
+pub const LAYER_EFFECTS: f32 = 1.0;
+pub const LAYER_SHAPES: f32 = 2.0;
+
+pub const VECTOR_RENDER_LAYER: u8 = 1;       // only the world camera sees the batch, text stays on the default layer
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VectorVertex {
+    pub position: Vec2,
+    pub color: Color
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct VectorPath {
+    pub vertices: Vec<VectorVertex>,
+    pub closed: bool
+}
+
+impl VectorPath {
+    pub fn polyline(points: impl IntoIterator<Item = Vec2>, color: Color) -> Self {
+        let vertices = points.into_iter().map(|position| VectorVertex { position, color }).collect();
+        Self { vertices, closed: false }
+    }
+
+    pub fn polygon(points: impl IntoIterator<Item = Vec2>, color: Color) -> Self {
+        Self { closed: true, ..Self::polyline(points, color) }
+    }
+
+    pub fn line(start: Vec2, end: Vec2, color: Color) -> Self {
+        Self::polyline([start, end], color)
+    }
+
+    pub fn circle(center: Vec2, radius: f32, segments: u32, color: Color) -> Self {
+        Self::polygon(circle_points(radius, segments).into_iter().map(|point| center + point), color)
+    }
+
+    pub fn push(&mut self, position: Vec2, color: Color) {
+        self.vertices.push(VectorVertex { position, color });
+    }
+}
+
+pub fn circle_points(radius: f32, segments: u32) -> Vec<Vec2> {
+    (0..segments)
+        .map(|i| {
+            let angle = 2.0 * std::f32::consts::PI * (i as f32) / (segments as f32);
+            Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+// the same seed always gives the same outline
+pub fn irregular_circle_points(seed: u64, radius_min: f32, radius_max: f32, segments: u32) -> Vec<Vec2> {
+    let mut rng = Pcg32::seed_from_u64(seed);
+    (0..segments)
+        .map(|i| {
+            let angle = 2.0 * std::f32::consts::PI * (i as f32) / (segments as f32);
+            Vec2::new(angle.cos(), angle.sin()) * rng.gen_range(radius_min..radius_max)
+        })
+        .collect()
+}
+
+// the outline of an entity, kept between frames and placed by its Transform when batched
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct VectorShape {
+    pub paths: Vec<VectorPath>,
+    pub width: f32,
+    pub layer: f32
+}
+
+impl VectorShape {
+    pub fn new(path: VectorPath, layer: f32) -> Self {
+        Self { paths: vec![path], width: VECTOR_LINE_WIDTH, layer }
+    }
+}
+
+// paths in world space that only last for the frame they were added in, overlay paths are in screen space instead
+#[derive(Resource, Default)]
+pub struct VectorLines {
+    paths: Vec<(f32, VectorPath)>,
+    overlay: Vec<(f32, VectorPath)>
+}
+
+impl VectorLines {
+    pub fn path(&mut self, path: VectorPath, layer: f32) {
+        if path.vertices.len() > 1 {
+            self.paths.push((layer, path));
+        }
+    }
+
+    pub fn overlay(&mut self, path: VectorPath, layer: f32) {
+        if path.vertices.len() > 1 {
+            self.overlay.push((layer, path));
+        }
+    }
+
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Color) {
+        self.path(VectorPath::line(start.truncate(), end.truncate(), color), LAYER_EFFECTS);
+    }
+
+    pub fn circle(&mut self, position: Vec3, radius: f32, color: Color, segments: u32) {
+        self.path(VectorPath::circle(position.truncate(), radius, segments, color), LAYER_EFFECTS);
+    }
+}
+
+// triangle geometry ready to be copied into a mesh
+#[derive(Default, Debug)]
+pub struct VectorMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub colors: Vec<[f32; 4]>,
+    pub indices: Vec<u32>
+}
+
+impl VectorMesh {
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.colors.clear();
+        self.indices.clear();
+    }
+
+    // each segment becomes a quad `width` wide, blending between the colours at its ends
+    pub fn stroke(&mut self, path: &VectorPath, transform: &Transform, width: f32, layer: f32) {
+        let count = path.vertices.len();
+        if count < 2 {
+            return;
+        }
+        let segments = if path.closed { count } else { count - 1 };
+        for i in 0..segments {
+            let start = path.vertices[i];
+            let end = path.vertices[(i + 1) % count];
+            let start_position = transform.transform_point(start.position.extend(0.0)).truncate();
+            let end_position = transform.transform_point(end.position.extend(0.0)).truncate();
+            let offset = (end_position - start_position).perp().normalize_or_zero() * width / 2.0;
+            if offset == Vec2::ZERO {
+                continue;
+            }
+
+            let first = self.positions.len() as u32;
+            for (position, color) in [
+                (start_position + offset, start.color),
+                (start_position - offset, start.color),
+                (end_position + offset, end.color),
+                (end_position - offset, end.color),
+            ] {
+                self.positions.push(position.extend(layer).to_array());
+                self.colors.push(color.as_linear_rgba_f32());
+            }
+            self.indices.extend_from_slice(&[first, first + 1, first + 2, first + 2, first + 1, first + 3]);
+        }
+    }
+}
+
+#[derive(Resource)]
+struct VectorBatch {
+    mesh: Handle<Mesh>,
+    overlay_mesh: Handle<Mesh>,
+    geometry: VectorMesh
+}
+
+fn vector_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new());
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, Vec::<[f32; 4]>::new());
+    mesh.set_indices(Some(Indices::U32(Vec::new())));
+    mesh
+}
+
+// the overlay sits on the default layer so the fixed hud camera draws it over the world
+fn setup_vector_batch(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<ColorMaterial>>) {
+    let material = materials.add(ColorMaterial::from(Color::WHITE));
+    let mesh = meshes.add(vector_mesh());
+    let overlay_mesh = meshes.add(vector_mesh());
+    commands.spawn((MaterialMesh2dBundle {
+                        mesh: mesh.clone().into(),
+                        material: material.clone(),
+                        ..default()
+                    },
+                    NoFrustumCulling,
+                    RenderLayers::layer(VECTOR_RENDER_LAYER)));
+    commands.spawn((MaterialMesh2dBundle {
+                        mesh: overlay_mesh.clone().into(),
+                        material,
+                        ..default()
+                    },
+                    NoFrustumCulling));
+    commands.insert_resource(VectorBatch { mesh, overlay_mesh, geometry: VectorMesh::default() });
+}
+
+fn upload_vector_mesh(geometry: &mut VectorMesh, mesh: Option<&mut Mesh>) {
+    // an empty vertex buffer can't be uploaded, so keep one invisible triangle
+    if geometry.positions.is_empty() {
+        geometry.positions.extend_from_slice(&[[0.0; 3]; 3]);
+        geometry.colors.extend_from_slice(&[[0.0; 4]; 3]);
+        geometry.indices.extend_from_slice(&[0, 1, 2]);
+    }
+
+    if let Some(mesh) = mesh {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, geometry.positions.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, geometry.colors.clone());
+        mesh.set_indices(Some(Indices::U32(geometry.indices.clone())));
+    }
+}
+
+// builds every shape and this frame's lines into the one mesh
+fn batch_vector_shapes(query: Query<(&VectorShape, &Transform)>, mut lines: ResMut<VectorLines>, mut batch: ResMut<VectorBatch>, mut meshes: ResMut<Assets<Mesh>>) {
+    let world = Transform::IDENTITY;
+    let mut draws: Vec<(f32, &VectorPath, &Transform, f32)> = Vec::new();
+    for (shape, transform) in &query {
+        for path in &shape.paths {
+            draws.push((shape.layer, path, transform, shape.width));
+        }
+    }
+    for (layer, path) in &lines.paths {
+        draws.push((*layer, path, &world, VECTOR_LINE_WIDTH));
+    }
+    draws.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let batch = &mut *batch;
+    batch.geometry.clear();
+    for (layer, path, transform, width) in draws {
+        batch.geometry.stroke(path, transform, width, layer);
+    }
+    lines.paths.clear();
+    upload_vector_mesh(&mut batch.geometry, meshes.get_mut(&batch.mesh));
+
+    lines.overlay.sort_by(|a, b| a.0.total_cmp(&b.0));
+    batch.geometry.clear();
+    for (layer, path) in &lines.overlay {
+        batch.geometry.stroke(path, &world, VECTOR_LINE_WIDTH, *layer);
+    }
+    lines.overlay.clear();
+    upload_vector_mesh(&mut batch.geometry, meshes.get_mut(&batch.overlay_mesh));
+}
+
+pub struct VectorPlugin;
+
+impl Plugin for VectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VectorLines>()
+            .add_startup_system(setup_vector_batch)
+            .add_system_to_stage(CoreStage::PostUpdate, batch_vector_shapes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Vec<Vec2> {
+        vec![Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0), Vec2::new(10.0, 10.0), Vec2::new(0.0, 10.0)]
+    }
+
+    fn stroke(path: &VectorPath) -> VectorMesh {
+        let mut mesh = VectorMesh::default();
+        mesh.stroke(path, &Transform::IDENTITY, 2.0, LAYER_SHAPES);
+        mesh
+    }
+
+    #[test]
+    fn every_segment_is_a_quad() {
+        let open = stroke(&VectorPath::polyline(square(), Color::WHITE));
+        assert_eq!((open.positions.len(), open.colors.len(), open.indices.len()), (12, 12, 18));
+        let closed = stroke(&VectorPath::polygon(square(), Color::WHITE));
+        assert_eq!((closed.positions.len(), closed.colors.len(), closed.indices.len()), (16, 16, 24));
+        assert!(closed.indices.iter().all(|index| (*index as usize) < closed.positions.len()));
+        assert!(closed.positions.iter().all(|position| position[2] == LAYER_SHAPES));
+    }
+
+    #[test]
+    fn zero_length_segments_and_single_points_draw_nothing() {
+        let points = [Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0), Vec2::new(5.0, 0.0), Vec2::new(5.0, 0.0)];
+        let mesh = stroke(&VectorPath::polyline(points, Color::WHITE));
+        assert_eq!((mesh.positions.len(), mesh.indices.len()), (4, 6));
+        assert!(stroke(&VectorPath::polyline([Vec2::ONE], Color::WHITE)).positions.is_empty());
+        assert!(stroke(&VectorPath::line(Vec2::ONE, Vec2::ONE, Color::WHITE)).positions.is_empty());
+    }
+
+    #[test]
+    fn irregular_outlines_depend_only_on_their_seed() {
+        let outline = irregular_circle_points(42, 8.0, 12.0, 16);
+        assert_eq!(outline.len(), 16);
+        assert_eq!(outline, irregular_circle_points(42, 8.0, 12.0, 16));
+        assert_ne!(outline, irregular_circle_points(43, 8.0, 12.0, 16));
+        assert!(outline.iter().all(|point| (8.0..12.0).contains(&point.length())));
+    }
+}